@@ -0,0 +1,126 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use save_sync::archive::{Archive, HASH_BUFFER_SIZE};
+use save_sync::atomic;
+use save_sync::progress::NoProgress;
+use save_sync::workers::Workers;
+use std::fs;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use twox_hash::XxHash64;
+
+const SEED: u64 = 1_912_251_925_143;
+const FILE_COUNT: usize = 16;
+const FILE_SIZE: usize = 8 * 1024 * 1024;
+
+fn create_files(dir: &Path) -> Vec<PathBuf> {
+    (0..FILE_COUNT)
+        .map(|i| {
+            let path = dir.join(format!("{}.sav", i));
+            let bytes: Vec<u8> = (0..FILE_SIZE).map(|j| (i * 31 + j * 7) as u8).collect();
+            fs::write(&path, bytes).unwrap();
+            path
+        })
+        .collect()
+}
+
+/// How `Archive::calc_hash` used to work, which allocated a new Vec for every 16 KiB chunk
+fn calc_hash_allocating(path: &Path) -> u64 {
+    let chunk_size = 0x4000;
+    let mut hasher = XxHash64::with_seed(SEED);
+    let mut file = fs::File::open(path).unwrap();
+
+    loop {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        let n = file
+            .by_ref()
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)
+            .unwrap();
+
+        if n == 0 {
+            break;
+        }
+        hasher.write(&chunk);
+    }
+    hasher.finish()
+}
+
+fn hashing(c: &mut Criterion) {
+    let test_dir = TempDir::new().unwrap();
+    let paths = create_files(test_dir.path());
+
+    let mut group = c.benchmark_group("hashing");
+    group.throughput(Throughput::Bytes((FILE_COUNT * FILE_SIZE) as u64));
+    group.sample_size(10);
+
+    group.bench_function("sequential (allocating)", |b| {
+        b.iter(|| {
+            for path in &paths {
+                calc_hash_allocating(path);
+            }
+        })
+    });
+
+    group.bench_function("sequential (reused buffer)", |b| {
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+
+        b.iter(|| {
+            for path in &paths {
+                Archive::calc_hash_with(path, SEED, &mut buffer, &NoProgress).unwrap();
+            }
+        })
+    });
+
+    for threads in &[2, 4, 8] {
+        let workers = Workers::new(*threads).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("parallel", threads), &paths, |b, paths| {
+            b.iter(|| workers.hash_files(SEED, paths, &NoProgress))
+        });
+    }
+
+    group.finish();
+}
+
+fn copying(c: &mut Criterion) {
+    let test_dir = TempDir::new().unwrap();
+    let paths = create_files(test_dir.path());
+    let backup_dir = TempDir::new().unwrap();
+
+    let pairs: Vec<(PathBuf, PathBuf)> = paths
+        .iter()
+        .map(|path| {
+            (
+                path.clone(),
+                backup_dir.path().join(path.file_name().unwrap()),
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("copying");
+    group.throughput(Throughput::Bytes((FILE_COUNT * FILE_SIZE) as u64));
+    group.sample_size(10);
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            for (from, to) in &pairs {
+                atomic::copy(from, to).unwrap();
+            }
+        })
+    });
+
+    for threads in &[2, 4, 8] {
+        let workers = Workers::new(*threads).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("parallel", threads), &pairs, |b, pairs| {
+            b.iter(|| workers.copy_files(pairs, &NoProgress))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, hashing, copying);
+criterion_main!(benches);
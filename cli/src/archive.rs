@@ -1,12 +1,23 @@
 use anyhow::{anyhow, Context, Result};
-use change::{SaveUpdate, Type};
+use change::{SaveDiff, SaveUpdate, Type};
 use chrono::Utc;
 use options::*;
-use save_sync::archive::query::{FileQuery, SaveQuery};
-use save_sync::config::Config;
-use save_sync::models::{NewFile, NewSave, Save, User};
+use save_sync::archive::query::{ChunkQuery, ExecutableQuery, FileQuery, SaveQuery};
+use save_sync::chunking::{ChunkRef, ChunkStore, Chunker};
+use save_sync::diff;
+use save_sync::entry::Entry;
+use save_sync::lock::LockMode;
+use save_sync::ludusavi::{Game, GameFile};
+use save_sync::models::{
+    Chunk, File, FileType, NewChunk, NewExecutable, NewFile, NewSave, NewTag, NewUpdate, Save, User,
+};
+use save_sync::paths;
+use save_sync::process::{self, Process, ProcessError};
+use save_sync::progress::{NoProgress, Progress, Task};
+use save_sync::storage::{self, StorageBackend};
+use save_sync::workers::Workers;
 use save_sync::Archive as BaseArchive;
-use save_sync::Database;
+use save_sync::{Database, SaveSync};
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
@@ -14,12 +25,43 @@ use uuid::Uuid;
 #[derive(Debug, Copy, Clone)]
 pub struct Archive {}
 
+/// Where the files of a save are on disk, and where their backups are stored
+struct Roots<'a> {
+    save_path: &'a Path,
+    backup_path: &'a Path,
+}
+
+impl Roots<'_> {
+    /// The key the backup of `file_path` is stored under
+    fn key<P: AsRef<Path>>(&self, file_path: &P) -> Result<String> {
+        let key = storage::backup_key(self.save_path, self.backup_path, file_path.as_ref())?;
+
+        Ok(key)
+    }
+}
+
+/// What backing up a single path has produced
+struct BackedUp {
+    entry: Entry,
+    hash: Vec<u8>,
+    /// Only large files are split into chunks
+    chunks: Option<Vec<ChunkRef>>,
+}
+
+/// What checking the files of a save has found
+struct Checked {
+    changes: Vec<SaveUpdate>,
+    /// Unchanged files whose cached size / modification time is out of date
+    stale: Vec<(PathBuf, Entry)>,
+}
+
 impl Archive {
     pub fn create_save<P: AsRef<Path>>(
-        db: &Database,
+        ctx: &SaveSync,
         user: &User,
         path: &P,
         opt: SaveOptions,
+        progress: &dyn Progress,
     ) -> Result<()> {
         if !path.as_ref().exists() {
             let path_str = path.as_ref().to_string_lossy();
@@ -27,20 +69,21 @@ impl Archive {
             return Err(err);
         }
 
+        let db = ctx.db();
+
+        // Backing up a tracked save again would only leave a second copy of it behind in storage
+        if db.get_save(SaveQuery::new().with_path(path)).is_some() {
+            let path_str = path.as_ref().to_string_lossy();
+            let err = anyhow!("{} is already a tracked save.", path_str);
+            return Err(err);
+        }
+
         let time = Utc::now().naive_utc();
         let mut uuid_buf = Uuid::encode_buffer();
         let uuid = Uuid::new_v4().to_hyphenated().encode_lower(&mut uuid_buf);
-        let backup_pathbuf = Self::create_backup_path(path, &uuid)?;
-        let backup_path = backup_pathbuf.to_str().with_context(|| {
-            let path_str = backup_pathbuf.to_string_lossy();
-            format!("The backup path \"{}\" was not UTF-8 compliant.", path_str)
-        })?;
-        let save_path = path.as_ref().to_str().with_context(|| {
-            format!(
-                "{} is not a UTF-8 compliant path.",
-                path.as_ref().to_string_lossy()
-            )
-        })?;
+        let _lock = ctx.lock_save(uuid, LockMode::Exclusive)?;
+        let backup_path = paths::encode(&Self::create_backup_path(ctx, path, &uuid)?);
+        let save_path = paths::encode(path);
         let friendly_name = {
             match opt.friendly_name {
                 Some(name) => name,
@@ -50,8 +93,8 @@ impl Archive {
 
         let new_save = NewSave {
             friendly_name,
-            save_path,
-            backup_path,
+            save_path: &save_path,
+            backup_path: &backup_path,
             uuid,
             user_id: user.id,
             created_at: time,
@@ -62,61 +105,261 @@ impl Archive {
         // Having useless files in the backup folder is better than having a save in the db
         // which isn't actually backed up like we assume it to be
         // Therefore we copy files and only upon success do we actually write to db.
-        let files = Self::crawl(path);
-        Self::copy_save_files(&new_save, &files)?;
+        let files = Self::scan(path, progress);
+        let workers = ctx.workers()?;
+        let backend = ctx.storage_for(uuid, friendly_name)?;
+        let store = ChunkStore::new(backend.clone());
+        let backup_path = paths::decode(new_save.backup_path);
+        let roots = Roots {
+            save_path: path.as_ref(),
+            backup_path: &backup_path,
+        };
 
-        db.create_save(new_save);
+        // Stored chunks must not be removed before they're recorded in the database
+        let _chunk_lock = ctx.lock_chunks(LockMode::Shared)?;
+        let hashed = Self::backup_entries(
+            ctx,
+            &workers,
+            backend.as_ref(),
+            &store,
+            &roots,
+            &files,
+            progress,
+        )?;
+
+        db.create_save(new_save)?;
         let query = SaveQuery::new().with_uuid(uuid);
         let save = db.get_save(query).with_context(|| {
-            let path_str = new_save.save_path;
+            let path_str = path.as_ref().to_string_lossy();
             format!("Unable to query {} from db.", path_str)
         })?;
 
-        for file in files {
-            if file.is_file() {
-                // FIXME: Empty Directories are on disk but not tracked in Database.
-                Self::create_file(db, &save, &file)?;
+        for (file, backed_up) in files.iter().zip(hashed) {
+            Self::create_file(db, &save, file, &backed_up.entry, &backed_up.hash)?;
+
+            // New files don't have any old chunks to release
+            if let Some(chunks) = backed_up.chunks {
+                Self::replace_chunks(db, &save, file, &chunks)?;
             }
         }
 
+        for name in opt.executables {
+            let new_executable = NewExecutable {
+                name,
+                save_id: save.id,
+                created_at: time,
+                modified_at: time,
+            };
+
+            db.create_executable(new_executable);
+        }
+
+        for name in opt.tags {
+            let new_tag = NewTag {
+                name,
+                save_id: save.id,
+                created_at: time,
+                modified_at: time,
+            };
+
+            db.create_tag(new_tag);
+        }
+
         Ok(())
     }
 
-    pub fn delete_save(db: &Database, save: &Save) -> Result<()> {
-        // We'd rather have abandoned files than a save with missing backup files
-        // Therefore we should delete the save first, and then files later.
-        let backup_path = Path::new(&save.backup_path)
-            .parent()
-            .with_context(|| format!("Unable to determine parent of {}", save.backup_path))?;
-
-        // Delete Related files in database first due to Database Constraints
-        let files_query = FileQuery::new().with_save_id(save.id);
-        let option = db.get_files(files_query);
-
-        if let Some(files) = option {
-            for file in files {
-                let file_query = FileQuery::new().with_id(file.id);
-                db.delete_file(file_query)?;
+    /// Creates a save out of a game which was backed up by Ludusavi, copying the files Ludusavi stored into
+    /// the backup.
+    ///
+    /// Games whose files are spread across several directories become one save per directory. Files keep the
+    /// time of the Ludusavi backup they were read from, and every later Ludusavi backup is recorded as an update.
+    pub fn import_game(
+        ctx: &SaveSync,
+        user: &User,
+        game: &Game,
+        progress: &dyn Progress,
+    ) -> Result<Vec<Save>> {
+        let db = ctx.db();
+        let dirs = game.roots();
+
+        // Nothing is written unless every directory can become a save
+        for root in &dirs {
+            if !root.is_absolute() {
+                let err = anyhow!(
+                    "{} was backed up on another operating system.",
+                    root.to_string_lossy()
+                );
+                return Err(err);
+            }
+
+            if db.get_save(SaveQuery::new().with_path(root)).is_some() {
+                let err = anyhow!("{} is already a tracked save.", root.to_string_lossy());
+                return Err(err);
             }
         }
 
+        let now = Utc::now().naive_utc();
+        let times = game.backups.iter().filter_map(|backup| backup.when);
+        let first_backup = times.clone().min().unwrap_or(now);
+        let last_backup = times.max().unwrap_or(now);
+        let mut saves = vec![];
+
+        for (i, root) in dirs.iter().enumerate() {
+            let friendly_name = match dirs.len() {
+                1 => game.name.clone(),
+                _ => format!("{} ({})", game.name, i + 1),
+            };
+
+            let mut uuid_buf = Uuid::encode_buffer();
+            let uuid = Uuid::new_v4().to_hyphenated().encode_lower(&mut uuid_buf);
+            let _lock = ctx.lock_save(uuid, LockMode::Exclusive)?;
+            let backup_path = Self::create_backup_path(ctx, root, uuid)?;
+            let files: Vec<&GameFile> = game
+                .files
+                .iter()
+                .filter(|file| file.path.starts_with(root))
+                .collect();
+
+            let backend = ctx.storage_for(uuid, &friendly_name)?;
+            let store = ChunkStore::new(backend.clone());
+            let roots = Roots {
+                save_path: root,
+                backup_path: &backup_path,
+            };
+
+            // Stored chunks must not be removed before they're recorded in the database
+            let _chunk_lock = ctx.lock_chunks(LockMode::Shared)?;
+            let hashed =
+                Self::import_files(ctx, backend.as_ref(), &store, &roots, &files, progress)?;
+
+            let save_path = paths::encode(root);
+            let backup_path = paths::encode(&backup_path);
+            db.create_save(NewSave {
+                friendly_name: &friendly_name,
+                save_path: &save_path,
+                backup_path: &backup_path,
+                uuid,
+                user_id: user.id,
+                created_at: first_backup,
+                modified_at: last_backup,
+            })?;
+
+            let save = db
+                .get_save(SaveQuery::new().with_uuid(uuid))
+                .with_context(|| format!("Unable to query {} from db.", save_path))?;
+
+            for (file, backed_up) in files.iter().zip(hashed) {
+                let file_path = paths::encode(&file.path);
+                let time = file.backed_up_at.unwrap_or(last_backup);
+                let entry = backed_up.entry;
+
+                db.create_file(NewFile {
+                    file_path: &file_path,
+                    file_hash: &backed_up.hash,
+                    save_id: save.id,
+                    created_at: time,
+                    modified_at: time,
+                    file_type: entry.file_type.into(),
+                    link_target: None,
+                    permissions: entry.permissions,
+                    file_mtime: entry.mtime,
+                    file_size: entry.size,
+                })?;
+
+                // New files don't have any old chunks to release
+                if let Some(chunks) = backed_up.chunks {
+                    Self::replace_chunks(db, &save, &file.path, &chunks)?;
+                }
+            }
+
+            // The first backup is the one the save was created from
+            for backup in game.backups.iter().skip(1) {
+                let files_changed = backup
+                    .files
+                    .iter()
+                    .filter(|path| path.starts_with(root))
+                    .count();
+
+                if files_changed > 0 {
+                    db.create_update(NewUpdate {
+                        save_id: save.id,
+                        files_changed: files_changed as i32,
+                        created_at: backup.when.unwrap_or(last_backup),
+                    });
+                }
+            }
+
+            saves.push(save);
+        }
+
+        Ok(saves)
+    }
+
+    pub fn delete_save(ctx: &SaveSync, save: &Save) -> Result<()> {
+        // We'd rather have abandoned files than a save with missing backup files
+        // Therefore we should delete the save first, and then files later.
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Exclusive)?;
+        let db = ctx.db();
+        let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+
+        // Chunks may be shared with other saves, so we have to know which ones belonged to this save
+        // before the database forgets about them
+        let store = ChunkStore::new(backend.clone());
+        let chunks = Self::save_chunks(db, save);
+
+        // Files, executables, tags and notes are deleted along with the save
+        let _chunk_lock = ctx.lock_chunks(LockMode::Exclusive)?;
         let save_query = SaveQuery::new().with_id(save.id);
         db.delete_save(save_query)?;
+        Self::release_chunks(ctx, save, &store, &chunks)?;
 
-        // Now Delete the Files on disk
-        fs::remove_dir_all(backup_path)?;
+        // Now Delete the Files in storage
+        backend.delete_prefix(&format!("{}/", save.uuid))?;
         Ok(())
     }
 
-    pub fn update_save(db: &Database, save: &Save) -> Result<Option<String>> {
-        let changes = Self::check_save(db, save)?;
-        let backup_path = Path::new(&save.backup_path);
+    pub fn update_save(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: UpdateOptions,
+        progress: &dyn Progress,
+    ) -> Result<Option<String>> {
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Exclusive)?;
+        let db = ctx.db();
+
+        if !opt.force {
+            // Copying a save while the game is writing to it leaves us with a corrupted backup
+            Self::ensure_not_running(db, save, "backing up")?;
+        }
+
+        let check = CheckOptions {
+            paranoid: opt.paranoid,
+        };
+        let Checked { changes, stale } = Self::check_files(ctx, save, check, progress)?;
+        let save_path = paths::decode(&save.save_path);
+        let backup_path = paths::decode(&save.backup_path);
+        let roots = Roots {
+            save_path: &save_path,
+            backup_path: &backup_path,
+        };
         let mut changelog = String::new();
 
+        for (file_path, entry) in &stale {
+            // The contents are the same, so remember the new metadata to skip hashing next time
+            Self::refresh_file(db, save, file_path, entry)?;
+        }
+
         if changes.is_empty() {
             return Ok(None);
         }
 
+        let files_changed = changes.len() as i32;
+
+        // Missing files are dealt with first, so that everything else can be backed up in parallel
+        let mut backups = vec![];
+        let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+        let store = ChunkStore::new(backend.clone());
+
         for log in changes {
             let file_path = log.path;
             match log.change {
@@ -127,37 +370,244 @@ impl Archive {
                     ));
 
                     //TODO: Be a bit more careful about deleting files
-                    let query = FileQuery::new().with_path(&file_path);
-
+                    let query = FileQuery::new().with_save_id(save.id).with_path(&file_path);
+                    let file = db.get_file(query);
+                    let chunks = file
+                        .as_ref()
+                        .and_then(|file| db.get_chunks(ChunkQuery::new().with_file_id(file.id)))
+                        .unwrap_or_default();
+
+                    // The chunks of the file are deleted along with it
+                    let _chunk_lock = ctx.lock_chunks(LockMode::Exclusive)?;
+                    let query = FileQuery::new().with_save_id(save.id).with_path(&file_path);
                     db.delete_file(query)?;
-                    let backup_path = Self::get_backup_path(&file_path, &backup_path)?;
-                    fs::remove_file(backup_path)?;
+                    Self::release_chunks(ctx, save, &store, &chunks)?;
+
+                    // Directories and symlinks only live in the database
+                    if file.map(|file| Entry::from_file(&file).file_type) == Some(FileType::File) {
+                        backend.delete(&roots.key(&file_path)?)?;
+                    }
                 }
+                change => backups.push((change, file_path)),
+            }
+        }
+
+        let workers = ctx.workers()?;
+        let paths: Vec<&PathBuf> = backups.iter().map(|(_, path)| path).collect();
+        let mut released = vec![];
+
+        // Stored chunks must not be removed before they're recorded in the database
+        let chunk_lock = ctx.lock_chunks(LockMode::Shared)?;
+        let hashed = Self::backup_entries(
+            ctx,
+            &workers,
+            backend.as_ref(),
+            &store,
+            &roots,
+            &paths,
+            progress,
+        )?;
+
+        for ((change, file_path), backed_up) in backups.iter().zip(hashed) {
+            let BackedUp {
+                entry,
+                hash,
+                chunks,
+            } = backed_up;
+
+            match change {
                 Type::New => {
                     changelog.push_str(&format!("\nNew: {}", file_path.to_string_lossy()));
-
-                    Self::copy_file_to_backup_dir(&backup_path, &file_path)?;
-                    Self::create_file(db, save, &file_path)?;
+                    Self::create_file(db, save, file_path, &entry, &hash)?;
                 }
-                Type::Update => {
+                _ => {
                     changelog.push_str(&format!("\nUpdated: {}", file_path.to_string_lossy()));
-
-                    Self::copy_file_to_backup_dir(&backup_path, &file_path)?;
-                    Self::update_file(db, &file_path)?;
+                    Self::update_file(db, save, file_path, &entry, &hash)?;
                 }
             }
+
+            // A file which shrank below the chunk threshold no longer needs its old chunks
+            let chunks = chunks.unwrap_or_default();
+            released.extend(Self::replace_chunks(db, save, file_path, &chunks)?);
+        }
+
+        db.create_update(NewUpdate {
+            save_id: save.id,
+            files_changed,
+            created_at: Utc::now().naive_utc(),
+        });
+        drop(chunk_lock);
+
+        // The backup is complete by now, so old chunks which can't be released only waste space
+        if !released.is_empty() {
+            let _chunk_lock = ctx.lock_chunks(LockMode::Exclusive)?;
+            Self::release_chunks(ctx, save, &store, &released)?;
         }
 
         Ok(Some(changelog))
     }
 
-    pub fn check_save(db: &Database, save: &Save) -> Result<Vec<SaveUpdate>> {
+    /// Renames `save`, moves it to another path or hands it to another user.
+    ///
+    /// Before a save is moved, every file in its backup is hashed at the new path, so that a typo doesn't leave
+    /// the save pointing at the wrong files. Unless `opt.force` is set, any difference is an error.
+    pub fn edit_save(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: EditOptions,
+        progress: &dyn Progress,
+    ) -> Result<()> {
+        use save_sync::models::EditSave;
+
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Exclusive)?;
+        let db = ctx.db();
+
+        if let Some(name) = opt.friendly_name {
+            let query = SaveQuery::new().with_friendly_name(name);
+            let others = db.get_saves(query).unwrap_or_default();
+
+            if others.iter().any(|other| other.id != save.id) {
+                return Err(anyhow!("There already is a save called \"{}\".", name));
+            }
+
+            // Backends are assigned by name, so the backups would be stranded in the old backend
+            if ctx
+                .config()
+                .rename_changes_storage(&save.uuid, &save.friendly_name, name)
+            {
+                return Err(anyhow!(
+                    "Renaming the save to \"{}\" would move it to another storage backend. Assign its UUID ({}) to a backend in save_backends first.",
+                    name,
+                    save.uuid
+                ));
+            }
+        }
+
+        let save_path = match opt.save_path {
+            Some(path) => {
+                let mismatched = Self::verify_relocation(ctx, save, path, progress)?;
+
+                if let Some(first) = mismatched.first().filter(|_| !opt.force) {
+                    return Err(anyhow!(
+                        "{} files in {} don't match the backup, like {}. Use --force to move the save anyway.",
+                        mismatched.len(),
+                        path.to_string_lossy(),
+                        first.to_string_lossy()
+                    ));
+                }
+
+                Some(paths::encode(&path))
+            }
+            None => None,
+        };
+
+        let edit = EditSave {
+            id: save.id,
+            friendly_name: opt.friendly_name,
+            save_path: save_path.as_deref(),
+            user_id: opt.owner.map(|user| user.id),
+            modified_at: Utc::now().naive_utc(),
+        };
+
+        db.update_save(edit);
+        Ok(())
+    }
+
+    /// Hashes every file in the backup of `save` as if the save were at `path` instead.
+    /// Returns the paths which are missing or don't match the backup
+    fn verify_relocation(
+        ctx: &SaveSync,
+        save: &Save,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<Vec<PathBuf>> {
+        let db = ctx.db();
+
+        if !path.exists() {
+            return Err(anyhow!(
+                "{} does not exist on disk.",
+                path.to_string_lossy()
+            ));
+        }
+
+        if let Some(other) = db.get_save(SaveQuery::new().with_path(&path)) {
+            if other.id != save.id {
+                let path_str = path.to_string_lossy();
+                return Err(anyhow!("{} is already tracked by another save.", path_str));
+            }
+        }
+
+        let old_root = paths::decode(&save.save_path);
+        let files = db
+            .get_files(FileQuery::new().with_save_id(save.id))
+            .unwrap_or_default();
+
+        let mut moved = vec![];
+        let mut total = 0;
+
+        for file in &files {
+            let file_path = path.join(paths::decode(&file.file_path).strip_prefix(&old_root)?);
+            total += file.file_size.unwrap_or(0) as u64;
+            moved.push((file_path, file));
+        }
+
+        let workers = ctx.workers()?;
+        let seed = ctx.hash_seed();
+        let opt = CheckOptions { paranoid: true };
+
+        progress.start(Task::Hashing, Some(total));
+        let checked = workers.map(&moved, |buffer, (file_path, expected)| {
+            // A file which can't be read doesn't match the backup either
+            let entry = Entry::read(file_path).ok()?;
+            let (is_changed, _) =
+                Self::check_entry(file_path, expected, &entry, seed, buffer, opt, progress).ok()?;
+
+            Some(is_changed)
+        });
+        progress.finish(Task::Hashing);
+
+        let mismatched = moved
+            .into_iter()
+            .zip(checked)
+            .filter(|(_, check)| *check != Some(false))
+            .map(|((file_path, _), _)| file_path)
+            .collect();
+
+        Ok(mismatched)
+    }
+
+    /// Lists every file in `save` which is new, missing or has changed since the last backup.
+    ///
+    /// Unless `opt.paranoid` is set, files whose size and modification time match the last backup are not hashed.
+    pub fn check_save(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: CheckOptions,
+        progress: &dyn Progress,
+    ) -> Result<Vec<SaveUpdate>> {
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Shared)?;
+        let checked = Self::check_files(ctx, save, opt, progress)?;
+
+        Ok(checked.changes)
+    }
+
+    /// `check_save` for callers which already hold the lock of `save`
+    ///
+    /// Checking never writes to the database, so out of date metadata is only returned for `update_save` to refresh.
+    fn check_files(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: CheckOptions,
+        progress: &dyn Progress,
+    ) -> Result<Checked> {
         use std::collections::HashMap;
 
+        let db = ctx.db();
         let mut result = vec![];
         let query = FileQuery::new().with_save_id(save.id);
         let tracked = db.get_files(query).with_context(|| {
-            let path = &save.save_path;
+            let path = paths::decode(&save.save_path);
+            let path = path.to_string_lossy();
             let name = &save.friendly_name;
 
             if name.is_empty() {
@@ -167,61 +617,300 @@ impl Archive {
             }
         })?;
 
-        let path = Path::new(&save.save_path);
-        let current = Self::crawl(&path);
+        let path = paths::decode(&save.save_path);
+        let current = Self::scan(&path, progress);
 
         // Check For Missing & Build
-        let mut tracked_hash_map = HashMap::new();
+        let mut tracked_map = HashMap::new();
 
         for file in tracked {
-            // While we're at it, build a HashMap
-            // FIXME: Can we do this with less allocations?
-            tracked_hash_map.insert(file.file_path.clone(), file.file_hash.clone());
-
             // if current tracked file does not match any on disk
             if !current.iter().any(|path| file == **path) {
                 result.push(SaveUpdate {
                     change: Type::Missing,
-                    path: PathBuf::from(file.file_path),
+                    path: paths::decode(&file.file_path),
                 })
             }
+
+            // While we're at it, build a HashMap
+            tracked_map.insert(file.file_path.clone(), file);
         }
 
-        for file_path in current {
-            if file_path.is_file() {
-                let file_str = file_path.to_str().context(format!(
-                    "Unable to convert {} to a UTF-8 String",
-                    file_path.to_string_lossy()
-                ))?;
+        let mut known = vec![];
+        let mut total = 0;
 
-                match tracked_hash_map.get(file_str) {
-                    Some(expected) => {
-                        let actual = {
-                            let num = BaseArchive::calc_hash(&file_path)?;
-                            BaseArchive::u64_to_byte_vec(num)?
-                        };
+        for file_path in &current {
+            if let Some(expected) = tracked_map.get(&paths::encode(file_path)) {
+                let entry = Entry::read(file_path)?;
 
-                        if actual != *expected {
-                            result.push(SaveUpdate {
-                                change: Type::Update,
-                                path: file_path,
-                            })
-                        }
-                    }
-                    None => result.push(SaveUpdate {
-                        change: Type::New,
+                if opt.paranoid || !entry.matches_cached(expected) {
+                    total += entry.size.unwrap_or(0) as u64;
+                }
+
+                known.push((file_path, expected, entry));
+            }
+        }
+
+        // Hashing is by far the most expensive part, so every tracked file is hashed in parallel
+        let workers = ctx.workers()?;
+        let seed = ctx.hash_seed();
+
+        progress.start(Task::Hashing, Some(total));
+        let checked = workers.map(&known, |buffer, (file_path, expected, entry)| {
+            Self::check_entry(file_path, expected, entry, seed, buffer, opt, progress)
+        });
+        progress.finish(Task::Hashing);
+
+        let mut changed = vec![];
+        let mut stale = vec![];
+
+        for ((file_path, _, _), check) in known.iter().zip(checked) {
+            let (is_changed, stale_entry) = check?;
+
+            if let Some(entry) = stale_entry {
+                stale.push((file_path.to_path_buf(), entry));
+            }
+
+            changed.push(is_changed);
+        }
+
+        let mut changed = changed.into_iter();
+
+        for file_path in current {
+            if tracked_map.contains_key(&paths::encode(&file_path)) {
+                // known is in the same order as current, so this lines up
+                if changed.next().unwrap_or(false) {
+                    result.push(SaveUpdate {
+                        change: Type::Update,
                         path: file_path,
-                    }),
+                    })
                 }
+            } else {
+                result.push(SaveUpdate {
+                    change: Type::New,
+                    path: file_path,
+                })
             }
         }
 
+        Ok(Checked {
+            changes: result,
+            stale,
+        })
+    }
+
+    /// Determines whether `file_path` has changed since `expected` was backed up.
+    ///
+    /// If the contents are unchanged but the cached size / modification time is out of date,
+    /// the freshly read metadata is returned as well.
+    fn check_entry(
+        file_path: &Path,
+        expected: &File,
+        entry: &Entry,
+        seed: u64,
+        buffer: &mut [u8],
+        opt: CheckOptions,
+        progress: &dyn Progress,
+    ) -> Result<(bool, Option<Entry>)> {
+        let is_meta_changed =
+            entry.file_type != expected.kind() || entry.permissions != expected.permissions;
+
+        if !opt.paranoid && entry.matches_cached(expected) {
+            return Ok((is_meta_changed, None));
+        }
+
+        let actual = Self::calc_entry_hash(&file_path, entry, seed, buffer, progress)?;
+
+        if actual != expected.file_hash || is_meta_changed {
+            return Ok((true, None));
+        }
+
+        let is_stale = entry.file_type == FileType::File
+            && (entry.size != expected.file_size || entry.mtime != expected.file_mtime);
+
+        Ok((false, if is_stale { Some(entry.clone()) } else { None }))
+    }
+
+    /// Compares every file in `save` which has changed since the last backup to its backed up version.
+    ///
+    /// New and missing files are listed without a diff, as is anything which isn't a regular file on both sides.
+    /// If `opt.files` isn't empty, only files inside of one of them are compared.
+    pub fn diff_save(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: DiffOptions,
+        progress: &dyn Progress,
+    ) -> Result<Vec<SaveDiff>> {
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Shared)?;
+        let db = ctx.db();
+        let save_path = paths::decode(&save.save_path);
+        let check = CheckOptions {
+            paranoid: opt.paranoid,
+        };
+
+        let filters: Vec<PathBuf> = opt.files.iter().map(|path| save_path.join(path)).collect();
+        let changes = Self::check_files(ctx, save, check, progress)?
+            .changes
+            .into_iter()
+            .filter(|update| {
+                filters.is_empty() || filters.iter().any(|path| update.path.starts_with(path))
+            });
+
+        let mut result = vec![];
+
+        for update in changes {
+            let diff = match update.change {
+                Type::Update => {
+                    let query = FileQuery::new()
+                        .with_path(&update.path)
+                        .with_save_id(save.id);
+                    let file = db.get_file(query).with_context(|| {
+                        let path_str = update.path.to_string_lossy();
+                        format!(
+                            "Unable to retrieve file with path {} from the database.",
+                            path_str
+                        )
+                    })?;
+                    let entry = Entry::read(&update.path)?;
+
+                    if file.kind() == FileType::File && entry.file_type == FileType::File {
+                        let old = Self::read_backup(ctx, save, &file)?;
+                        let new = fs::read(&update.path)?;
+                        let name = update.path.to_string_lossy();
+                        let old_name = format!("{} (backup)", name);
+
+                        Some(diff::diff(&old, &new, &old_name, &name))
+                    } else {
+                        None
+                    }
+                }
+                Type::New | Type::Missing => None,
+            };
+
+            result.push(SaveDiff {
+                change: update.change,
+                path: update.path,
+                diff,
+            });
+        }
+
         Ok(result)
     }
 
-    pub fn old_check_save(db: &Database, save: &Save) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    /// Reads the backed up contents of `file`, whether it was split into chunks or stored whole
+    pub fn read_backup(ctx: &SaveSync, save: &Save, file: &File) -> Result<Vec<u8>> {
+        let db = ctx.db();
+        let save_path = paths::decode(&save.save_path);
+        let backup_path = paths::decode(&save.backup_path);
+        let roots = Roots {
+            save_path: &save_path,
+            backup_path: &backup_path,
+        };
+        let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+
+        match db.get_chunks(ChunkQuery::new().with_file_id(file.id)) {
+            Some(chunks) => {
+                let store = ChunkStore::new(backend);
+                Ok(store.read_file(&Self::chunk_refs(&chunks)?)?)
+            }
+            None => {
+                let key = roots.key(&paths::decode(&file.file_path))?;
+                Ok(backend.get(&key)?)
+            }
+        }
+    }
+
+    /// Copies every file in the backup of `save` back onto disk, recreating empty directories and symlinks
+    /// along with the permissions and modification times which were recorded during the last backup.
+    ///
+    /// Files on disk which aren't part of the backup are left alone.
+    /// Returns the paths which were restored.
+    pub fn restore_save(
+        ctx: &SaveSync,
+        save: &Save,
+        opt: RestoreOptions,
+        progress: &dyn Progress,
+    ) -> Result<Vec<PathBuf>> {
+        let _lock = ctx.lock_save(&save.uuid, LockMode::Exclusive)?;
+        let db = ctx.db();
+
+        if !opt.force {
+            // The game would overwrite whatever we restore as soon as it saves again
+            Self::ensure_not_running(db, save, "restoring")?;
+        }
+
+        let query = FileQuery::new().with_save_id(save.id);
+        let mut files = db.get_files(query).with_context(|| {
+            let path = paths::decode(&save.save_path);
+            let path = path.to_string_lossy();
+            format!("{} does not have any files associated with it.", path)
+        })?;
+
+        let save_path = paths::decode(&save.save_path);
+        let backup_path = paths::decode(&save.backup_path);
+        let roots = Roots {
+            save_path: &save_path,
+            backup_path: &backup_path,
+        };
+        let root = opt.target.unwrap_or(&save_path);
+
+        // Parents need to exist before their children can be restored
+        files.sort_by_key(|file| paths::decode(&file.file_path).components().count());
+        fs::create_dir_all(root)?;
+
+        let mut restored = vec![];
+        let mut directories = vec![];
+        let total = files.iter().filter_map(|file| file.file_size).sum::<i64>();
+        let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+        let store = ChunkStore::new(backend.clone());
+
+        progress.start(Task::Copying, Some(total as u64));
+
+        for file in files {
+            let file_path = paths::decode(&file.file_path);
+            let destination = root.join(file_path.strip_prefix(&save_path)?);
+            let entry = Entry::from_file(&file);
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            match entry.file_type {
+                FileType::File => match db.get_chunks(ChunkQuery::new().with_file_id(file.id)) {
+                    Some(chunks) => {
+                        let chunks = Self::chunk_refs(&chunks)?;
+                        store.restore_file(&chunks, &destination, progress)?;
+                    }
+                    None => {
+                        let key = roots.key(&file_path)?;
+                        backend.get_file(&key, &destination, progress)?;
+                    }
+                },
+                _ => entry.create(&destination)?,
+            }
+
+            if entry.file_type == FileType::Directory {
+                // Writing the contents of a directory changes its mtime, so this has to wait until the end
+                directories.push((destination.clone(), entry));
+            } else {
+                entry.restore_metadata(&destination)?;
+            }
+
+            restored.push(destination);
+        }
+
+        for (path, entry) in directories.iter().rev() {
+            entry.restore_metadata(path)?;
+        }
+
+        progress.finish(Task::Copying);
+        Ok(restored)
+    }
+
+    pub fn old_check_save(ctx: &SaveSync, save: &Save) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
         use std::collections::HashMap;
 
+        let db = ctx.db();
         let mut new_files: Vec<PathBuf> = vec![];
         let mut changed_files: Vec<PathBuf> = vec![];
 
@@ -230,7 +919,7 @@ impl Archive {
             if save.friendly_name.is_empty() {
                 format!(
                     "Save with path \"{}\" does not have any files associated with it.",
-                    save.save_path
+                    paths::decode(&save.save_path).to_string_lossy()
                 )
             } else {
                 format!(
@@ -246,20 +935,15 @@ impl Archive {
             tracked_files_map.insert(file.file_path, file.file_hash);
         }
 
-        let save_path = Path::new(&save.save_path);
-        let current_save_files = Self::crawl(&save_path);
+        let save_path = paths::decode(&save.save_path);
+        let current_save_files = Self::crawl(&save_path, &NoProgress);
 
         for file_path in current_save_files {
             if file_path.is_file() {
-                let string = file_path.to_str().with_context(|| {
-                    let path_str = file_path.to_string_lossy();
-                    format!("Unable to convert {} to a UTF-8 String", path_str)
-                })?;
-
-                match tracked_files_map.get(string) {
+                match tracked_files_map.get(&paths::encode(&file_path)) {
                     Some(expected) => {
                         let actual = {
-                            let hash_num = BaseArchive::calc_hash(&file_path)?;
+                            let hash_num = ctx.calc_hash(&file_path)?;
                             BaseArchive::u64_to_byte_vec(hash_num)?
                         };
 
@@ -275,36 +959,77 @@ impl Archive {
         Ok((new_files, changed_files))
     }
 
-    fn create_file<P: AsRef<Path>>(db: &Database, save: &Save, path: &P) -> Result<()> {
-        let file_path = path.as_ref().to_str().with_context(|| {
-            format!(
-                "{} is not a UTF-8 compliant path.",
-                path.as_ref().to_string_lossy()
-            )
-        })?;
+    /// Lists the running processes which belong to the executables associated with `save`
+    ///
+    /// On Operating Systems where process detection isn't supported this is always empty.
+    pub fn find_running_executables(db: &Database, save: &Save) -> Result<Vec<Process>> {
+        let query = ExecutableQuery::new().with_save_id(save.id);
+
+        match db.get_executables(query) {
+            Some(executables) => {
+                let names: Vec<&str> = executables.iter().map(|exe| exe.name.as_str()).collect();
+
+                match process::find_running(&names) {
+                    Err(ProcessError::Unsupported) => Ok(vec![]),
+                    result => Ok(result?),
+                }
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    fn ensure_not_running(db: &Database, save: &Save, action: &str) -> Result<()> {
+        let running = Self::find_running_executables(db, save)?;
+
+        match running.first() {
+            Some(process) => Err(anyhow!(
+                "{} (pid {}) is still running. Close it before {} this save, or use --force.",
+                process.name,
+                process.pid,
+                action
+            )),
+            None => Ok(()),
+        }
+    }
 
+    fn create_file<P: AsRef<Path>>(
+        db: &Database,
+        save: &Save,
+        path: &P,
+        entry: &Entry,
+        file_hash: &[u8],
+    ) -> Result<()> {
+        let file_path = paths::encode(path);
         let time = Utc::now().naive_utc();
-        let file_hash = &{
-            let num = BaseArchive::calc_hash(path)?;
-            BaseArchive::u64_to_byte_vec(num)?
-        };
+        let link_target = Self::link_target_text(entry);
 
         let new_file = NewFile {
-            file_path,
+            file_path: &file_path,
             file_hash,
             save_id: save.id,
             created_at: time,
             modified_at: time,
+            file_type: entry.file_type.into(),
+            link_target: link_target.as_deref(),
+            permissions: entry.permissions,
+            file_mtime: entry.mtime,
+            file_size: entry.size,
         };
 
-        db.create_file(new_file);
+        db.create_file(new_file)?;
         Ok(())
     }
 
-    fn update_file<P: AsRef<Path>>(db: &Database, path: &P) -> Result<()> {
+    fn update_file<P: AsRef<Path>>(
+        db: &Database,
+        save: &Save,
+        path: &P,
+        entry: &Entry,
+        file_hash: &[u8],
+    ) -> Result<()> {
         use save_sync::models::EditFile;
 
-        let query = FileQuery::new().with_path(path);
+        let query = FileQuery::new().with_path(path).with_save_id(save.id);
         let time = Utc::now().naive_utc();
         let original_file = db.get_file(query).with_context(|| {
             let path_str = path.as_ref().to_string_lossy();
@@ -313,15 +1038,17 @@ impl Archive {
                 path_str
             )
         })?;
-        let file_hash = &{
-            let hash_num = BaseArchive::calc_hash(path)?;
-            BaseArchive::u64_to_byte_vec(hash_num)?
-        };
+        let link_target = Self::link_target_text(entry);
 
         let edit = EditFile {
             id: original_file.id,
             file_hash,
             modified_at: time,
+            file_type: Some(entry.file_type.into()),
+            link_target: link_target.as_deref(),
+            permissions: entry.permissions,
+            file_mtime: entry.mtime,
+            file_size: entry.size,
         };
 
         db.update_file(edit);
@@ -329,108 +1056,340 @@ impl Archive {
         Ok(())
     }
 
-    fn create_backup_path<P: AsRef<Path>>(path: &P, uuid: &str) -> Result<PathBuf> {
-        let config = Config::static_config()
-            .map_err(|_| anyhow!("Unable to get a reference to the global config"))?; // ConfigError is not thread save FIXME: Remove this workaround.
-        let root_path = &config.data_location;
-        let name = path.as_ref().file_name().with_context(|| {
+    /// Caches the size and modification time of a file whose contents haven't changed since it was backed up.
+    ///
+    /// Unlike `update_file`, this leaves `modified_at` alone, since the backup itself is untouched.
+    fn refresh_file<P: AsRef<Path>>(
+        db: &Database,
+        save: &Save,
+        path: &P,
+        entry: &Entry,
+    ) -> Result<()> {
+        use save_sync::models::EditFile;
+
+        let query = FileQuery::new().with_path(path).with_save_id(save.id);
+        let original_file = db.get_file(query).with_context(|| {
             let path_str = path.as_ref().to_string_lossy();
-            format!("Unable to determine the name (last part) of {}", path_str)
+            format!(
+                "Unable to retrieve file with path {} from the database.",
+                path_str
+            )
         })?;
 
-        let backup_path = Path::new(root_path).join(uuid).join(name);
-        Ok(backup_path)
+        let edit = EditFile {
+            id: original_file.id,
+            file_hash: &original_file.file_hash,
+            modified_at: original_file.modified_at,
+            file_type: Some(original_file.file_type),
+            link_target: original_file.link_target.as_deref(),
+            permissions: original_file.permissions,
+            file_mtime: entry.mtime,
+            file_size: entry.size,
+        };
+
+        db.update_file(edit);
+
+        Ok(())
     }
 
-    fn crawl<P: AsRef<Path>>(path: &P) -> Vec<PathBuf> {
-        let mut files: Vec<PathBuf> = vec![];
-        let result = fs::read_dir(path);
+    /// Files are hashed by their contents, symlinks by their target and directories by nothing at all
+    fn calc_entry_hash<P: AsRef<Path>>(
+        path: &P,
+        entry: &Entry,
+        seed: u64,
+        buffer: &mut [u8],
+        progress: &dyn Progress,
+    ) -> Result<Vec<u8>> {
+        let num = match entry.file_type {
+            FileType::File => BaseArchive::calc_hash_with(path, seed, buffer, progress)?,
+            FileType::Directory => BaseArchive::calc_hash_from_bytes_with([], seed),
+            FileType::Symlink => {
+                let target = Self::link_target_text(entry).unwrap_or_default();
+                BaseArchive::calc_hash_from_bytes_with(target, seed)
+            }
+        };
 
-        match result {
-            Err(_) => files,
-            Ok(list) => {
-                let valid = list.map(|entry| entry.unwrap().path());
-                for path in valid {
-                    if path.is_dir() {
-                        files.append(&mut Self::crawl(&path))
-                    }
-                    files.push(path) // If we just want files, we can filter later.
+        Ok(BaseArchive::u64_to_byte_vec(num)?)
+    }
+
+    /// Copies every path into the backup and hashes it, both in parallel.
+    ///
+    /// The metadata and hash of every path are returned in the same order as `paths`.
+    ///
+    /// Files which are at least as large as the chunk threshold in the config are split into chunks instead,
+    /// and only chunks which aren't in `store` yet are written.
+    fn backup_entries<P>(
+        ctx: &SaveSync,
+        workers: &Workers,
+        backend: &dyn StorageBackend,
+        store: &ChunkStore,
+        roots: &Roots,
+        paths: &[P],
+        progress: &dyn Progress,
+    ) -> Result<Vec<BackedUp>>
+    where
+        P: AsRef<Path> + Sync,
+    {
+        let seed = ctx.hash_seed();
+        let threshold = ctx.config().chunk_threshold;
+        let chunker = Chunker::default();
+        let total: u64 = paths
+            .iter()
+            .filter_map(|path| fs::symlink_metadata(path).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        // Every file is read twice, once to copy it and once to hash it
+        progress.start(Task::Copying, Some(total));
+        progress.start(Task::Hashing, Some(total));
+
+        let result = workers
+            .map(paths, |buffer, path| {
+                let entry = Entry::read(path)?;
+                let is_large = entry.size.is_some_and(|size| size as u64 >= threshold);
+
+                if entry.file_type == FileType::File && is_large {
+                    let chunked = store.store_file(&chunker, path, seed, progress)?;
+
+                    // A full copy from before the file grew past the threshold would only waste space
+                    backend.delete(&roots.key(path)?)?;
+
+                    return Ok(BackedUp {
+                        entry,
+                        hash: BaseArchive::u64_to_byte_vec(chunked.hash)?,
+                        chunks: Some(chunked.chunks),
+                    });
                 }
-                files
-            }
-        }
+
+                // Directories and symlinks are recorded in the database, so only regular files are stored
+                if entry.file_type == FileType::File {
+                    let key = roots.key(path)?;
+                    backend.put_file(&key, path.as_ref(), progress)?;
+                }
+
+                let hash = Self::calc_entry_hash(path, &entry, seed, buffer, progress)?;
+
+                Ok(BackedUp {
+                    entry,
+                    hash,
+                    chunks: None,
+                })
+            })
+            .into_iter()
+            .collect();
+
+        progress.finish(Task::Hashing);
+        progress.finish(Task::Copying);
+        result
     }
 
-    fn copy_save_files<P: AsRef<Path>>(save: &NewSave, files: &[P]) -> Result<()> {
-        let backup_path = Path::new(save.backup_path);
+    /// Like `backup_entries`, but the files are read from where Ludusavi stored them instead of from where they
+    /// were backed up from
+    fn import_files(
+        ctx: &SaveSync,
+        backend: &dyn StorageBackend,
+        store: &ChunkStore,
+        roots: &Roots,
+        files: &[&GameFile],
+        progress: &dyn Progress,
+    ) -> Result<Vec<BackedUp>> {
+        let seed = ctx.hash_seed();
+        let threshold = ctx.config().chunk_threshold;
+        let chunker = Chunker::default();
+        let workers = ctx.workers()?;
+        let total: u64 = files
+            .iter()
+            .filter_map(|file| fs::metadata(&file.source).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        progress.start(Task::Copying, Some(total));
+        progress.start(Task::Hashing, Some(total));
+
+        let result = workers
+            .map(files, |buffer, file| {
+                let entry = Entry::read(&file.source)?;
+
+                if entry.size.is_some_and(|size| size as u64 >= threshold) {
+                    let chunked = store.store_file(&chunker, &file.source, seed, progress)?;
+
+                    return Ok(BackedUp {
+                        entry,
+                        hash: BaseArchive::u64_to_byte_vec(chunked.hash)?,
+                        chunks: Some(chunked.chunks),
+                    });
+                }
 
-        for file_path in files {
-            Self::copy_file_to_backup_dir(&backup_path, &file_path.as_ref())?;
-        }
+                backend.put_file(&roots.key(&file.path)?, &file.source, progress)?;
+                let hash = Self::calc_entry_hash(&file.source, &entry, seed, buffer, progress)?;
 
-        Ok(())
+                Ok(BackedUp {
+                    entry,
+                    hash,
+                    chunks: None,
+                })
+            })
+            .into_iter()
+            .collect();
+
+        progress.finish(Task::Hashing);
+        progress.finish(Task::Copying);
+        result
     }
 
-    fn get_backup_path<P: AsRef<Path>, Q: AsRef<Path>>(
-        file_path: &P,
-        backup_path: &Q,
-    ) -> Result<PathBuf> {
-        let common_component_name = backup_path.as_ref().file_name().with_context(|| {
-            let path_str = backup_path.as_ref().to_string_lossy();
-            format!("Unable to determine file / directory name of {}", path_str)
-        })?;
+    /// Makes the File at `file_path` consist of `chunks`, replacing whatever chunks it consisted of before.
+    ///
+    /// Returns the old chunks, which have to be released once the chunk lock isn't held anymore
+    fn replace_chunks(
+        db: &Database,
+        save: &Save,
+        file_path: &Path,
+        chunks: &[ChunkRef],
+    ) -> Result<Vec<Chunk>> {
+        let query = FileQuery::new().with_save_id(save.id).with_path(&file_path);
+        let file = db
+            .get_file(query)
+            .with_context(|| format!("Unable to query {} from db.", file_path.to_string_lossy()))?;
 
-        let mut base = PathBuf::new();
+        let time = Utc::now().naive_utc();
+        let hashes = chunks
+            .iter()
+            .map(|chunk| BaseArchive::u64_to_byte_vec(chunk.hash))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let new_chunks: Vec<NewChunk> = chunks
+            .iter()
+            .zip(&hashes)
+            .enumerate()
+            .map(|(position, (chunk, hash))| NewChunk {
+                file_id: file.id,
+                position: position as i32,
+                chunk_hash: hash,
+                chunk_size: chunk.size as i64,
+                created_at: time,
+                modified_at: time,
+            })
+            .collect();
+
+        // The new chunks have to be known before the old ones are dropped, since they may share some
+        let old = db.get_chunks(ChunkQuery::new().with_file_id(file.id));
+        db.delete_chunks(ChunkQuery::new().with_file_id(file.id));
+        db.create_chunks(&new_chunks);
+
+        Ok(old.unwrap_or_default())
+    }
 
-        for component in file_path.as_ref().components() {
-            base.push(component);
+    /// Every chunk of every File in `save`
+    fn save_chunks(db: &Database, save: &Save) -> Vec<Chunk> {
+        let files = db
+            .get_files(FileQuery::new().with_save_id(save.id))
+            .unwrap_or_default();
+
+        files
+            .iter()
+            .filter_map(|file| db.get_chunks(ChunkQuery::new().with_file_id(file.id)))
+            .flatten()
+            .collect()
+    }
 
-            if component.as_os_str() == common_component_name {
-                break;
+    /// Removes every chunk in `chunks` from the store of `save` which no File in that store uses anymore.
+    ///
+    /// The caller has to hold the chunk lock exclusively
+    fn release_chunks(
+        ctx: &SaveSync,
+        save: &Save,
+        store: &ChunkStore,
+        chunks: &[Chunk],
+    ) -> Result<()> {
+        for chunk in chunks {
+            if !ctx.is_chunk_used(save, &chunk.chunk_hash) {
+                store.remove(BaseArchive::byte_vec_to_u64(&chunk.chunk_hash)?)?;
             }
         }
 
-        let prefixless = file_path.as_ref().strip_prefix(base)?;
-        Ok(backup_path.as_ref().join(prefixless))
+        Ok(())
     }
 
-    fn copy_file_to_backup_dir<P: AsRef<Path>, Q: AsRef<Path>>(
-        backup_path: &P,
-        file_path: &Q,
-    ) -> Result<()> {
-        let backup_destination = Self::get_backup_path(file_path, backup_path)?;
+    fn chunk_refs(chunks: &[Chunk]) -> Result<Vec<ChunkRef>> {
+        chunks
+            .iter()
+            .map(|chunk| {
+                Ok(ChunkRef {
+                    hash: BaseArchive::byte_vec_to_u64(&chunk.chunk_hash)?,
+                    size: chunk.chunk_size as u64,
+                })
+            })
+            .collect()
+    }
 
-        if file_path.as_ref().is_dir() {
-            // We just want to make sure that directory exists and re-create it if it doesnt
-            if !backup_destination.exists() {
-                fs::create_dir_all(backup_destination)?;
-            }
-        } else {
-            // I assume if it's not a directory it's a file
-            let backup_destination_parent = backup_destination.parent().with_context(|| {
-                let path_str = backup_destination.to_string_lossy();
-                format!("Unable to determine parent of {}", path_str)
-            })?;
+    /// The target of a symlink, encoded the way it is stored in the database
+    fn link_target_text(entry: &Entry) -> Option<String> {
+        entry.link_target.as_ref().map(paths::encode)
+    }
 
-            if !backup_destination_parent.exists() {
-                // It's good to be on the safer side.
-                fs::create_dir_all(backup_destination_parent)?;
-            }
+    fn create_backup_path<P: AsRef<Path>>(ctx: &SaveSync, path: &P, uuid: &str) -> Result<PathBuf> {
+        let root_path = &ctx.config().data_location;
+        let name = path.as_ref().file_name().with_context(|| {
+            let path_str = path.as_ref().to_string_lossy();
+            format!("Unable to determine the name (last part) of {}", path_str)
+        })?;
 
-            fs::copy(file_path, backup_destination)?;
-        }
+        let backup_path = Path::new(root_path).join(uuid).join(name);
+        Ok(backup_path)
+    }
 
-        Ok(())
+    /// Crawls `path` while reporting every path that was found
+    fn scan<P: AsRef<Path>>(path: &P, progress: &dyn Progress) -> Vec<PathBuf> {
+        progress.start(Task::Scanning, None);
+        let files = Self::crawl(path, progress);
+        progress.finish(Task::Scanning);
+
+        files
+    }
+
+    fn crawl<P: AsRef<Path>>(path: &P, progress: &dyn Progress) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = vec![];
+        let result = fs::read_dir(path);
+
+        match result {
+            Err(_) => files,
+            Ok(list) => {
+                let valid = list.map(|entry| entry.unwrap().path());
+                for path in valid {
+                    progress.path(Task::Scanning, &path);
+
+                    // Symlinks are tracked, not followed
+                    let is_dir = fs::symlink_metadata(&path)
+                        .map(|metadata| metadata.is_dir())
+                        .unwrap_or(false);
+
+                    if is_dir {
+                        files.append(&mut Self::crawl(&path, progress))
+                    }
+                    files.push(path) // If we just want files, we can filter later.
+                }
+                files
+            }
+        }
     }
 }
 
 pub mod change {
+    use save_sync::diff::Diff;
     use std::path::PathBuf;
 
     pub struct SaveUpdate {
         pub change: Type,
         pub path: PathBuf,
     }
+
+    pub struct SaveDiff {
+        pub change: Type,
+        pub path: PathBuf,
+        /// Only changed regular files are compared
+        pub diff: Option<Diff>,
+    }
     pub enum Type {
         Update,
         New,
@@ -439,7 +1398,48 @@ pub mod change {
 }
 
 pub mod options {
+    use save_sync::models::User;
+    use std::path::Path;
+
     pub struct SaveOptions<'a> {
         pub friendly_name: Option<&'a str>,
+        pub executables: Vec<&'a str>,
+        pub tags: Vec<&'a str>,
+    }
+
+    #[derive(Debug, Default, Copy, Clone)]
+    pub struct UpdateOptions {
+        pub force: bool,
+        pub paranoid: bool,
+    }
+
+    #[derive(Debug, Default, Copy, Clone)]
+    pub struct CheckOptions {
+        /// Hash every file, even if its size and modification time haven't changed
+        pub paranoid: bool,
+    }
+
+    #[derive(Debug, Default, Clone)]
+    pub struct DiffOptions<'a> {
+        /// Hash every file, even if its size and modification time haven't changed
+        pub paranoid: bool,
+        /// The files or directories to compare, relative to the save path. Everything is compared if this is empty
+        pub files: Vec<&'a Path>,
+    }
+
+    #[derive(Debug, Default, Copy, Clone)]
+    pub struct EditOptions<'a> {
+        pub friendly_name: Option<&'a str>,
+        /// Where the save has been moved to
+        pub save_path: Option<&'a Path>,
+        pub owner: Option<&'a User>,
+        /// Move the save even if the files at `save_path` don't match the backup
+        pub force: bool,
+    }
+
+    #[derive(Debug, Default, Copy, Clone)]
+    pub struct RestoreOptions<'a> {
+        pub target: Option<&'a Path>,
+        pub force: bool,
     }
 }
@@ -1,4 +1,5 @@
 pub mod archive;
+pub mod progress;
 
 #[cfg(test)]
 mod tests {
@@ -1,20 +1,66 @@
-use clap::{App, Arg, ArgMatches, SubCommand};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
 use cli::archive::change::Type as ChangeType;
+use cli::archive::options::{
+    CheckOptions, DiffOptions, EditOptions, RestoreOptions, UpdateOptions,
+};
 use cli::archive::Archive;
-use save_sync::archive::query::{SaveQuery, UserQuery};
-use save_sync::config::Config;
-use save_sync::models::{NewUser, Save, User};
-use save_sync::ConfigManager;
+use cli::progress::BarProgress;
+use indicatif::HumanBytes;
+use save_sync::archive::query::{ExecutableQuery, NoteQuery, SaveQuery, TagQuery, UserQuery};
+use save_sync::config::layers::{Layers, Origin};
+use save_sync::config::{Config, ConfigError};
+use save_sync::diff::{BinaryDiff, Diff};
+use save_sync::doctor;
+use save_sync::ludusavi::{self, Game};
+use save_sync::models::{NewNote, NewTag, NewUser, Save, User};
+use save_sync::paths;
+use save_sync::schedule::{self, Installed, Interval, Schedule, Scheduler};
+use save_sync::stats::{self, UserStats};
 use save_sync::Database;
+use save_sync::{ConfigManager, SaveSync};
+use std::collections::HashSet;
 use std::path::Path;
+use std::process;
 
 fn main() {
-    let _manager = ConfigManager::default(); // Initialize Config
-
     let matches = App::new("Save Sync")
         .version("0.1.0")
         .author("paoda <musukarekai@gmail.com>")
         .about("Manages saved game data across platforms.")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("A config file which overrides every other config file and environment variable."),
+        )
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("The database to use instead of the configured one."),
+        )
+        .arg(
+            Arg::with_name("data-dir")
+                .long("data-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("The directory to store backups in instead of the configured one."),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Inspect the save-sync configuration.")
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Prints the configuration which is in effect.")
+                        .arg(
+                            Arg::with_name("origin")
+                                .long("origin")
+                                .help("Shows which file, environment variable or flag every value came from."),
+                        ),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("info")
                 .about("Display information about saved data.")
@@ -69,6 +115,28 @@ fn main() {
                         .takes_value(true)
                         .help("The friendly name of the saved data"),
                 )
+                .arg(
+                    Arg::with_name("exe")
+                        .short("e")
+                        .long("exe")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("The name of an executable which writes to the saved data"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .short("t")
+                        .long("tag")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "A tag to attach to the saved data (e.g. the platform or \"speedrun\")",
+                        ),
+                )
                 .arg(
                     Arg::with_name("path")
                         .help("The path which will be added")
@@ -77,7 +145,159 @@ fn main() {
                 ),
         )
         .subcommand(
-            SubCommand::with_name("list").about("Lists every tracked save directory / file"),
+            SubCommand::with_name("list")
+                .about("Lists every tracked save directory / file")
+                .arg(
+                    Arg::with_name("tag")
+                        .short("t")
+                        .long("tag")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .help("Only list saves which have been tagged with TAG"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Shows how much space every save takes up and how often it changes")
+                .arg(
+                    Arg::with_name("user")
+                        .short("u")
+                        .long("user")
+                        .value_name("USERNAME")
+                        .takes_value(true)
+                        .help("Only show the saves of USERNAME"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Searches the names, paths, tags and notes of every tracked save")
+                .arg(
+                    Arg::with_name("text")
+                        .help("The text to search for")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Lists, adds or removes the tags of a save.")
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help("The friendly name of the save"),
+                )
+                .arg(
+                    Arg::with_name("add")
+                        .short("a")
+                        .long("add")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A tag which will be attached to the save"),
+                )
+                .arg(
+                    Arg::with_name("remove")
+                        .short("r")
+                        .long("remove")
+                        .value_name("TAG")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A tag which will be removed from the save"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .help("The path of the save")
+                        .index(1)
+                        .required_unless("friendly"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .about("Renames a save, moves it to another path or hands it to another user.")
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help("The friendly name of the save"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help("The new friendly name of the save"),
+                )
+                .arg(
+                    Arg::with_name("new-path")
+                        .long("path")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Where the save has been moved to. The files there have to match the backup"),
+                )
+                .arg(
+                    Arg::with_name("owner")
+                        .long("owner")
+                        .value_name("USER")
+                        .takes_value(true)
+                        .help("The user who the save will belong to"),
+                )
+                .arg(Arg::with_name("force").long("force").help(
+                    "Move the save even if the files at the new path don't match the backup",
+                ))
+                .group(
+                    ArgGroup::with_name("changes")
+                        .args(&["name", "new-path", "owner"])
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .help("The path of the save")
+                        .index(1)
+                        .required_unless("friendly"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("note")
+                .about("Lists, adds or deletes the notes of a save.")
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help("The friendly name of the save"),
+                )
+                .arg(
+                    Arg::with_name("add")
+                        .short("a")
+                        .long("add")
+                        .value_name("TEXT")
+                        .takes_value(true)
+                        .help("Writes a new note about the save"),
+                )
+                .arg(
+                    Arg::with_name("delete")
+                        .short("d")
+                        .long("delete")
+                        .value_name("ID")
+                        .takes_value(true)
+                        .conflicts_with("add")
+                        .help("Deletes the note with the given ID"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .help("The path of the save")
+                        .index(1)
+                        .required_unless("friendly"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("update")
@@ -88,13 +308,72 @@ fn main() {
                         .long("friendly")
                         .value_name("NAME")
                         .takes_value(true)
-                        .help("The friendly name of the save which will be updated"),
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("The friendly name of a save which will be updated"),
+                )
+                .args(&selector_args())
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Back up the save even if its game is still running"),
+                )
+                .arg(
+                    Arg::with_name("wait")
+                        .short("w")
+                        .long("wait")
+                        .conflicts_with("force")
+                        .help("Wait for the game to exit instead of refusing to back up"),
+                )
+                .arg(Arg::with_name("paranoid").long("paranoid").help(
+                    "Hash every file, even if its size and modification time haven't changed",
+                ))
+                .arg(
+                    Arg::with_name("path")
+                        .help("The path of a save which will be updated. May be a glob, e.g. '/home/*/Saves/*'")
+                        .index(1)
+                        .multiple(true)
+                        .required_unless_one(&["friendly", "all", "user"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Restores a save from its backup.")
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("The friendly name of a save which will be restored"),
+                )
+                .args(&selector_args())
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .help("Restore into DIR instead of the original location of the save"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Restore the save even if its game is still running"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .short("y")
+                        .long("yes")
+                        .help("Restore several saves without asking first"),
                 )
                 .arg(
                     Arg::with_name("path")
-                        .help("The path of the save which will be updated")
+                        .help("The path of a save which will be restored. May be a glob, e.g. '/home/*/Saves/*'")
                         .index(1)
-                        .required_unless("friendly"),
+                        .multiple(true)
+                        .required_unless_one(&["friendly", "all", "user"]),
                 ),
         )
         .subcommand(
@@ -106,53 +385,244 @@ fn main() {
                         .long("friendly")
                         .value_name("NAME")
                         .takes_value(true)
-                        .help("The friendly name of the save that you want to check"),
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("The friendly name of a save that you want to check"),
                 )
+                .args(&selector_args())
+                .arg(Arg::with_name("paranoid").long("paranoid").help(
+                    "Hash every file, even if its size and modification time haven't changed",
+                ))
                 .arg(
                     Arg::with_name("path")
-                        .help("The path of the save that you want to check.")
+                        .help("The path of a save that you want to check. May be a glob, e.g. '/home/*/Saves/*'")
                         .index(1)
-                        .required_unless("friendly"),
+                        .multiple(true)
+                        .required_unless_one(&["friendly", "all", "user"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Looks for inconsistencies between the database and the backups, and repairs them.")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Repair every problem without asking first"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Show what has changed in a save since its last backup.")
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("The friendly name of a save that you want to compare"),
+                )
+                .args(&selector_args())
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only compare this file or directory. Relative to the save path"),
+                )
+                .arg(Arg::with_name("paranoid").long("paranoid").help(
+                    "Hash every file, even if its size and modification time haven't changed",
+                ))
+                .arg(
+                    Arg::with_name("path")
+                        .help("The path of a save that you want to compare. May be a glob, e.g. '/home/*/Saves/*'")
+                        .index(1)
+                        .multiple(true)
+                        .required_unless_one(&["friendly", "all", "user"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Imports backups which were made by other tools.")
+                .subcommand(
+                    SubCommand::with_name("ludusavi")
+                        .about("Imports every game in a Ludusavi backup directory.")
+                        .arg(
+                            Arg::with_name("game")
+                                .long("game")
+                                .value_name("NAME")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .help("Only import this game"),
+                        )
+                        .arg(
+                            Arg::with_name("dir")
+                                .help("The Ludusavi backup directory, or the folder of a single game in it.")
+                                .index(1)
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schedule")
+                .about("Backs up saves on a schedule, using a systemd user timer or cron.")
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .arg(
+                    Arg::with_name("every")
+                        .long("every")
+                        .value_name("INTERVAL")
+                        .takes_value(true)
+                        .required(true)
+                        .help("How often to back up, e.g. 30m, 6h, daily or weekly"),
+                )
+                .arg(
+                    Arg::with_name("friendly")
+                        .short("f")
+                        .long("friendly")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only back up this save. Every save is backed up if this is left out"),
+                )
+                .arg(
+                    Arg::with_name("cron")
+                        .long("cron")
+                        .help("Install a crontab line instead of a systemd user timer"),
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .long("print")
+                        .help("Print what would be installed without installing it"),
+                )
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Shows which schedule is installed."),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("Stops and removes the installed schedule."),
                 ),
         )
         .get_matches();
 
+    let layers = load_config(&matches).expect("Unable to load the save-sync configuration.");
+    let config = layers
+        .config()
+        .expect("Unable to load the save-sync configuration.");
+    Config::update(config).unwrap();
+
     match matches.subcommand() {
+        ("config", Some(sub_matches)) => show_config(&layers, sub_matches),
         ("add", Some(sub_matches)) => add_save(sub_matches),
         ("delete", Some(sub_matches)) => del_save(sub_matches),
         ("info", Some(sub_matches)) => get_save_info(sub_matches),
-        ("list", Some(_sub_matches)) => list_tracked_saves(),
+        ("list", Some(sub_matches)) => list_tracked_saves(sub_matches),
+        ("search", Some(sub_matches)) => search_saves(sub_matches),
+        ("stats", Some(sub_matches)) => show_stats(sub_matches),
+        ("tag", Some(sub_matches)) => edit_tags(sub_matches),
+        ("note", Some(sub_matches)) => edit_notes(sub_matches),
+        ("edit", Some(sub_matches)) => edit_save(sub_matches),
         ("update", Some(sub_matches)) => update_saves(sub_matches),
+        ("restore", Some(sub_matches)) => restore_save(sub_matches),
         ("check", Some(sub_matches)) => check_save(sub_matches),
+        ("diff", Some(sub_matches)) => diff_save(sub_matches),
+        ("doctor", Some(sub_matches)) => run_doctor(sub_matches),
+        ("import", Some(sub_matches)) => import_backups(sub_matches),
+        ("schedule", Some(sub_matches)) => schedule_updates(&matches, sub_matches),
         _ => {}
     }
 }
 
+/// Layers the `--config`, `--db` and `--data-dir` flags on top of every other source of configuration
+fn load_config(args: &ArgMatches) -> Result<Layers, ConfigError> {
+    let mut layers = Layers::discover()?;
+
+    if let Some(path) = args.value_of_os("config") {
+        let path = Path::new(path);
+        let origin = Origin::File(path.to_path_buf());
+
+        if !path.is_file() {
+            return Err(ConfigError::InvalidPath(path.to_string_lossy().to_string()));
+        }
+
+        layers = layers.with_file(&path, origin)?;
+    }
+
+    for (flag, key) in &[("db", "db_location"), ("data-dir", "data_location")] {
+        if let Some(path) = args.value_of_os(flag) {
+            let path = path
+                .to_str()
+                .ok_or_else(|| ConfigError::IllegalPath(path.to_string_lossy().to_string()))?;
+            let origin = Origin::Flag(format!("--{}", flag));
+
+            layers = layers.with_value(key, path.to_string().into(), origin);
+        }
+    }
+
+    Ok(layers)
+}
+
+fn show_config(layers: &Layers, args: &ArgMatches) {
+    let show_origin = match args.subcommand() {
+        ("show", Some(sub_matches)) => sub_matches.is_present("origin"),
+        _ => return eprintln!("{}", args.usage()),
+    };
+
+    for (key, value, origin) in layers.entries() {
+        if show_origin {
+            println!("{} = {}  # {}", key, value, origin);
+        } else {
+            println!("{} = {}", key, value);
+        }
+    }
+}
+
 // Maybe move these functions into a separate module?
 fn add_save(args: &ArgMatches) {
     use cli::archive::options::SaveOptions;
 
-    let config = Config::static_config().unwrap();
-    let path = args.value_of("path").unwrap(); // required
+    let ctx = SaveSync::from_global().unwrap();
+    let path = args.value_of_os("path").unwrap(); // required
 
-    let username = (&config.local_username).clone();
-    let db = Database::new(&config.db_location).unwrap();
-    let user = get_local_user(&db, &username);
+    let username = (&ctx.config().local_username).clone();
+    let user = get_local_user(ctx.db(), &username);
     let path = Path::new(path);
     let mut opt = SaveOptions {
         friendly_name: None,
+        executables: vec![],
+        tags: vec![],
     };
 
     if let Some(name) = args.value_of("friendly") {
         opt.friendly_name = Some(name)
     }
 
-    Archive::create_save(&db, &user, &path, opt).expect("Unable to create Save");
+    if let Some(names) = args.values_of("exe") {
+        opt.executables = names.collect();
+    }
+
+    if let Some(tags) = args.values_of("tag") {
+        opt.tags = tags.collect();
+    }
+
+    Archive::create_save(
+        &ctx,
+        &user,
+        &path,
+        opt,
+        BarProgress::for_terminal().as_ref(),
+    )
+    .expect("Unable to create Save");
 }
 
 fn del_save(args: &ArgMatches) {
-    let config = Config::static_config().unwrap();
-    let db = Database::new(&config.db_location).unwrap();
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
     let mut save: Option<Save> = None;
 
     if let Some(name) = args.value_of("friendly") {
@@ -164,7 +634,7 @@ fn del_save(args: &ArgMatches) {
             None => eprintln!("{} is not related to any save in the database.", name),
         }
     } else {
-        let path = args.value_of("path").unwrap(); // Required if friendly is not set
+        let path = args.value_of_os("path").unwrap(); // Required if friendly is not set
         let path = Path::new(path);
         let query = SaveQuery::new().with_path(&path);
         let option = db.get_save(query);
@@ -179,13 +649,13 @@ fn del_save(args: &ArgMatches) {
     }
 
     if let Some(save) = save {
-        Archive::delete_save(&db, &save).expect("Error while trying to delete save.");
+        Archive::delete_save(&ctx, &save).expect("Error while trying to delete save.");
     }
 }
 
 fn get_save_info(args: &ArgMatches) {
-    let config = Config::static_config().unwrap();
-    let db = Database::new(&config.db_location).unwrap();
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
     let mut save: Option<Save> = None;
 
     if let Some(name) = args.value_of("friendly") {
@@ -198,7 +668,7 @@ fn get_save_info(args: &ArgMatches) {
             None => eprintln!("There was no save labelled as \"{}\" in the db.", name),
         }
     } else {
-        let path = args.value_of("path").unwrap(); // Required if friendly is not set
+        let path = args.value_of_os("path").unwrap(); // Required if friendly is not set
         let path = Path::new(path);
 
         let query = SaveQuery::new().with_path(&path);
@@ -214,7 +684,7 @@ fn get_save_info(args: &ArgMatches) {
     }
 
     if let Some(save) = save {
-        println!("\"{}\"", save.save_path);
+        println!("\"{}\"", paths::decode(&save.save_path).display());
         println!("---");
 
         if save.friendly_name.is_empty() {
@@ -232,137 +702,993 @@ fn get_save_info(args: &ArgMatches) {
             None => println!("Belongs to: User #{}", save.user_id),
         }
 
+        let query = ExecutableQuery::new().with_save_id(save.id);
+
+        if let Some(executables) = db.get_executables(query) {
+            let names: Vec<&str> = executables.iter().map(|exe| exe.name.as_str()).collect();
+            println!("Executables: {}", names.join(", "));
+        }
+
+        if let Some(tags) = db.get_tags(TagQuery::new().with_save_id(save.id)) {
+            let names: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+            println!("Tags: {}", names.join(", "));
+        }
+
         println!("UUID: {}", save.uuid);
-        println!("Backup path: {}", save.backup_path);
+        println!(
+            "Backup path: {}",
+            paths::decode(&save.backup_path).display()
+        );
         println!("Created: {}", save.created_at);
         println!("Modified: {}", save.modified_at);
+
+        if let Some(notes) = db.get_notes(NoteQuery::new().with_save_id(save.id)) {
+            println!("Notes:");
+
+            for note in notes {
+                println!("  #{} ({}): {}", note.id, note.created_at, note.body);
+            }
+        }
     }
 }
 
-fn list_tracked_saves() {
-    let config = Config::static_config().unwrap();
-    let db = Database::new(&config.db_location).unwrap();
-    let user = get_local_user(&db, &config.local_username);
+fn list_tracked_saves(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+    let user = get_local_user(db, &ctx.config().local_username);
 
-    let query = SaveQuery::new().with_user_id(user.id);
-    let option = db.get_saves(query);
+    let mut query = SaveQuery::new().with_user_id(user.id);
 
-    match option {
-        Some(saves) => {
-            for save in saves {
-                let friendly_name = save.friendly_name;
-                let save_path = save.save_path;
-                let uuid = save.uuid;
-
-                if !friendly_name.is_empty() {
-                    print!("[{}]: ", friendly_name);
-                }
+    if let Some(tag) = args.value_of("tag") {
+        query = query.with_tag(tag);
+    }
 
-                println!("\"{}\" | {{{}}}", save_path, uuid);
-            }
-        }
-        None => eprintln!("No saves in database."),
+    match db.get_saves(query) {
+        Some(saves) => print_saves(saves),
+        None => match args.value_of("tag") {
+            Some(tag) => eprintln!("No saves are tagged with \"{}\".", tag),
+            None => eprintln!("No saves in database."),
+        },
     }
 }
 
-fn check_save(args: &ArgMatches) {
-    let config = Config::static_config().unwrap();
-    let db = Database::new(&config.db_location).unwrap();
-    let mut save: Option<Save> = None;
+fn search_saves(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+    let user = get_local_user(db, &ctx.config().local_username);
 
-    if let Some(name) = args.value_of("friendly") {
-        // Get save by friendly name.
-        let query = SaveQuery::new().with_friendly_name(name);
-        let option = db.get_save(query);
+    let text = args.value_of("text").unwrap(); // required
+    let query = SaveQuery::new().with_user_id(user.id).with_text(text);
 
-        match option {
-            Some(result) => save = Some(result),
-            None => eprintln!("There was no save labelled as \"{}\" in the db.", name),
-        }
-    } else {
-        let path = args.value_of("path").unwrap(); // Required unless friendly is set.
-        let path = Path::new(path);
+    match db.get_saves(query) {
+        Some(saves) => print_saves(saves),
+        None => eprintln!("No saves match \"{}\".", text),
+    }
+}
 
-        let query = SaveQuery::new().with_path(&path);
-        let option = db.get_save(query);
+fn print_saves(saves: Vec<Save>) {
+    for save in saves {
+        let friendly_name = save.friendly_name;
+        let save_path = paths::decode(&save.save_path);
+        let uuid = save.uuid;
 
-        match option {
-            Some(result) => save = Some(result),
-            None => eprintln!(
-                "\"{}\" is not a path which is stored in the database.",
-                path.to_string_lossy()
-            ),
+        if !friendly_name.is_empty() {
+            print!("[{}]: ", friendly_name);
         }
+
+        println!("\"{}\" | {{{}}}", save_path.display(), uuid);
     }
+}
 
-    if let Some(save) = save {
-        let changes =
-            Archive::check_save(&db, &save).expect("Failed to check the integrity of this save.");
+fn show_stats(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
 
-        if changes.is_empty() {
-            if save.friendly_name.is_empty() {
-                println!("No changes were detected in {}", save.save_path)
-            } else {
-                println!("{}'s backup is up to date.", save.friendly_name)
+    let users = match args.value_of("user") {
+        Some(username) => match ctx.db().get_user(UserQuery::new().with_username(username)) {
+            Some(user) => UserStats::collect(&ctx, user).map(|stats| vec![stats]),
+            None => {
+                eprintln!("There is no user called \"{}\" in the database.", username);
+                return;
             }
-        } else {
-            for log in changes {
-                let file_path = log.path;
+        },
+        None => stats::collect(&ctx),
+    };
 
-                match log.change {
-                    ChangeType::New => {
-                        println!("New: {}", file_path.to_string_lossy());
-                    }
-                    ChangeType::Update => {
-                        println!("Changed: {}", file_path.to_string_lossy());
-                    }
-                    ChangeType::Missing => {
-                        println!("Missing: {}", file_path.to_string_lossy());
-                    }
-                }
+    for user in users.expect("Unable to collect statistics.") {
+        println!("{}", user.user.username);
+        println!("---");
+        println!("Saves: {}", user.saves.len());
+        println!("Files: {}", user.files());
+        println!(
+            "Size: {} live, {} backed up{}",
+            HumanBytes(user.live_size()),
+            HumanBytes(user.backup_size()),
+            format_ratio(user.compression_ratio())
+        );
+        println!("Updates: {}", user.updates());
+
+        if let Some(time) = user.last_backup() {
+            println!("Last backup: {}", time);
+        }
+
+        for save in &user.saves {
+            let save_path = paths::decode(&save.save.save_path);
+
+            println!();
+            if save.save.friendly_name.is_empty() {
+                println!("  \"{}\"", save_path.display());
+            } else {
+                println!(
+                    "  [{}] \"{}\"",
+                    save.save.friendly_name,
+                    save_path.display()
+                );
             }
+            println!("    Files: {}", save.files);
+            println!(
+                "    Size: {} live, {} backed up{}",
+                HumanBytes(save.live_size),
+                HumanBytes(save.backup_size),
+                format_ratio(save.compression_ratio())
+            );
+            println!("    Updates: {}", save.updates);
+            println!("    Last backup: {}", save.last_backup);
         }
+
+        println!();
     }
 }
 
-fn update_saves(args: &ArgMatches) {
-    let config = Config::static_config().unwrap();
-    let db = Database::new(&config.db_location).unwrap();
-    let mut save: Option<Save> = None;
+fn format_ratio(ratio: Option<f64>) -> String {
+    match ratio {
+        Some(ratio) => format!(" (compression ratio {:.2})", ratio),
+        None => String::new(),
+    }
+}
 
-    if let Some(name) = args.value_of("friendly") {
-        let query = SaveQuery::new().with_friendly_name(name);
+fn edit_tags(args: &ArgMatches) {
+    use chrono::Utc;
+
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+
+    let save = match find_save(db, args) {
+        Some(save) => save,
+        None => return,
+    };
+
+    let time = Utc::now().naive_utc();
+
+    if let Some(tags) = args.values_of("add") {
+        for name in tags {
+            let new_tag = NewTag {
+                name,
+                save_id: save.id,
+                created_at: time,
+                modified_at: time,
+            };
+
+            db.create_tag(new_tag);
+        }
+    }
+
+    if let Some(tags) = args.values_of("remove") {
+        for name in tags {
+            let query = TagQuery::new().with_save_id(save.id).with_name(name);
+            db.delete_tags(query);
+        }
+    }
+
+    match db.get_tags(TagQuery::new().with_save_id(save.id)) {
+        Some(tags) => {
+            for tag in tags {
+                println!("{}", tag.name);
+            }
+        }
+        None => eprintln!("{} has no tags.", paths::decode(&save.save_path).display()),
+    }
+}
+
+fn edit_notes(args: &ArgMatches) {
+    use chrono::Utc;
+
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+
+    let save = match find_save(db, args) {
+        Some(save) => save,
+        None => return,
+    };
+
+    if let Some(body) = args.value_of("add") {
+        let time = Utc::now().naive_utc();
+        let new_note = NewNote {
+            body,
+            save_id: save.id,
+            created_at: time,
+            modified_at: time,
+        };
+
+        db.create_note(new_note);
+        return;
+    }
+
+    if let Some(note_id) = args.value_of("delete") {
+        let note_id: i32 = match note_id.parse() {
+            Ok(note_id) => note_id,
+            Err(_) => return eprintln!("\"{}\" is not a valid note ID.", note_id),
+        };
+
+        // Make sure that we don't delete a note that belongs to some other save
+        let notes = db.get_notes(NoteQuery::new().with_id(note_id));
+
+        match notes {
+            Some(notes) if notes.iter().all(|note| note.save_id == save.id) => {
+                db.delete_notes(NoteQuery::new().with_id(note_id))
+            }
+            _ => eprintln!(
+                "{} has no note #{}.",
+                paths::decode(&save.save_path).display(),
+                note_id
+            ),
+        }
+
+        return;
+    }
+
+    match db.get_notes(NoteQuery::new().with_save_id(save.id)) {
+        Some(notes) => {
+            for note in notes {
+                println!("#{} ({}): {}", note.id, note.created_at, note.body);
+            }
+        }
+        None => eprintln!("{} has no notes.", paths::decode(&save.save_path).display()),
+    }
+}
+
+fn edit_save(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+
+    let save = match find_save(db, args) {
+        Some(save) => save,
+        None => return,
+    };
+
+    let owner = match args.value_of("owner") {
+        Some(username) => match db.get_user(UserQuery::new().with_username(username)) {
+            Some(user) => Some(user),
+            None => return eprintln!("There is no user called \"{}\".", username),
+        },
+        None => None,
+    };
+
+    let opt = EditOptions {
+        friendly_name: args.value_of("name"),
+        save_path: args.value_of_os("new-path").map(Path::new),
+        owner: owner.as_ref(),
+        force: args.is_present("force"),
+    };
+
+    Archive::edit_save(&ctx, &save, opt, BarProgress::for_terminal().as_ref())
+        .expect("Error while trying to edit save.");
+
+    let save = db.get_save(SaveQuery::new().with_id(save.id)).unwrap();
+    print_saves(vec![save]);
+}
+
+fn find_save(db: &Database, args: &ArgMatches) -> Option<Save> {
+    if let Some(name) = args.value_of("friendly") {
+        let query = SaveQuery::new().with_friendly_name(name);
         let option = db.get_save(query);
 
-        match option {
-            Some(result) => save = Some(result),
-            None => eprintln!("{} is not related to any save in the database.", name),
+        if option.is_none() {
+            eprintln!("{} is not related to any save in the database.", name);
         }
+
+        option
     } else {
-        let path = args.value_of("path").unwrap();
+        let path = args.value_of_os("path").unwrap(); // Required if friendly is not set
         let path = Path::new(path);
 
         let query = SaveQuery::new().with_path(&path);
         let option = db.get_save(query);
 
-        match option {
-            Some(result) => save = Some(result),
-            None => eprintln!(
+        if option.is_none() {
+            eprintln!(
                 "{} is not a tracked save in the database.",
                 path.to_string_lossy()
-            ),
+            );
         }
+
+        option
     }
+}
 
-    if let Some(save) = save {
-        let option = Archive::update_save(&db, &save).expect("Error while trying to update save.");
+/// The selectors every command which can run on several saves at once takes, besides `--friendly` and the path
+fn selector_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("all")
+            .long("all")
+            .conflicts_with_all(&["friendly", "path"])
+            .help("Run on every save of the local user, or of the user given by --user"),
+        Arg::with_name("user")
+            .short("u")
+            .long("user")
+            .value_name("USERNAME")
+            .takes_value(true)
+            .help("Only run on the saves of USERNAME. Runs on all of them if no save is given"),
+    ]
+}
 
-        match option {
-            Some(changelog) => {
-                print!("Update successful:");
-                println!("{}", changelog)
+/// Finds every save which was selected by `--all`, `--user`, `--friendly` and the paths or path globs.
+///
+/// Selectors which don't match any save are reported. The second value is whether every selector matched
+fn select_saves(ctx: &SaveSync, args: &ArgMatches) -> (Vec<Save>, bool) {
+    let db = ctx.db();
+    let user = match args.value_of("user") {
+        Some(name) => match db.get_user(UserQuery::new().with_username(name)) {
+            Some(user) => Some(user),
+            None => {
+                eprintln!("{} is not a user in the database.", name);
+                return (vec![], false);
+            }
+        },
+        None if args.is_present("all") => Some(get_local_user(db, &ctx.config().local_username)),
+        None => None,
+    };
+    let user_id = user.map(|user| user.id);
+    let names = args.values_of("friendly");
+    let paths = args.values_of_os("path");
+
+    if names.is_none() && paths.is_none() {
+        let query = SaveQuery {
+            user_id,
+            ..SaveQuery::new()
+        };
+
+        return (db.get_saves(query).unwrap_or_default(), true);
+    }
+
+    let mut saves: Vec<Save> = vec![];
+    let mut matched = true;
+
+    for name in names.into_iter().flatten() {
+        let query = SaveQuery {
+            user_id,
+            ..SaveQuery::new().with_friendly_name(name)
+        };
+
+        match db.get_save(query) {
+            Some(save) => saves.push(save),
+            None => {
+                eprintln!("{} is not related to any save in the database.", name);
+                matched = false;
+            }
+        }
+    }
+
+    for path in paths.into_iter().flatten() {
+        let path = Path::new(path);
+        let path_str = path.to_string_lossy();
+
+        let found = if path_str.contains(['*', '?', '[']) {
+            let pattern = paths::encode(&path);
+            let query = SaveQuery {
+                user_id,
+                ..SaveQuery::new().with_path_glob(&pattern)
+            };
+
+            db.get_saves(query).unwrap_or_default()
+        } else {
+            let query = SaveQuery {
+                user_id,
+                ..SaveQuery::new().with_path(&path)
+            };
+
+            db.get_save(query).into_iter().collect()
+        };
+
+        if found.is_empty() {
+            eprintln!("{} is not a tracked save in the database.", path_str);
+            matched = false;
+        }
+
+        saves.extend(found);
+    }
+
+    // A save may be selected more than once, e.g. by its name and by a glob
+    let mut seen = HashSet::new();
+    saves.retain(|save| seen.insert(save.id));
+
+    (saves, matched)
+}
+
+/// Counts how running a command on several saves went, so that a summary can be printed at the end
+#[derive(Debug, Default)]
+struct Summary {
+    unchanged: usize,
+    changed: usize,
+    failed: usize,
+}
+
+impl Summary {
+    /// `verb` is what was done to every save, `unchanged` and `changed` describe the saves which were counted as such
+    fn print(&self, verb: &str, unchanged: &str, changed: &str) {
+        let total = self.unchanged + self.changed + self.failed;
+
+        println!(
+            "{} {} saves: {} {}, {} {}, {} failed.",
+            verb, total, self.unchanged, unchanged, self.changed, changed, self.failed
+        );
+    }
+}
+
+fn check_save(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let (saves, matched) = select_saves(&ctx, args);
+    let several = saves.len() > 1;
+    let mut summary = Summary::default();
+
+    for save in saves {
+        let opt = CheckOptions {
+            paranoid: args.is_present("paranoid"),
+        };
+        let result = Archive::check_save(&ctx, &save, opt, BarProgress::for_terminal().as_ref());
+
+        let changes = match result {
+            Ok(changes) => changes,
+            Err(err) => {
+                eprintln!(
+                    "Failed to check the integrity of {}: {:#}",
+                    save_label(&save),
+                    err
+                );
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        if changes.is_empty() {
+            summary.unchanged += 1;
+
+            if save.friendly_name.is_empty() {
+                println!(
+                    "No changes were detected in {}",
+                    paths::decode(&save.save_path).display()
+                )
+            } else {
+                println!("{}'s backup is up to date.", save.friendly_name)
+            }
+        } else {
+            summary.changed += 1;
+
+            if several {
+                println!("{}:", save_label(&save));
+            }
+
+            for log in changes {
+                let file_path = log.path;
+
+                match log.change {
+                    ChangeType::New => {
+                        println!("New: {}", file_path.to_string_lossy());
+                    }
+                    ChangeType::Update => {
+                        println!("Changed: {}", file_path.to_string_lossy());
+                    }
+                    ChangeType::Missing => {
+                        println!("Missing: {}", file_path.to_string_lossy());
+                    }
+                }
+            }
+        }
+    }
+
+    if several {
+        summary.print("Checked", "up to date", "with changes");
+    }
+
+    if !matched || summary.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn diff_save(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let (saves, matched) = select_saves(&ctx, args);
+    let several = saves.len() > 1;
+    let mut summary = Summary::default();
+
+    for save in saves {
+        let opt = DiffOptions {
+            paranoid: args.is_present("paranoid"),
+            files: args
+                .values_of_os("file")
+                .map(|files| files.map(Path::new).collect())
+                .unwrap_or_default(),
+        };
+        let result = Archive::diff_save(&ctx, &save, opt, BarProgress::for_terminal().as_ref());
+
+        let diffs = match result {
+            Ok(diffs) => diffs,
+            Err(err) => {
+                eprintln!(
+                    "Failed to compare {} to its backup: {:#}",
+                    save_label(&save),
+                    err
+                );
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        if diffs.is_empty() {
+            summary.unchanged += 1;
+            println!("No changes were detected in {}", save_label(&save));
+            continue;
+        }
+
+        summary.changed += 1;
+
+        if several {
+            println!("{}:", save_label(&save));
+        }
+
+        for file in diffs {
+            let path = file.path.to_string_lossy();
+
+            match (file.change, file.diff) {
+                (ChangeType::New, _) => println!("New: {}", path),
+                (ChangeType::Missing, _) => println!("Missing: {}", path),
+                (ChangeType::Update, None) => println!("Changed: {}", path),
+                (ChangeType::Update, Some(Diff::Same)) => {
+                    println!("Changed: {} (only its metadata)", path)
+                }
+                (ChangeType::Update, Some(Diff::Text(text))) => print!("{}", text),
+                (ChangeType::Update, Some(Diff::Binary(binary))) => {
+                    print_binary_diff(&path, &binary)
+                }
+            }
+        }
+    }
+
+    if several {
+        summary.print("Compared", "up to date", "with changes");
+    }
+
+    if !matched || summary.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn save_label(save: &Save) -> String {
+    if save.friendly_name.is_empty() {
+        paths::decode(&save.save_path).display().to_string()
+    } else {
+        save.friendly_name.clone()
+    }
+}
+
+fn print_binary_diff(path: &str, binary: &BinaryDiff) {
+    // Saves which are rewritten from scratch every time can differ in thousands of places
+    const MAX_RANGES: usize = 10;
+
+    println!("Binary file {} differs", path);
+    println!("  Size: {} -> {} bytes", binary.old_size, binary.new_size);
+    println!(
+        "  {} bytes changed in {} ranges",
+        binary.changed_bytes(),
+        binary.ranges.len()
+    );
+
+    for range in binary.ranges.iter().take(MAX_RANGES) {
+        println!(
+            "    0x{:08x}..0x{:08x} ({} bytes)",
+            range.start,
+            range.end,
+            range.end - range.start
+        );
+    }
+
+    if binary.ranges.len() > MAX_RANGES {
+        println!("    ...and {} more", binary.ranges.len() - MAX_RANGES);
+    }
+}
+
+fn run_doctor(args: &ArgMatches) {
+    use std::io::{self, BufRead, IsTerminal, Write};
+
+    let ctx = SaveSync::from_global().unwrap();
+    // Repairs touch every save, so nothing else may run alongside the doctor
+    let _lock = ctx
+        .lock_all()
+        .expect("Unable to wait for other save-sync processes.");
+    let problems = doctor::diagnose(&ctx).expect("Unable to look for problems.");
+    let fix_all = args.is_present("fix");
+    // Without a terminal there is nobody to ask, so problems are only reported
+    let is_interactive = !fix_all && io::stdin().is_terminal();
+
+    if problems.is_empty() {
+        println!("No problems were found.");
+        return;
+    }
+
+    let mut fixed = 0;
+
+    for problem in &problems {
+        println!("{}", problem);
+
+        let should_fix = if fix_all {
+            true
+        } else if is_interactive {
+            print!("  Fix this ({})? [y/N] ", problem.remedy());
+            io::stdout().flush().unwrap();
+
+            let mut answer = String::new();
+            io::stdin().lock().read_line(&mut answer).unwrap();
+            answer.trim().eq_ignore_ascii_case("y")
+        } else {
+            false
+        };
+
+        if should_fix {
+            match doctor::repair(&ctx, problem) {
+                Ok(()) => fixed += 1,
+                Err(err) => eprintln!("  Unable to fix this: {}", err),
+            }
+        }
+    }
+
+    println!("{} problem(s) found, {} fixed.", problems.len(), fixed);
+
+    if fixed < problems.len() && !is_interactive && !fix_all {
+        println!("Run with --fix to repair them.");
+    }
+}
+
+fn import_backups(args: &ArgMatches) {
+    if let ("ludusavi", Some(sub_matches)) = args.subcommand() {
+        import_ludusavi(sub_matches)
+    }
+}
+
+fn import_ludusavi(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let dir = Path::new(args.value_of_os("dir").unwrap()); // required
+    let only: Vec<&str> = args
+        .values_of("game")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+
+    let username = ctx.config().local_username.clone();
+    let user = get_local_user(ctx.db(), &username);
+    let games = ludusavi::find_games(&dir).expect("Unable to read the Ludusavi backup directory.");
+
+    if games.is_empty() {
+        println!(
+            "{} does not contain any Ludusavi backups.",
+            dir.to_string_lossy()
+        );
+        return;
+    }
+
+    let mut imported = 0;
+    let mut failed = 0;
+
+    for game_dir in games {
+        let game = match Game::read(&game_dir) {
+            Ok(game) => game,
+            Err(err) => {
+                // Without a readable mapping, only the name of the folder can tell which game this is
+                let name = game_dir.file_name().unwrap_or_default();
+
+                if only.is_empty() || only.iter().any(|game| name == *game) {
+                    eprintln!("Skipped {}: {}", game_dir.to_string_lossy(), err);
+                    failed += 1;
+                }
+
+                continue;
+            }
+        };
+
+        if !only.is_empty() && !only.contains(&game.name.as_str()) {
+            continue;
+        }
+
+        match Archive::import_game(&ctx, &user, &game, BarProgress::for_terminal().as_ref()) {
+            Ok(saves) => {
+                for save in saves {
+                    println!("Imported {} into {}", save.friendly_name, save.save_path);
+                }
+
+                imported += 1;
+            }
+            Err(err) => {
+                eprintln!("Skipped {}: {:#}", game.name, err);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Imported {} game(s), skipped {}.", imported, failed);
+}
+
+fn schedule_updates(global: &ArgMatches, args: &ArgMatches) {
+    match args.subcommand() {
+        ("status", Some(_)) => show_schedule(),
+        ("remove", Some(_)) => {
+            let removed = schedule::remove().expect("Unable to remove the schedule.");
+
+            if removed.is_empty() {
+                println!("No backups were scheduled.");
+            } else {
+                println!("Scheduled backups have been removed.");
+            }
+        }
+        _ => install_schedule(global, args),
+    }
+}
+
+fn install_schedule(global: &ArgMatches, args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let interval: Interval = args
+        .value_of("every")
+        .unwrap() // required
+        .parse()
+        .expect("Unable to parse the interval.");
+
+    let exe = std::env::current_exe().expect("Unable to determine where save-sync is installed.");
+    let mut command = vec![exe.to_string_lossy().into_owned()];
+
+    // The scheduled run has to use the same database and backups as this one, whichever directory it runs in
+    for flag in &["config", "db", "data-dir"] {
+        if let Some(value) = global.value_of_os(flag) {
+            let path = std::env::current_dir().unwrap().join(value);
+            command.push(format!("--{}", flag));
+            command.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    command.push("update".to_string());
+
+    match args.values_of("friendly") {
+        Some(names) => {
+            for name in names {
+                let query = SaveQuery::new().with_friendly_name(name);
+
+                if ctx.db().get_save(query).is_none() {
+                    eprintln!("{} is not related to any save in the database.", name);
+                    return;
+                }
+
+                command.push("-f".to_string());
+                command.push(name.to_string());
+            }
+        }
+        None => command.push("--all".to_string()),
+    }
+
+    let schedule = Schedule { command, interval };
+    let scheduler = if args.is_present("cron") {
+        Scheduler::Cron
+    } else {
+        Scheduler::Systemd
+    };
+
+    if args.is_present("print") {
+        match scheduler {
+            Scheduler::Systemd => {
+                println!("# {}.service", schedule::UNIT_NAME);
+                println!("{}", schedule.service_unit());
+                println!("# {}.timer", schedule::UNIT_NAME);
+                print!("{}", schedule.timer_unit());
+            }
+            Scheduler::Cron => {
+                let line = schedule
+                    .crontab_line()
+                    .expect("Unable to create a crontab line.");
+                println!("{}", line);
+            }
+        }
+
+        return;
+    }
+
+    schedule
+        .install(scheduler)
+        .expect("Unable to install the schedule.");
+
+    println!("Saves will be backed up every {}.", interval);
+}
+
+fn show_schedule() {
+    let installed = schedule::installed().expect("Unable to read the installed schedule.");
+
+    if installed.is_empty() {
+        println!("No backups are scheduled.");
+    }
+
+    for schedule in installed {
+        match schedule {
+            Installed::Systemd {
+                timer,
+                active,
+                next,
+            } => {
+                let state = if active { "active" } else { "inactive" };
+                println!("systemd timer: {} ({})", timer.to_string_lossy(), state);
+
+                if let Some(next) = next {
+                    println!("Next backup: {}", next);
+                }
+            }
+            Installed::Cron(line) => println!("crontab: {}", line),
+        }
+    }
+}
+
+fn update_saves(args: &ArgMatches) {
+    let ctx = SaveSync::from_global().unwrap();
+    let db = ctx.db();
+    let (saves, matched) = select_saves(&ctx, args);
+    let several = saves.len() > 1;
+    let mut summary = Summary::default();
+
+    let opt = UpdateOptions {
+        force: args.is_present("force"),
+        paranoid: args.is_present("paranoid"),
+    };
+
+    // One save failing to update shouldn't keep the others from being backed up
+    for save in saves {
+        if several {
+            println!("{}", save_label(&save));
+        }
+
+        if args.is_present("wait") {
+            wait_for_executables(db, &save);
+        }
+
+        match Archive::update_save(&ctx, &save, opt, BarProgress::for_terminal().as_ref()) {
+            Ok(Some(changelog)) => {
+                summary.changed += 1;
+                print_update(Some(changelog));
+            }
+            Ok(None) => {
+                summary.unchanged += 1;
+                print_update(None);
+            }
+            Err(err) => {
+                eprintln!(
+                    "Error while trying to update {}: {:#}",
+                    save_label(&save),
+                    err
+                );
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if several {
+        summary.print("Updated", "up to date", "backed up");
+    }
+
+    if !matched || summary.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn print_update(changelog: Option<String>) {
+    match changelog {
+        Some(changelog) => {
+            print!("Update successful:");
+            println!("{}", changelog)
+        }
+        None => println!("Backup is alredy up to date. There is nothing to do."),
+    }
+}
+
+fn restore_save(args: &ArgMatches) {
+    use std::io::{self, BufRead, IsTerminal, Write};
+
+    let ctx = SaveSync::from_global().unwrap();
+    let (saves, matched) = select_saves(&ctx, args);
+    let several = saves.len() > 1;
+    let target = args.value_of_os("to").map(Path::new);
+
+    if several && target.is_some() {
+        eprintln!("--to can only be used when restoring a single save.");
+        process::exit(1);
+    }
+
+    // Restoring overwrites the files on disk, so that isn't done to several saves at once without asking
+    if several && !args.is_present("yes") {
+        for save in &saves {
+            println!("{}", save_label(save));
+        }
+
+        if !io::stdin().is_terminal() {
+            eprintln!("Use --yes to restore these {} saves.", saves.len());
+            process::exit(1);
+        }
+
+        print!("Overwrite the files of these {} saves? [y/N] ", saves.len());
+        io::stdout().flush().unwrap();
+
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).unwrap();
+
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing was restored.");
+            return;
+        }
+    }
+
+    let mut summary = Summary::default();
+
+    for save in saves {
+        let opt = RestoreOptions {
+            target,
+            force: args.is_present("force"),
+        };
+        let result = Archive::restore_save(&ctx, &save, opt, BarProgress::for_terminal().as_ref());
+
+        match result {
+            Ok(restored) => {
+                if restored.is_empty() {
+                    summary.unchanged += 1;
+                } else {
+                    summary.changed += 1;
+                }
+
+                let save_path = paths::decode(&save.save_path);
+                let root = opt.target.unwrap_or(&save_path);
+                println!(
+                    "Restored {} files to {}",
+                    restored.len(),
+                    root.to_string_lossy()
+                );
+            }
+            Err(err) => {
+                eprintln!("Failed to restore {}: {:#}", save_label(&save), err);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if several {
+        summary.print("Restored", "without files", "restored");
+    }
+
+    if !matched || summary.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn wait_for_executables(db: &Database, save: &Save) {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut notified = false;
+
+    loop {
+        let running = Archive::find_running_executables(db, save)
+            .expect("Unable to determine which processes are running.");
+
+        match running.first() {
+            Some(process) => {
+                if !notified {
+                    println!(
+                        "Waiting for {} (pid {}) to exit...",
+                        process.name, process.pid
+                    );
+                    notified = true;
+                }
+
+                thread::sleep(Duration::from_secs(5));
             }
-            None => println!("Backup is alredy up to date. There is nothing to do."),
+            None => break,
         }
     }
 }
@@ -392,8 +1718,11 @@ fn get_local_user(db: &Database, username: &str) -> User {
                         };
                         Config::update(new_config).unwrap();
 
+                        // Only the username is written, so that values from other layers don't end up in the user file
                         let manager = ConfigManager::default();
-                        manager.write_to_file().unwrap(); // Update the Config File
+                        manager
+                            .set("local_username", new_default_user.username.clone().into())
+                            .unwrap();
 
                         new_default_user.clone()
                     } else {
@@ -411,7 +1740,8 @@ fn get_local_user(db: &Database, username: &str) -> User {
                         modified_at: time,
                     };
 
-                    db.create_user(new_user);
+                    db.create_user(new_user)
+                        .expect("Unable to create the local user in the database.");
 
                     let query = UserQuery::new().with_username(&username);
                     db.get_user(query).expect(
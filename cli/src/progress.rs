@@ -0,0 +1,106 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use save_sync::progress::{NoProgress, Progress, Task};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Draws a progress bar on stderr for every task which has been started but hasn't finished yet.
+///
+/// Tasks which run at the same time (e.g. copying and hashing) share a single bar.
+pub struct BarProgress {
+    bar: ProgressBar,
+    active: Mutex<Vec<Task>>,
+}
+
+impl BarProgress {
+    pub fn new() -> BarProgress {
+        BarProgress {
+            bar: ProgressBar::hidden(),
+            active: Mutex::new(vec![]),
+        }
+    }
+
+    /// A progress bar if stderr is a terminal, and nothing at all otherwise
+    pub fn for_terminal() -> Box<dyn Progress> {
+        if std::io::stderr().is_terminal() {
+            Box::new(Self::new())
+        } else {
+            Box::new(NoProgress)
+        }
+    }
+
+    fn label(task: Task) -> &'static str {
+        match task {
+            Task::Scanning => "Scanning",
+            Task::Hashing => "Hashing",
+            Task::Copying => "Copying",
+            Task::Compressing => "Compressing",
+            Task::Decompressing => "Decompressing",
+        }
+    }
+}
+
+impl Default for BarProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress for BarProgress {
+    fn start(&self, task: Task, total: Option<u64>) {
+        let mut active = self.active.lock().unwrap();
+
+        if active.is_empty() {
+            self.bar.reset();
+            self.bar.set_length(0);
+            self.bar
+                .set_draw_target(indicatif::ProgressDrawTarget::stderr());
+        }
+
+        active.push(task);
+
+        let labels: Vec<&str> = active.iter().map(|task| Self::label(*task)).collect();
+        self.bar.set_prefix(&labels.join(" & "));
+
+        match total {
+            Some(total) => {
+                let style = ProgressStyle::default_bar()
+                    .template("{prefix} [{bar:30}] {bytes}/{total_bytes} {wide_msg}")
+                    .progress_chars("=> ");
+
+                self.bar.set_style(style);
+                self.bar.set_length(self.bar.length() + total);
+            }
+            None => {
+                let style =
+                    ProgressStyle::default_spinner().template("{spinner} {prefix} {wide_msg}");
+                self.bar.set_style(style);
+            }
+        }
+    }
+
+    fn path(&self, task: Task, path: &Path) {
+        if self.active.lock().unwrap().contains(&task) {
+            self.bar.set_message(&path.to_string_lossy());
+
+            if task == Task::Scanning {
+                self.bar.tick();
+            }
+        }
+    }
+
+    fn advance(&self, task: Task, bytes: u64) {
+        if self.active.lock().unwrap().contains(&task) {
+            self.bar.inc(bytes);
+        }
+    }
+
+    fn finish(&self, task: Task) {
+        let mut active = self.active.lock().unwrap();
+        active.retain(|t| *t != task);
+
+        if active.is_empty() {
+            self.bar.finish_and_clear();
+        }
+    }
+}
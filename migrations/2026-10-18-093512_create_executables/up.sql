@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE executables (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
\ No newline at end of file
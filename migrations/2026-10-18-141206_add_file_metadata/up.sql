@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE files ADD COLUMN file_type INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE files ADD COLUMN link_target TEXT;
+ALTER TABLE files ADD COLUMN permissions INTEGER;
+ALTER TABLE files ADD COLUMN file_mtime DATETIME;
\ No newline at end of file
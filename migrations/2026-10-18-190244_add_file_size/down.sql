@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+-- SQLite is unable to drop columns, so the table has to be rebuilt without them
+CREATE TABLE files_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_path TEXT NOT NULL,
+  file_hash BLOB NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  file_type INTEGER NOT NULL DEFAULT 0,
+  link_target TEXT,
+  permissions INTEGER,
+  file_mtime DATETIME,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
+INSERT INTO files_old SELECT id, file_path, file_hash, save_id, created_at, modified_at, file_type, link_target, permissions, file_mtime FROM files;
+DROP TABLE files;
+ALTER TABLE files_old RENAME TO files;
\ No newline at end of file
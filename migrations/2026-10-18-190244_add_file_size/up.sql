@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE files ADD COLUMN file_size BIGINT;
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE chunks (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_id INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  chunk_hash BLOB NOT NULL,
+  chunk_size BIGINT NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(file_id) REFERENCES files(id)
+);
\ No newline at end of file
@@ -0,0 +1,86 @@
+-- This file should undo anything in `up.sql`
+PRAGMA defer_foreign_keys = ON;
+
+DROP INDEX users_username_unique;
+DROP INDEX saves_uuid_unique;
+DROP INDEX saves_save_path_unique;
+DROP INDEX saves_user_id_index;
+
+CREATE TABLE chunks_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_id INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  chunk_hash BLOB NOT NULL,
+  chunk_size BIGINT NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL
+);
+INSERT INTO chunks_old SELECT id, file_id, position, chunk_hash, chunk_size, created_at, modified_at FROM chunks;
+DROP TABLE chunks;
+
+CREATE TABLE files_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_path TEXT NOT NULL,
+  file_hash BLOB NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  file_type INTEGER NOT NULL DEFAULT 0,
+  link_target TEXT,
+  permissions INTEGER,
+  file_mtime DATETIME,
+  file_size BIGINT,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
+INSERT INTO files_old SELECT id, file_path, file_hash, save_id, created_at, modified_at, file_type, link_target, permissions, file_mtime, file_size FROM files;
+DROP TABLE files;
+ALTER TABLE files_old RENAME TO files;
+
+CREATE TABLE chunks (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_id INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  chunk_hash BLOB NOT NULL,
+  chunk_size BIGINT NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(file_id) REFERENCES files(id)
+);
+INSERT INTO chunks SELECT id, file_id, position, chunk_hash, chunk_size, created_at, modified_at FROM chunks_old;
+DROP TABLE chunks_old;
+
+CREATE TABLE executables_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
+INSERT INTO executables_old SELECT id, name, save_id, created_at, modified_at FROM executables;
+DROP TABLE executables;
+ALTER TABLE executables_old RENAME TO executables;
+
+CREATE TABLE tags_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
+INSERT INTO tags_old SELECT id, name, save_id, created_at, modified_at FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_old RENAME TO tags;
+
+CREATE TABLE notes_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  body TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id)
+);
+INSERT INTO notes_old SELECT id, body, save_id, created_at, modified_at FROM notes;
+DROP TABLE notes;
+ALTER TABLE notes_old RENAME TO notes;
\ No newline at end of file
@@ -0,0 +1,98 @@
+-- Your SQL goes here
+-- Foreign keys can't be switched off inside of the transaction a migration runs in, so violations are
+-- only checked once the tables have been rebuilt
+PRAGMA defer_foreign_keys = ON;
+
+CREATE UNIQUE INDEX users_username_unique ON users(username);
+CREATE UNIQUE INDEX saves_uuid_unique ON saves(uuid);
+CREATE UNIQUE INDEX saves_save_path_unique ON saves(save_path);
+CREATE INDEX saves_user_id_index ON saves(user_id);
+
+-- SQLite is unable to alter constraints, so every table which references saves or files has to be rebuilt.
+-- Chunks are set aside first, since dropping files would otherwise delete them along with it
+CREATE TABLE chunks_old (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_id INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  chunk_hash BLOB NOT NULL,
+  chunk_size BIGINT NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL
+);
+INSERT INTO chunks_old SELECT id, file_id, position, chunk_hash, chunk_size, created_at, modified_at FROM chunks;
+DROP TABLE chunks;
+
+CREATE TABLE files_new (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_path TEXT NOT NULL,
+  file_hash BLOB NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  file_type INTEGER NOT NULL DEFAULT 0,
+  link_target TEXT,
+  permissions INTEGER,
+  file_mtime DATETIME,
+  file_size BIGINT,
+  UNIQUE(save_id, file_path),
+  FOREIGN KEY(save_id) REFERENCES saves(id) ON DELETE CASCADE
+);
+INSERT INTO files_new SELECT id, file_path, file_hash, save_id, created_at, modified_at, file_type, link_target, permissions, file_mtime, file_size FROM files;
+DROP TABLE files;
+ALTER TABLE files_new RENAME TO files;
+CREATE INDEX files_file_path_index ON files(file_path);
+
+CREATE TABLE chunks (
+  id INTEGER NOT NULL PRIMARY KEY,
+  file_id INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  chunk_hash BLOB NOT NULL,
+  chunk_size BIGINT NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  UNIQUE(file_id, position),
+  FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+INSERT INTO chunks SELECT id, file_id, position, chunk_hash, chunk_size, created_at, modified_at FROM chunks_old;
+DROP TABLE chunks_old;
+CREATE INDEX chunks_chunk_hash_index ON chunks(chunk_hash);
+
+CREATE TABLE executables_new (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id) ON DELETE CASCADE
+);
+INSERT INTO executables_new SELECT id, name, save_id, created_at, modified_at FROM executables;
+DROP TABLE executables;
+ALTER TABLE executables_new RENAME TO executables;
+CREATE INDEX executables_save_id_index ON executables(save_id);
+
+CREATE TABLE tags_new (
+  id INTEGER NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id) ON DELETE CASCADE
+);
+INSERT INTO tags_new SELECT id, name, save_id, created_at, modified_at FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_new RENAME TO tags;
+CREATE INDEX tags_save_id_index ON tags(save_id);
+CREATE INDEX tags_name_index ON tags(name);
+
+CREATE TABLE notes_new (
+  id INTEGER NOT NULL PRIMARY KEY,
+  body TEXT NOT NULL,
+  save_id INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  modified_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id) ON DELETE CASCADE
+);
+INSERT INTO notes_new SELECT id, body, save_id, created_at, modified_at FROM notes;
+DROP TABLE notes;
+ALTER TABLE notes_new RENAME TO notes;
+CREATE INDEX notes_save_id_index ON notes(save_id);
\ No newline at end of file
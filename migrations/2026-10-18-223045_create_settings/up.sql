@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE settings (
+  name TEXT NOT NULL PRIMARY KEY,
+  value TEXT NOT NULL,
+  modified_at DATETIME NOT NULL
+);
\ No newline at end of file
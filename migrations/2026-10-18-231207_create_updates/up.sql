@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE updates (
+  id INTEGER NOT NULL PRIMARY KEY,
+  save_id INTEGER NOT NULL,
+  files_changed INTEGER NOT NULL,
+  created_at DATETIME NOT NULL,
+  FOREIGN KEY(save_id) REFERENCES saves(id) ON DELETE CASCADE
+);
+CREATE INDEX updates_save_id_index ON updates(save_id);
\ No newline at end of file
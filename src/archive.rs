@@ -1,4 +1,5 @@
 use crate::config::Config;
+use crate::progress::{NoProgress, Progress, ProgressReader, ProgressWriter, Task};
 use chrono::prelude::{NaiveDateTime, Utc};
 use std::fs::File;
 use std::hash::Hasher;
@@ -14,14 +15,15 @@ pub enum ArchiveError {
     IOError(#[from] std::io::Error),
     #[error("{0} was found to be an invalid path.")]
     InvalidPath(String),
-    #[error("{0} is not a valid UTF-8 compatible path")]
-    IllegalPath(String),
     #[error("Unable to determine the file / path name of {0}")]
     UnknownFileName(String),
     #[error("Unable to obtain reference to the global static config")]
     InaccessibleConfig,
 }
 
+/// How many bytes of a file are read into memory at a time while hashing it
+pub const HASH_BUFFER_SIZE: usize = 0x10000;
+
 #[derive(Debug, Default)]
 pub struct Archive {}
 
@@ -38,51 +40,99 @@ impl Archive {
         Ok(bytes)
     }
 
+    /// The inverse of `u64_to_byte_vec`
+    pub fn byte_vec_to_u64(bytes: &[u8]) -> Result<u64, ArchiveError> {
+        use byteorder::{LittleEndian, ReadBytesExt};
+
+        let mut reader = bytes;
+        Ok(reader.read_u64::<LittleEndian>()?)
+    }
+
     pub fn calc_hash<P: AsRef<Path>>(path: &P) -> Result<u64, ArchiveError> {
-        use std::io::Read;
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
 
-        let path = path.as_ref();
-        let config = Config::static_config().map_err(|_| ArchiveError::InaccessibleConfig)?;
-        let seed = config.xxhash_seed as u64;
+        Self::calc_hash_with(path, Self::hash_seed()?, &mut buffer, &NoProgress)
+    }
+
+    /// Hashes the file at `path` while reusing `buffer`, so that hashing many files doesn't allocate once per chunk
+    pub fn calc_hash_with<P: AsRef<Path>>(
+        path: &P,
+        seed: u64,
+        buffer: &mut [u8],
+        progress: &dyn Progress,
+    ) -> Result<u64, ArchiveError> {
+        use std::io::{ErrorKind, Read};
 
-        // If hasher implements Writer we can use std::io::copy
         let mut hasher = XxHash64::with_seed(seed);
-        let chunk_size = 0x4000;
         let mut file = File::open(path)?;
+        progress.path(Task::Hashing, path.as_ref());
 
         loop {
-            let mut chunk = Vec::with_capacity(chunk_size);
-            let n = file
-                .by_ref()
-                .take(chunk_size as u64)
-                .read_to_end(&mut chunk)?;
-
-            if n == 0 {
-                break;
-            }
-            hasher.write(&chunk);
+            let n = match file.read(buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            hasher.write(&buffer[..n]);
+            progress.advance(Task::Hashing, n as u64);
         }
+
         Ok(hasher.finish())
     }
 
+    /// The seed which every hash is calculated with
+    pub fn hash_seed() -> Result<u64, ArchiveError> {
+        let config = Config::static_config().map_err(|_| ArchiveError::InaccessibleConfig)?;
+        Ok(config.xxhash_seed)
+    }
+
+    /// Hashes `bytes` using the same seed as `calc_hash`
+    pub fn calc_hash_from_bytes<B: AsRef<[u8]>>(bytes: B) -> Result<u64, ArchiveError> {
+        Ok(Self::calc_hash_from_bytes_with(bytes, Self::hash_seed()?))
+    }
+
+    /// Hashes `bytes` using `seed`
+    pub fn calc_hash_from_bytes_with<B: AsRef<[u8]>>(bytes: B, seed: u64) -> u64 {
+        let mut hasher = XxHash64::with_seed(seed);
+        hasher.write(bytes.as_ref());
+        hasher.finish()
+    }
+
     pub fn compress_directory<P: AsRef<Path>, Q: AsRef<Path>>(
         source: &P,
         target: &Q,
+    ) -> Result<(), ArchiveError> {
+        Self::compress_directory_with_progress(source, target, &NoProgress)
+    }
+
+    /// Does the same as `compress_directory`, while reporting how many bytes have been compressed
+    pub fn compress_directory_with_progress<P: AsRef<Path>, Q: AsRef<Path>>(
+        source: &P,
+        target: &Q,
+        progress: &dyn Progress,
     ) -> Result<(), ArchiveError> {
         let tar_file = File::create(target)?;
         let zstd_encoder = zstd::stream::Encoder::new(tar_file, 0)?;
-        let mut archive = TarBuilder::new(zstd_encoder);
+        // Counting what goes into the encoder rather than what comes out of it lines up with the size on disk
+        let writer = ProgressWriter::new(zstd_encoder, Task::Compressing, progress);
+        let mut archive = TarBuilder::new(writer);
 
         let err = ArchiveError::UnknownFileName(source.as_ref().to_string_lossy().to_string());
         let base_name = source.as_ref().file_name().ok_or(err)?;
 
-        let name = base_name
-            .to_str()
-            .ok_or_else(|| ArchiveError::IllegalPath(base_name.to_string_lossy().to_string()))?;
+        progress.start(
+            Task::Compressing,
+            Some(Self::size_on_disk(source.as_ref())?),
+        );
+        progress.path(Task::Compressing, source.as_ref());
 
-        archive.append_dir_all(name, source)?;
-        let zstd_encoder = archive.into_inner()?;
+        archive.append_dir_all(base_name, source)?;
+        let zstd_encoder = archive.into_inner()?.into_inner();
         zstd_encoder.finish()?;
+
+        progress.finish(Task::Compressing);
         Ok(())
     }
 
@@ -90,35 +140,98 @@ impl Archive {
         source: &P,
         target: &Q,
     ) -> Result<(), ArchiveError> {
-        let mut file = File::open(source)?; // Reader
+        Self::compress_file_with_progress(source, target, &NoProgress)
+    }
+
+    /// Does the same as `compress_file`, while reporting how many bytes have been compressed
+    pub fn compress_file_with_progress<P: AsRef<Path>, Q: AsRef<Path>>(
+        source: &P,
+        target: &Q,
+        progress: &dyn Progress,
+    ) -> Result<(), ArchiveError> {
+        let file = File::open(source)?; // Reader
         let compressed_file = File::create(target)?; // Writer
         let mut zstd_encoder = zstd::stream::Encoder::new(compressed_file, 0)?;
 
-        std::io::copy(&mut file, &mut zstd_encoder)?;
+        progress.start(Task::Compressing, Some(file.metadata()?.len()));
+        progress.path(Task::Compressing, source.as_ref());
+
+        let mut reader = ProgressReader::new(file, Task::Compressing, progress);
+        std::io::copy(&mut reader, &mut zstd_encoder)?;
         zstd_encoder.finish()?;
 
+        progress.finish(Task::Compressing);
         Ok(())
     }
 
     pub fn decompress_archive<P: AsRef<Path>, Q: AsRef<Path>>(
         source: &P,
         target: &Q,
+    ) -> Result<(), ArchiveError> {
+        Self::decompress_archive_with_progress(source, target, &NoProgress)
+    }
+
+    /// Does the same as `decompress_archive`, while reporting how many bytes of `source` have been read
+    pub fn decompress_archive_with_progress<P: AsRef<Path>, Q: AsRef<Path>>(
+        source: &P,
+        target: &Q,
+        progress: &dyn Progress,
     ) -> Result<(), ArchiveError> {
         let source_file = File::open(source)?;
-        let zstd_decoder = zstd::stream::Decoder::new(source_file)?;
+
+        progress.start(Task::Decompressing, Some(source_file.metadata()?.len()));
+        progress.path(Task::Decompressing, source.as_ref());
+
+        let reader = ProgressReader::new(source_file, Task::Decompressing, progress);
+        let zstd_decoder = zstd::stream::Decoder::new(reader)?;
         let mut archive = TarArchive::new(zstd_decoder);
+        archive.unpack(target)?;
 
-        Ok(archive.unpack(target)?)
+        progress.finish(Task::Decompressing);
+        Ok(())
     }
 
     pub fn decompress_file<P: AsRef<Path>, Q: AsRef<Path>>(
         source: &P,
         target: &Q,
+    ) -> Result<(), ArchiveError> {
+        Self::decompress_file_with_progress(source, target, &NoProgress)
+    }
+
+    /// Does the same as `decompress_file`, while reporting how many bytes of `source` have been read
+    pub fn decompress_file_with_progress<P: AsRef<Path>, Q: AsRef<Path>>(
+        source: &P,
+        target: &Q,
+        progress: &dyn Progress,
     ) -> Result<(), ArchiveError> {
         let file = File::open(source)?;
         let mut target_file = File::create(target)?;
 
-        Ok(zstd::stream::copy_decode(&file, &mut target_file)?)
+        progress.start(Task::Decompressing, Some(file.metadata()?.len()));
+        progress.path(Task::Decompressing, source.as_ref());
+
+        let reader = ProgressReader::new(file, Task::Decompressing, progress);
+        zstd::stream::copy_decode(reader, &mut target_file)?;
+
+        progress.finish(Task::Decompressing);
+        Ok(())
+    }
+
+    /// Adds up the size of every file in `path`. Symlinks aren't followed
+    fn size_on_disk(path: &Path) -> Result<u64, ArchiveError> {
+        let metadata = std::fs::symlink_metadata(path)?;
+
+        if !metadata.is_dir() {
+            return Ok(metadata.len());
+        }
+
+        let mut size = 0;
+
+        for entry in std::fs::read_dir(path)? {
+            size += Self::size_on_disk(&entry?.path())?;
+        }
+
+        Ok(size)
     }
 
     /// Gets a unix time stamp in UTC±0:00
@@ -128,15 +241,56 @@ impl Archive {
 }
 
 pub mod query {
+    use chrono::NaiveDateTime;
     use std::path::Path;
 
+    /// The direction in which query results are sorted
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Order {
+        Ascending,
+        Descending,
+    }
+
+    /// The column which saves are sorted by
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum SaveOrder {
+        Id,
+        FriendlyName,
+        Path,
+        CreatedAt,
+        ModifiedAt,
+    }
+
+    /// The column which files are sorted by
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum FileOrder {
+        Id,
+        Path,
+        CreatedAt,
+        ModifiedAt,
+    }
+
+    /// Every field which is set has to match (they are combined with AND).
+    ///
+    /// `order_by`, `limit` and `offset` only apply to queries which may return more than one save.
     #[derive(Debug, Default, PartialEq, Eq)]
     pub struct SaveQuery<'a> {
         pub id: Option<i32>,
         pub friendly_name: Option<&'a str>,
         pub uuid: Option<&'a str>,
         pub path: Option<&'a Path>,
+        pub path_prefix: Option<&'a Path>,
+        pub path_glob: Option<&'a str>,
         pub user_id: Option<i32>,
+        pub tag: Option<&'a str>,
+        pub text: Option<&'a str>,
+        pub created_since: Option<NaiveDateTime>,
+        pub created_before: Option<NaiveDateTime>,
+        pub modified_since: Option<NaiveDateTime>,
+        pub modified_before: Option<NaiveDateTime>,
+        pub order_by: Option<(SaveOrder, Order)>,
+        pub limit: Option<i64>,
+        pub offset: Option<i64>,
     }
 
     impl<'a> SaveQuery<'a> {
@@ -146,7 +300,18 @@ pub mod query {
                 friendly_name: None,
                 uuid: None,
                 path: None,
+                path_prefix: None,
+                path_glob: None,
                 user_id: None,
+                tag: None,
+                text: None,
+                created_since: None,
+                created_before: None,
+                modified_since: None,
+                modified_before: None,
+                order_by: None,
+                limit: None,
+                offset: None,
             }
         }
         pub fn with_id(mut self, id: i32) -> SaveQuery<'a> {
@@ -173,14 +338,89 @@ pub mod query {
             self.uuid = Some(uuid);
             self
         }
+
+        /// Only matches saves which have been tagged with `tag`
+        pub fn with_tag(mut self, tag: &'a str) -> SaveQuery<'a> {
+            self.tag = Some(tag);
+            self
+        }
+
+        /// Matches saves whose friendly name, path, tags or notes contain `text`
+        pub fn with_text(mut self, text: &'a str) -> SaveQuery<'a> {
+            self.text = Some(text);
+            self
+        }
+
+        /// Matches saves which are located at or inside of `path`
+        pub fn with_path_prefix<P: AsRef<Path>>(mut self, path: &'a P) -> SaveQuery<'a> {
+            self.path_prefix = Some(path.as_ref());
+            self
+        }
+
+        /// Matches saves whose path matches a glob (e.g. `/home/*/Documents/*`)
+        pub fn with_path_glob(mut self, glob: &'a str) -> SaveQuery<'a> {
+            self.path_glob = Some(glob);
+            self
+        }
+
+        /// Matches saves which were created at or after `time`
+        pub fn with_created_since(mut self, time: NaiveDateTime) -> SaveQuery<'a> {
+            self.created_since = Some(time);
+            self
+        }
+
+        /// Matches saves which were created before `time`
+        pub fn with_created_before(mut self, time: NaiveDateTime) -> SaveQuery<'a> {
+            self.created_before = Some(time);
+            self
+        }
+
+        /// Matches saves which were last modified at or after `time`
+        pub fn with_modified_since(mut self, time: NaiveDateTime) -> SaveQuery<'a> {
+            self.modified_since = Some(time);
+            self
+        }
+
+        /// Matches saves which were last modified before `time`
+        pub fn with_modified_before(mut self, time: NaiveDateTime) -> SaveQuery<'a> {
+            self.modified_before = Some(time);
+            self
+        }
+
+        pub fn with_order(mut self, column: SaveOrder, order: Order) -> SaveQuery<'a> {
+            self.order_by = Some((column, order));
+            self
+        }
+
+        pub fn with_limit(mut self, limit: i64) -> SaveQuery<'a> {
+            self.limit = Some(limit);
+            self
+        }
+
+        pub fn with_offset(mut self, offset: i64) -> SaveQuery<'a> {
+            self.offset = Some(offset);
+            self
+        }
     }
 
+    /// Every field which is set has to match (they are combined with AND).
+    ///
+    /// `order_by`, `limit` and `offset` only apply to queries which may return more than one file.
     #[derive(Debug, Default, PartialEq, Eq)]
     pub struct FileQuery<'a> {
         pub id: Option<i32>,
         pub path: Option<&'a Path>,
+        pub path_prefix: Option<&'a Path>,
+        pub path_glob: Option<&'a str>,
         pub hash: Option<&'a [u8]>,
         pub save_id: Option<i32>,
+        pub created_since: Option<NaiveDateTime>,
+        pub created_before: Option<NaiveDateTime>,
+        pub modified_since: Option<NaiveDateTime>,
+        pub modified_before: Option<NaiveDateTime>,
+        pub order_by: Option<(FileOrder, Order)>,
+        pub limit: Option<i64>,
+        pub offset: Option<i64>,
     }
 
     impl<'a> FileQuery<'a> {
@@ -188,8 +428,17 @@ pub mod query {
             FileQuery {
                 id: None,
                 path: None,
+                path_prefix: None,
+                path_glob: None,
                 hash: None,
                 save_id: None,
+                created_since: None,
+                created_before: None,
+                modified_since: None,
+                modified_before: None,
+                order_by: None,
+                limit: None,
+                offset: None,
             }
         }
 
@@ -212,6 +461,178 @@ pub mod query {
             self.save_id = Some(save_id);
             self
         }
+
+        /// Matches files which are located at or inside of `path`
+        pub fn with_path_prefix<P: AsRef<Path>>(mut self, path: &'a P) -> FileQuery<'a> {
+            self.path_prefix = Some(path.as_ref());
+            self
+        }
+
+        /// Matches files whose path matches a glob (e.g. `*/screenshots/*.png`)
+        pub fn with_path_glob(mut self, glob: &'a str) -> FileQuery<'a> {
+            self.path_glob = Some(glob);
+            self
+        }
+
+        /// Matches files which were created at or after `time`
+        pub fn with_created_since(mut self, time: NaiveDateTime) -> FileQuery<'a> {
+            self.created_since = Some(time);
+            self
+        }
+
+        /// Matches files which were created before `time`
+        pub fn with_created_before(mut self, time: NaiveDateTime) -> FileQuery<'a> {
+            self.created_before = Some(time);
+            self
+        }
+
+        /// Matches files which were last modified at or after `time`
+        pub fn with_modified_since(mut self, time: NaiveDateTime) -> FileQuery<'a> {
+            self.modified_since = Some(time);
+            self
+        }
+
+        /// Matches files which were last modified before `time`
+        pub fn with_modified_before(mut self, time: NaiveDateTime) -> FileQuery<'a> {
+            self.modified_before = Some(time);
+            self
+        }
+
+        pub fn with_order(mut self, column: FileOrder, order: Order) -> FileQuery<'a> {
+            self.order_by = Some((column, order));
+            self
+        }
+
+        pub fn with_limit(mut self, limit: i64) -> FileQuery<'a> {
+            self.limit = Some(limit);
+            self
+        }
+
+        pub fn with_offset(mut self, offset: i64) -> FileQuery<'a> {
+            self.offset = Some(offset);
+            self
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct ExecutableQuery<'a> {
+        pub id: Option<i32>,
+        pub name: Option<&'a str>,
+        pub save_id: Option<i32>,
+    }
+
+    impl<'a> ExecutableQuery<'a> {
+        pub fn new() -> ExecutableQuery<'a> {
+            ExecutableQuery {
+                id: None,
+                name: None,
+                save_id: None,
+            }
+        }
+
+        pub fn with_id(mut self, id: i32) -> ExecutableQuery<'a> {
+            self.id = Some(id);
+            self
+        }
+
+        pub fn with_name(mut self, name: &'a str) -> ExecutableQuery<'a> {
+            self.name = Some(name);
+            self
+        }
+
+        pub fn with_save_id(mut self, save_id: i32) -> ExecutableQuery<'a> {
+            self.save_id = Some(save_id);
+            self
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct ChunkQuery<'a> {
+        pub id: Option<i32>,
+        pub file_id: Option<i32>,
+        pub hash: Option<&'a [u8]>,
+    }
+
+    impl<'a> ChunkQuery<'a> {
+        pub fn new() -> ChunkQuery<'a> {
+            ChunkQuery {
+                id: None,
+                file_id: None,
+                hash: None,
+            }
+        }
+
+        pub fn with_id(mut self, id: i32) -> ChunkQuery<'a> {
+            self.id = Some(id);
+            self
+        }
+
+        pub fn with_file_id(mut self, file_id: i32) -> ChunkQuery<'a> {
+            self.file_id = Some(file_id);
+            self
+        }
+
+        pub fn with_hash(mut self, hash: &'a [u8]) -> ChunkQuery<'a> {
+            self.hash = Some(hash);
+            self
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct TagQuery<'a> {
+        pub id: Option<i32>,
+        pub name: Option<&'a str>,
+        pub save_id: Option<i32>,
+    }
+
+    impl<'a> TagQuery<'a> {
+        pub fn new() -> TagQuery<'a> {
+            TagQuery {
+                id: None,
+                name: None,
+                save_id: None,
+            }
+        }
+
+        pub fn with_id(mut self, id: i32) -> TagQuery<'a> {
+            self.id = Some(id);
+            self
+        }
+
+        pub fn with_name(mut self, name: &'a str) -> TagQuery<'a> {
+            self.name = Some(name);
+            self
+        }
+
+        pub fn with_save_id(mut self, save_id: i32) -> TagQuery<'a> {
+            self.save_id = Some(save_id);
+            self
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct NoteQuery {
+        pub id: Option<i32>,
+        pub save_id: Option<i32>,
+    }
+
+    impl NoteQuery {
+        pub fn new() -> NoteQuery {
+            NoteQuery {
+                id: None,
+                save_id: None,
+            }
+        }
+
+        pub fn with_id(mut self, id: i32) -> NoteQuery {
+            self.id = Some(id);
+            self
+        }
+
+        pub fn with_save_id(mut self, save_id: i32) -> NoteQuery {
+            self.save_id = Some(save_id);
+            self
+        }
     }
 
     #[derive(Debug, Default, PartialEq, Eq)]
@@ -244,7 +665,6 @@ pub mod query {
 mod tests {
     use super::query::*;
     use super::*;
-    use crate::config::Config;
     use std::fs;
     use std::fs::File;
     use std::path::PathBuf;
@@ -274,8 +694,9 @@ mod tests {
         let mut file = File::create(&file_path).unwrap();
         file.write_all(&bytes).unwrap();
 
-        let config = Config::static_config().unwrap();
-        let seed = config.xxhash_seed as u64;
+        // Other tests replace the global config at the same time, so the seed is passed explicitly
+        let seed = 1_912_251_925_143;
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
 
         let expected = {
             let mut hasher = XxHash64::with_seed(seed); // Make sure same seed
@@ -284,7 +705,7 @@ mod tests {
             hasher.finish()
         };
 
-        let actual = Archive::calc_hash(&file_path).unwrap();
+        let actual = Archive::calc_hash_with(&file_path, seed, &mut buffer, &NoProgress).unwrap();
 
         test_dir.close().unwrap();
         assert_eq!(actual, expected);
@@ -387,7 +808,42 @@ mod tests {
             friendly_name: Some("game1"),
             uuid: Some("{uuid}"),
             path: Some(Path::new("test_location")),
+            path_prefix: None,
+            path_glob: None,
             user_id: None,
+            tag: None,
+            text: None,
+            created_since: None,
+            created_before: None,
+            modified_since: None,
+            modified_before: None,
+            order_by: None,
+            limit: None,
+            offset: None,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn example_save_query_with_range() {
+        let since = Utc::now().naive_utc();
+        let prefix = Path::new("/home/user/Documents");
+
+        let actual = SaveQuery::new()
+            .with_path_prefix(&prefix)
+            .with_modified_since(since)
+            .with_order(SaveOrder::ModifiedAt, Order::Descending)
+            .with_limit(10)
+            .with_offset(20);
+
+        let expected = SaveQuery {
+            path_prefix: Some(prefix),
+            modified_since: Some(since),
+            order_by: Some((SaveOrder::ModifiedAt, Order::Descending)),
+            limit: Some(10),
+            offset: Some(20),
+            ..SaveQuery::new()
         };
 
         assert_eq!(actual, expected);
@@ -405,8 +861,43 @@ mod tests {
         let expected = FileQuery {
             id: Some(943),
             path: None,
+            path_prefix: None,
+            path_glob: None,
             hash: Some(&hash),
             save_id: Some(2),
+            created_since: None,
+            created_before: None,
+            modified_since: None,
+            modified_before: None,
+            order_by: None,
+            limit: None,
+            offset: None,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn example_executable_query() {
+        let actual = ExecutableQuery::new().with_name("game.exe").with_save_id(7);
+
+        let expected = ExecutableQuery {
+            id: None,
+            name: Some("game.exe"),
+            save_id: Some(7),
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn example_tag_query() {
+        let actual = TagQuery::new().with_name("speedrun").with_save_id(3);
+
+        let expected = TagQuery {
+            id: None,
+            name: Some("speedrun"),
+            save_id: Some(3),
         };
 
         assert_eq!(actual, expected);
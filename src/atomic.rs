@@ -0,0 +1,224 @@
+use crate::progress::{Progress, Task};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Atomically replaces the contents of `path` with `contents`
+///
+/// The data is written to a temporary file next to `path`, flushed to disk and then renamed over `path`,
+/// so an interrupted write leaves either the old or the new contents behind, never a mix of both.
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: &P, contents: C) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = temp_file_for(path)?;
+
+    tmp.write_all(contents.as_ref())?;
+    persist(tmp, path)
+}
+
+/// Atomically replaces the contents of `path` with whatever `f` writes into the file it is handed
+pub fn write_with<P, F>(path: &P, f: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let path = path.as_ref();
+    let mut tmp = temp_file_for(path)?;
+
+    f(tmp.as_file_mut())?;
+    persist(tmp, path)
+}
+
+/// Atomically copies `from` to `to`, preserving the permissions of `from` like `fs::copy` does
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: &P, to: &Q) -> io::Result<u64> {
+    let to = to.as_ref();
+    let mut source = File::open(from)?;
+    let permissions = source.metadata()?.permissions();
+    let mut tmp = temp_file_for(to)?;
+
+    let n = io::copy(&mut source, &mut tmp)?;
+    tmp.as_file().set_permissions(permissions)?;
+    persist(tmp, to)?;
+
+    Ok(n)
+}
+
+/// Does the same as `copy`, but goes through `buffer` so that every chunk can be reported to `progress`
+pub fn copy_with<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: &P,
+    to: &Q,
+    buffer: &mut [u8],
+    progress: &dyn Progress,
+) -> io::Result<u64> {
+    let to = to.as_ref();
+    let mut source = File::open(from)?;
+    let permissions = source.metadata()?.permissions();
+    let mut tmp = temp_file_for(to)?;
+    let mut total = 0;
+
+    progress.path(Task::Copying, from.as_ref());
+
+    loop {
+        let n = match source.read(buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        tmp.write_all(&buffer[..n])?;
+        progress.advance(Task::Copying, n as u64);
+        total += n as u64;
+    }
+
+    tmp.as_file().set_permissions(permissions)?;
+    persist(tmp, to)?;
+
+    Ok(total)
+}
+
+fn temp_file_for(path: &Path) -> io::Result<NamedTempFile> {
+    // The temporary file has to live on the same filesystem as path for rename(2) to be atomic
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    tempfile::Builder::new()
+        .prefix(".save-sync-")
+        .suffix(".tmp")
+        .tempfile_in(parent)
+}
+
+fn persist(tmp: NamedTempFile, path: &Path) -> io::Result<()> {
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).map_err(|err| err.error)?;
+
+    sync_parent(path)
+}
+
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    // The rename itself is only durable once the directory entry has been flushed
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn write_replaces_contents() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let file_path: PathBuf = [tmp_dir, &PathBuf::from("settings.toml")].iter().collect();
+        fs::write(
+            &file_path,
+            "a much longer piece of text than the replacement",
+        )
+        .unwrap();
+
+        write(&file_path, "short").unwrap();
+
+        let actual = fs::read_to_string(&file_path).unwrap();
+        let entries = fs::read_dir(tmp_dir).unwrap().count();
+
+        test_dir.close().unwrap();
+        assert_eq!(actual, "short");
+        assert_eq!(entries, 1); // No temporary files left behind
+    }
+
+    #[test]
+    fn copy_replaces_contents() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let expected: [u8; 32] = rand::random();
+        let source: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        let target: PathBuf = [tmp_dir, &PathBuf::from("backup.sav")].iter().collect();
+
+        fs::write(&source, expected).unwrap();
+        fs::write(&target, vec![0; 1024]).unwrap();
+
+        let n = copy(&source, &target).unwrap();
+
+        let actual = fs::read(&target).unwrap();
+        let entries = fs::read_dir(tmp_dir).unwrap().count();
+
+        test_dir.close().unwrap();
+        assert_eq!(n, 32);
+        assert_eq!(actual, expected.to_vec());
+        assert_eq!(entries, 2);
+    }
+
+    #[test]
+    fn copy_with_matches_copy() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        // Larger than the buffer, so that it is used more than once
+        let expected: Vec<u8> = (0..100).map(|_| rand::random()).collect();
+        let source: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        let target: PathBuf = [tmp_dir, &PathBuf::from("backup.sav")].iter().collect();
+        fs::write(&source, &expected).unwrap();
+
+        let mut buffer = vec![0; 16];
+        let n = copy_with(&source, &target, &mut buffer, &NoProgress).unwrap();
+        let actual = fs::read(&target).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(n, 100);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let source: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        let target: PathBuf = [tmp_dir, &PathBuf::from("backup.sav")].iter().collect();
+
+        fs::write(&source, "save data").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o640)).unwrap();
+
+        copy(&source, &target).unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+
+        test_dir.close().unwrap();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[test]
+    fn copy_missing_source_leaves_target() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let source: PathBuf = [tmp_dir, &PathBuf::from("missing.sav")].iter().collect();
+        let target: PathBuf = [tmp_dir, &PathBuf::from("backup.sav")].iter().collect();
+        fs::write(&target, "old backup").unwrap();
+
+        let result = copy(&source, &target);
+        let actual = fs::read_to_string(&target).unwrap();
+
+        test_dir.close().unwrap();
+        assert!(result.is_err());
+        assert_eq!(actual, "old backup");
+    }
+}
@@ -0,0 +1,415 @@
+use crate::atomic;
+use crate::progress::{Progress, Task};
+use crate::storage::{StorageBackend, StorageError};
+use std::fmt;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+use twox_hash::XxHash64;
+
+#[derive(Error, Debug)]
+pub enum ChunkError {
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    StorageError(#[from] StorageError),
+    #[error("The chunk {0:016x} is either missing or corrupted")]
+    CorruptChunk(u64),
+}
+
+/// One random number for every possible byte, which is what the gear hash is built from.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64, so that the table is the same on every machine without having to spell out 256 numbers
+    let mut table = [0; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut i = 0;
+
+    while i < table.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    table
+}
+
+/// A piece of a file, identified by the hash of its contents
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub hash: u64,
+    pub size: u64,
+}
+
+/// A file which has been split into chunks
+///
+/// # Properties
+/// * `hash` - The hash of the whole file. This is the same as what `Archive::calc_hash_with` returns
+/// * `chunks` - Every chunk of the file, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkedFile {
+    pub hash: u64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+/// Splits data into content-defined chunks using a gear hash.
+///
+/// Chunk boundaries depend on the data around them rather than on their offset, so inserting or removing a
+/// few bytes only changes the chunks close to the edit. Every other chunk stays the same, and doesn't have
+/// to be stored again.
+///
+/// # Examples
+/// ```
+/// use save_sync::chunking::Chunker;
+///
+/// let chunker = Chunker::new(64, 256, 1024);
+/// let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+///
+/// let chunked = chunker.chunk(&data[..], 1337, |_chunk, _bytes| Ok(())).unwrap();
+/// let size: u64 = chunked.chunks.iter().map(|chunk| chunk.size).sum();
+///
+/// assert!(chunked.chunks.iter().all(|chunk| chunk.size <= 1024));
+/// assert_eq!(size, 10_000);
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Chunker {
+    min_size: usize,
+    max_size: usize,
+    mask: u64,
+}
+
+impl Default for Chunker {
+    /// Chunks are between 256KiB and 4MiB large, and 1MiB on average
+    fn default() -> Self {
+        Self::new(256 * 1024, 1024 * 1024, 4 * 1024 * 1024)
+    }
+}
+
+impl Chunker {
+    /// `avg_size` is rounded down to a power of two
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Chunker {
+        let bits = (usize::BITS - 1).saturating_sub(avg_size.leading_zeros());
+        // The high bits of a gear hash depend on more bytes than the low bits do
+        let mask = match bits {
+            0 => 0,
+            bits => u64::MAX << (64 - bits),
+        };
+
+        Chunker {
+            min_size: min_size.max(1),
+            max_size: max_size.max(min_size.max(1)),
+            mask,
+        }
+    }
+
+    /// Finds where the first chunk in `data` ends. `data` has to be at least `max_size` bytes long,
+    /// unless it is the end of the file.
+    fn cut_point(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+
+        let end = data.len().min(self.max_size);
+        let mut hash: u64 = 0;
+
+        for (i, byte) in data[self.min_size..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+
+            if hash & self.mask == 0 {
+                return self.min_size + i + 1;
+            }
+        }
+
+        end
+    }
+
+    /// Splits everything in `reader` into chunks, which are hashed using `seed`.
+    ///
+    /// `f` is called with every chunk and its contents, in order.
+    pub fn chunk<R, F>(&self, mut reader: R, seed: u64, mut f: F) -> Result<ChunkedFile, ChunkError>
+    where
+        R: Read,
+        F: FnMut(&ChunkRef, &[u8]) -> Result<(), ChunkError>,
+    {
+        let mut file_hasher = XxHash64::with_seed(seed);
+        let mut chunks = vec![];
+        let mut buffer = vec![0; self.max_size * 2];
+        let mut len = 0;
+        let mut eof = false;
+
+        loop {
+            // Keep at least one chunk's worth of data around, so that cut_point sees the whole chunk
+            while !eof && len < self.max_size {
+                match reader.read(&mut buffer[len..]) {
+                    Ok(0) => eof = true,
+                    Ok(n) => len += n,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            if len == 0 {
+                break;
+            }
+
+            let cut = self.cut_point(&buffer[..len]);
+            let bytes = &buffer[..cut];
+
+            let mut hasher = XxHash64::with_seed(seed);
+            hasher.write(bytes);
+            file_hasher.write(bytes);
+
+            let chunk = ChunkRef {
+                hash: hasher.finish(),
+                size: cut as u64,
+            };
+
+            f(&chunk, bytes)?;
+            chunks.push(chunk);
+
+            buffer.copy_within(cut..len, 0);
+            len -= cut;
+        }
+
+        Ok(ChunkedFile {
+            hash: file_hasher.finish(),
+            chunks,
+        })
+    }
+}
+
+/// Chunks which are stored under their hash in a storage backend.
+///
+/// Chunks are shared between every file and save, so a chunk is only ever written once.
+#[derive(Clone)]
+pub struct ChunkStore {
+    backend: Arc<dyn StorageBackend>,
+}
+
+impl fmt::Debug for ChunkStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkStore").finish()
+    }
+}
+
+impl ChunkStore {
+    pub fn new(backend: Arc<dyn StorageBackend>) -> ChunkStore {
+        ChunkStore { backend }
+    }
+
+    /// The key the chunk with `hash` is stored under. Chunks are spread across 256 prefixes
+    pub fn chunk_key(hash: u64) -> String {
+        let name = format!("{:016x}", hash);
+        format!("chunks/{}/{}", &name[..2], name)
+    }
+
+    pub fn contains(&self, hash: u64) -> Result<bool, ChunkError> {
+        Ok(self.backend.exists(&Self::chunk_key(hash))?)
+    }
+
+    /// Splits the file at `path` into chunks, and writes the ones which aren't in the store yet.
+    ///
+    /// Every byte is reported to `progress` as both hashed and copied, since the file is only read once.
+    pub fn store_file<P: AsRef<Path>>(
+        &self,
+        chunker: &Chunker,
+        path: &P,
+        seed: u64,
+        progress: &dyn Progress,
+    ) -> Result<ChunkedFile, ChunkError> {
+        let file = File::open(path)?;
+        progress.path(Task::Copying, path.as_ref());
+
+        chunker.chunk(file, seed, |chunk, bytes| {
+            let key = Self::chunk_key(chunk.hash);
+
+            if !self.backend.exists(&key)? {
+                self.backend.put(&key, bytes)?;
+            }
+
+            progress.advance(Task::Hashing, chunk.size);
+            progress.advance(Task::Copying, chunk.size);
+            Ok(())
+        })
+    }
+
+    /// Atomically puts `chunks` back together at `target`. Returns the number of bytes written
+    pub fn restore_file<P: AsRef<Path>>(
+        &self,
+        chunks: &[ChunkRef],
+        target: &P,
+        progress: &dyn Progress,
+    ) -> Result<u64, ChunkError> {
+        let mut total = 0;
+        let mut result = Ok(());
+
+        atomic::write_with(target, |file| {
+            for chunk in chunks {
+                let bytes = match self.get(chunk) {
+                    Ok(bytes) => bytes,
+                    Err(err @ ChunkError::CorruptChunk(_)) => {
+                        // Leaves whatever was at target before untouched
+                        result = Err(err);
+                        return Err(io::Error::new(ErrorKind::InvalidData, "corrupt chunk"));
+                    }
+                    Err(err) => {
+                        result = Err(err);
+                        return Err(io::Error::other("unreadable chunk"));
+                    }
+                };
+
+                file.write_all(&bytes)?;
+                progress.advance(Task::Copying, chunk.size);
+                total += chunk.size;
+            }
+
+            Ok(())
+        })
+        .or_else(|err| result.and(Err(err.into())))?;
+
+        Ok(total)
+    }
+
+    /// Puts `chunks` back together in memory, for when the file itself isn't needed on disk
+    pub fn read_file(&self, chunks: &[ChunkRef]) -> Result<Vec<u8>, ChunkError> {
+        let size = chunks.iter().map(|chunk| chunk.size).sum::<u64>();
+        let mut data = Vec::with_capacity(size as usize);
+
+        for chunk in chunks {
+            data.extend_from_slice(&self.get(chunk)?);
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the contents of `chunk`, which have to be as large as the chunk claims to be
+    fn get(&self, chunk: &ChunkRef) -> Result<Vec<u8>, ChunkError> {
+        match self.backend.get(&Self::chunk_key(chunk.hash)) {
+            Ok(bytes) if bytes.len() as u64 == chunk.size => Ok(bytes),
+            Ok(_) | Err(StorageError::NotFound(_)) => Err(ChunkError::CorruptChunk(chunk.hash)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Removes the chunk with `hash` from the store. Missing chunks are ignored
+    pub fn remove(&self, hash: u64) -> Result<(), ChunkError> {
+        Ok(self.backend.delete(&Self::chunk_key(hash))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Archive;
+    use crate::progress::NoProgress;
+    use crate::storage::LocalStorage;
+    use std::collections::HashSet;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SEED: u64 = 1_912_251_925_143;
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|_| rand::random()).collect()
+    }
+
+    #[test]
+    fn chunks_cover_the_whole_file() {
+        let chunker = Chunker::new(1024, 4096, 16 * 1024);
+        let data = random_bytes(200 * 1024 + 17);
+
+        let mut joined = vec![];
+        let chunked = chunker
+            .chunk(&data[..], SEED, |_chunk, bytes| {
+                joined.extend_from_slice(bytes);
+                Ok(())
+            })
+            .unwrap();
+
+        let last = chunked.chunks.len() - 1;
+
+        assert_eq!(joined, data);
+        assert_eq!(
+            chunked.hash,
+            Archive::calc_hash_from_bytes_with(&data, SEED)
+        );
+        assert!(chunked.chunks[..last]
+            .iter()
+            .all(|chunk| chunk.size >= 1024 && chunk.size <= 16 * 1024));
+    }
+
+    #[test]
+    fn small_edit_keeps_most_chunks() {
+        let chunker = Chunker::new(1024, 4096, 16 * 1024);
+        let original = random_bytes(512 * 1024);
+
+        let mut edited = original.clone();
+        edited.splice(100_000..100_010, random_bytes(25));
+
+        let hashes = |data: &[u8]| -> Vec<u64> {
+            let chunked = chunker.chunk(data, SEED, |_, _| Ok(())).unwrap();
+            chunked.chunks.iter().map(|chunk| chunk.hash).collect()
+        };
+
+        let before: HashSet<u64> = hashes(&original).into_iter().collect();
+        let after = hashes(&edited);
+        let changed = after.iter().filter(|hash| !before.contains(hash)).count();
+
+        // Only the chunks around the edit should differ
+        assert!(
+            changed <= 3,
+            "{} of {} chunks changed",
+            changed,
+            after.len()
+        );
+    }
+
+    #[test]
+    fn store_and_restore_file() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let store = ChunkStore::new(Arc::new(LocalStorage::new(&tmp_dir.join("backups"))));
+        let chunker = Chunker::new(1024, 4096, 16 * 1024);
+        let source = tmp_dir.join("00.sav");
+        let target = tmp_dir.join("restored.sav");
+
+        let data = random_bytes(100 * 1024);
+        fs::write(&source, &data).unwrap();
+
+        let chunked = store
+            .store_file(&chunker, &source, SEED, &NoProgress)
+            .unwrap();
+        let all_stored = chunked
+            .chunks
+            .iter()
+            .all(|chunk| store.contains(chunk.hash).unwrap());
+        let n = store
+            .restore_file(&chunked.chunks, &target, &NoProgress)
+            .unwrap();
+        let restored = fs::read(&target).unwrap();
+        let read = store.read_file(&chunked.chunks).unwrap();
+
+        // A missing chunk must leave the target alone
+        store.remove(chunked.chunks[0].hash).unwrap();
+        let result = store.restore_file(&chunked.chunks, &target, &NoProgress);
+        let untouched = fs::read(&target).unwrap();
+        let missing = store.read_file(&chunked.chunks);
+
+        test_dir.close().unwrap();
+        assert!(all_stored);
+        assert_eq!(n, data.len() as u64);
+        assert_eq!(restored, data);
+        assert_eq!(read, data);
+        assert!(matches!(result, Err(ChunkError::CorruptChunk(_))));
+        assert_eq!(untouched, data);
+        assert!(matches!(missing, Err(ChunkError::CorruptChunk(_))));
+    }
+}
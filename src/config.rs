@@ -1,11 +1,19 @@
+use crate::atomic;
+use crate::storage::StorageConfig;
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
-use std::io::{BufReader, Read, Write};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use thiserror::Error;
 
+pub mod layers;
+pub mod migrations;
+
+/// The version of the config schema which this build of save-sync writes
+pub const CONFIG_VERSION: u32 = 2;
+
 lazy_static! {
     static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
 }
@@ -23,6 +31,14 @@ pub enum ConfigError {
     DeserializationError(#[from] toml::de::Error),
     #[error("Falied to Serialize save-sync configuration to disk.")]
     SerializationError(#[from] toml::ser::Error),
+    #[error("Failed to parse {0}: {1}")]
+    InvalidFile(String, toml::de::Error),
+    #[error("\"{1}\" is not a valid value for {0}")]
+    InvalidVariable(String, String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+    #[error("Config version {0} is newer than the newest version this build of save-sync understands ({1})")]
+    UnsupportedVersion(i64, u32),
     #[error("{0} was found to be an invalid path.")]
     InvalidPath(String),
     #[error("{0} is not a valid UTF-8 compatible path")]
@@ -53,11 +69,35 @@ pub enum ConfigError {
 /// ```
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    /// The version of the config schema. Older files are upgraded by `migrations::migrate` when they are loaded
+    pub version: u32,
     pub db_location: PathBuf,
     pub data_location: PathBuf,
-    pub xxhash_seed: i64, // Issue: https://github.com/alexcrichton/toml-rs/issues/256 (should be u64)
+    /// Written as a string when it doesn't fit into a TOML integer, which is signed
+    #[serde(with = "seed")]
+    pub xxhash_seed: u64,
     pub local_username: String,
+    /// How many threads are used to hash and copy files. 0 means one thread per CPU
+    #[serde(default)]
+    pub threads: usize,
+    /// Files which are at least this many bytes large are split into chunks, so that only changed chunks are stored
+    #[serde(default = "Config::default_chunk_threshold")]
+    pub chunk_threshold: u64,
+    /// How many seconds to wait for another save-sync process to let go of a save before giving up.
+    /// 0 means giving up right away
+    pub lock_timeout: u64,
+    /// Name of the backend in `backends` which saves without an entry in `save_backends` are stored in.
+    /// When unset, they are stored in `data_location`
+    #[serde(default)]
+    pub default_backend: Option<String>,
+    /// Named places to store backups in
+    #[serde(default)]
+    pub backends: BTreeMap<String, StorageConfig>,
+    /// Maps the UUID or friendly name of a save to the name of the backend it is stored in
+    #[serde(default)]
+    pub save_backends: BTreeMap<String, String>,
 }
 
 impl Default for Config {
@@ -67,10 +107,17 @@ impl Default for Config {
         let db_location = data_location.join("saves.db");
 
         Config {
+            version: CONFIG_VERSION,
             db_location,
             data_location,
             xxhash_seed: 1_912_251_925_143,
             local_username: "Default".to_string(),
+            threads: 0,
+            chunk_threshold: Self::default_chunk_threshold(),
+            lock_timeout: 30,
+            default_backend: None,
+            backends: BTreeMap::new(),
+            save_backends: BTreeMap::new(),
         }
     }
 }
@@ -80,13 +127,20 @@ impl<'a> Config {
     ///
     /// ```
     /// # use std::path::PathBuf;
-    /// use save_sync::config::Config;
+    /// use save_sync::config::{Config, CONFIG_VERSION};
     ///
     /// let new_config = Config {
+    ///     version: CONFIG_VERSION,
     ///     db_location: PathBuf::from("/some/where"),
     ///     data_location: PathBuf::from("/some/where/else"),
     ///     xxhash_seed: 11037,
     ///     local_username: "UniqueUsername".to_string(),
+    ///     threads: 4,
+    ///     chunk_threshold: 1024 * 1024,
+    ///     lock_timeout: 30,
+    ///     default_backend: None,
+    ///     backends: Default::default(),
+    ///     save_backends: Default::default(),
     /// };
     ///
     /// Config::update(new_config.clone()).unwrap();
@@ -112,6 +166,118 @@ impl<'a> Config {
         Ok((*CONFIG.read()?).clone())
     }
 
+    /// Where the backups of a save are stored
+    ///
+    /// A backend assigned to the save's UUID takes precedence over one assigned to its friendly name.
+    /// Saves without an assigned backend use `default_backend`, and if that isn't set either, `data_location`.
+    ///
+    /// ```
+    /// use save_sync::config::Config;
+    /// use save_sync::storage::StorageConfig;
+    /// # use std::path::PathBuf;
+    ///
+    /// let mut config = Config::default();
+    /// let usb = StorageConfig::Local { path: Some(PathBuf::from("/mnt/usb")) };
+    ///
+    /// config.backends.insert("usb".to_string(), usb.clone());
+    /// config.save_backends.insert("Celeste".to_string(), "usb".to_string());
+    ///
+    /// assert_eq!(config.storage_for("some-uuid", "Celeste"), usb);
+    /// assert_eq!(config.storage_for("some-uuid", "Hades"), StorageConfig::default());
+    /// ```
+    pub fn storage_for(&self, uuid: &str, friendly_name: &str) -> StorageConfig {
+        let name = self
+            .save_backends
+            .get(uuid)
+            .or_else(|| self.save_backends.get(friendly_name))
+            .or(self.default_backend.as_ref());
+
+        name.and_then(|name| self.backends.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether renaming the save with `uuid` from `old_name` to `new_name` would assign it to another backend.
+    /// Its backups would be left behind in the old one if it were renamed anyway
+    ///
+    /// ```
+    /// use save_sync::config::Config;
+    /// use save_sync::storage::StorageConfig;
+    /// # use std::path::PathBuf;
+    ///
+    /// let mut config = Config::default();
+    /// let usb = StorageConfig::Local { path: Some(PathBuf::from("/mnt/usb")) };
+    ///
+    /// config.backends.insert("usb".to_string(), usb);
+    /// config.save_backends.insert("Celeste".to_string(), "usb".to_string());
+    ///
+    /// assert!(config.rename_changes_storage("some-uuid", "Celeste", "Hades"));
+    /// assert!(!config.rename_changes_storage("some-uuid", "Hades", "Bastion"));
+    /// ```
+    pub fn rename_changes_storage(&self, uuid: &str, old_name: &str, new_name: &str) -> bool {
+        self.storage_for(uuid, old_name) != self.storage_for(uuid, new_name)
+    }
+
+    /// Checks the settings which can be checked without a database, and names the first setting which is invalid
+    ///
+    /// ```
+    /// use save_sync::config::{Config, ConfigError};
+    /// # use std::path::PathBuf;
+    ///
+    /// let config = Config {
+    ///     data_location: PathBuf::from("/does/not/exist/data"),
+    ///     ..Config::default()
+    /// };
+    ///
+    /// match config.validate() {
+    ///     Err(ConfigError::InvalidValue(key, _)) => assert_eq!(key, "data_location"),
+    ///     _ => panic!("data_location should be invalid"),
+    /// }
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid =
+            |key: &str, reason: String| Err(ConfigError::InvalidValue(key.to_string(), reason));
+
+        // A configured data location is created when it's needed, but the directory it's in has to exist already.
+        // The default one is created along with its parents, since they may not exist on a fresh system
+        let is_default = self.data_location == Self::get_default_data_path();
+
+        if let Some(parent) = self.data_location.parent().filter(|_| !is_default) {
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                return invalid(
+                    "data_location",
+                    format!("{} does not exist", parent.display()),
+                );
+            }
+        }
+
+        if self.chunk_threshold == 0 {
+            return invalid("chunk_threshold", "must be larger than 0".to_string());
+        }
+
+        if let Some(name) = &self.default_backend {
+            if !self.backends.contains_key(name) {
+                return invalid(
+                    "default_backend",
+                    format!("there is no backend called \"{}\"", name),
+                );
+            }
+        }
+
+        for (save, name) in &self.save_backends {
+            if !self.backends.contains_key(name) {
+                let key = format!("save_backends.{}", save);
+                return invalid(&key, format!("there is no backend called \"{}\"", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn default_chunk_threshold() -> u64 {
+        16 * 1024 * 1024
+    }
+
     fn get_default_data_path() -> PathBuf {
         match ProjectDirs::from("moe", "paoda", "save-sync") {
             Some(project) => project.data_dir().to_path_buf(),
@@ -122,6 +288,38 @@ impl<'a> Config {
     }
 }
 
+/// TOML integers are signed, so seeds which are larger than `i64::MAX` are written as strings
+mod seed {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Seed {
+        Number(i64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(*seed) {
+            Ok(seed) => serializer.serialize_i64(seed),
+            Err(_) => serializer.serialize_str(&seed.to_string()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        use serde::de::Error;
+
+        match Seed::deserialize(deserializer)? {
+            Seed::Number(seed) => u64::try_from(seed)
+                .map_err(|_| D::Error::custom(format!("{} is a negative seed", seed))),
+            Seed::Text(text) => text
+                .parse()
+                .map_err(|_| D::Error::custom(format!("\"{}\" is not a seed", text))),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ConfigManager {
     config_file_path: PathBuf,
@@ -174,45 +372,88 @@ impl ConfigManager {
             let config = Config::default();
 
             let toml_string = toml::to_string(&config)?;
-            let mut file = File::create(path)?;
-            file.write_all(toml_string.as_bytes())?;
+            atomic::write(&path, toml_string)?;
         } else {
-            let file = File::open(path)?;
-            Self::update_config_from_file(&file)?;
+            Config::update(Self::read_config(path)?)?;
         }
 
         Ok(())
     }
 
-    fn update_config_from_file(file: &File) -> Result<(), ConfigError> {
-        let mut buf_reader = BufReader::new(file);
-        let mut toml_buf = vec![];
-        buf_reader.read_to_end(&mut toml_buf)?;
+    /// Reads the file at `path`, which is upgraded on disk first if it was written by an older version
+    fn read_config(path: &Path) -> Result<Config, ConfigError> {
+        let table = Self::read_table(path)?
+            .ok_or_else(|| ConfigError::InvalidPath(path.to_string_lossy().to_string()))?;
+
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    /// The settings in the file at `path`, upgraded to `CONFIG_VERSION`. `None` if there is no such file
+    fn read_table(path: &Path) -> Result<Option<toml::value::Table>, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut table = toml::from_str(&text)
+            .map_err(|err| ConfigError::InvalidFile(path.to_string_lossy().to_string(), err))?;
+
+        if migrations::migrate(&mut table)? {
+            atomic::write(&path, toml::to_string(&table)?)?;
+        }
+
+        Ok(Some(table))
+    }
 
-        let config: Config = toml::from_slice(&toml_buf)?;
-        Config::update(config)?;
+    /// Reads the config file without touching the global config
+    pub fn read(&self) -> Result<Config, ConfigError> {
+        Self::read_config(&self.config_file_path)
+    }
+
+    /// Upgrades the config file on disk if it was written by an older version of save-sync
+    pub fn upgrade(&self) -> Result<(), ConfigError> {
+        Self::read_table(&self.config_file_path)?;
 
         Ok(())
     }
 
-    pub fn load_from_file(&self) -> Result<(), ConfigError> {
-        let file = File::open(&self.config_file_path)?;
-        Self::update_config_from_file(&file)?;
+    /// Writes `config` to the config file without touching the global config
+    pub fn write(&self, config: &Config) -> Result<(), ConfigError> {
+        let toml_string = toml::to_string(config)?;
+        atomic::write(&self.config_file_path, toml_string)?;
 
         Ok(())
     }
 
+    /// Replaces the global config with the contents of the config file
+    pub fn load_from_file(&self) -> Result<(), ConfigError> {
+        Config::update(self.read()?)
+    }
+
+    /// Writes the global config to the config file
     pub fn write_to_file(&self) -> Result<(), ConfigError> {
         let config = Config::static_config()?;
-        let toml_string = toml::to_string(&(*config))?;
 
-        let mut file = File::create(&self.config_file_path)?;
+        self.write(&config)
+    }
+
+    /// Sets the setting called `key` in the config file, leaving every other setting in the file as it is.
+    ///
+    /// Unlike `write`, this doesn't add settings the file didn't contain, so they keep coming from other layers.
+    pub fn set(&self, key: &str, value: toml::Value) -> Result<(), ConfigError> {
+        let mut table = Self::read_table(&self.config_file_path)?.unwrap_or_default();
 
-        file.write_all(toml_string.as_bytes())?;
+        table.insert(key.to_string(), value);
+        atomic::write(&self.config_file_path, toml::to_string(&table)?)?;
 
         Ok(())
     }
 
+    pub fn path(&self) -> &Path {
+        &self.config_file_path
+    }
+
     pub fn get_config_dir() -> PathBuf {
         match ProjectDirs::from("moe", "paoda", "save-sync") {
             Some(project) => project.config_dir().to_path_buf(),
@@ -233,14 +474,21 @@ mod tests {
     #[test]
     fn config_update_valid_input() {
         let expected_data_location = PathBuf::from("new_data_location");
-        let expected_xxhash_seed: i64 = rand::random();
+        let expected_xxhash_seed: u64 = rand::random();
         let expected_db_location = PathBuf::from("new_db_location");
 
         let expected = Config {
             data_location: expected_data_location.clone(),
+            version: CONFIG_VERSION,
             xxhash_seed: expected_xxhash_seed,
             db_location: expected_db_location.clone(),
             local_username: "SomeUser".to_string(),
+            threads: 0,
+            chunk_threshold: 1024,
+            lock_timeout: 30,
+            default_backend: None,
+            backends: BTreeMap::new(),
+            save_backends: BTreeMap::new(),
         };
 
         Config::update(expected.clone()).unwrap();
@@ -258,19 +506,25 @@ mod tests {
         let settings_path: PathBuf = [tmp_dir, &PathBuf::from("settings.toml")].iter().collect();
 
         let expected_data_location = PathBuf::from("new_data_location");
-        let expected_xxhash_seed: i64 = rand::random();
+        let expected_xxhash_seed: u64 = rand::random();
         let expected_db_location = PathBuf::from("new_db_location");
 
         let expected = Config {
             data_location: expected_data_location,
+            version: CONFIG_VERSION,
             xxhash_seed: expected_xxhash_seed,
             db_location: expected_db_location,
             local_username: "User1".to_string(),
+            threads: 0,
+            chunk_threshold: 1024,
+            lock_timeout: 30,
+            default_backend: None,
+            backends: BTreeMap::new(),
+            save_backends: BTreeMap::new(),
         };
 
         let manager = ConfigManager::new(&settings_path);
-        Config::update(expected.clone()).unwrap();
-        manager.write_to_file().unwrap();
+        manager.write(&expected).unwrap();
 
         let mut file = File::open(settings_path).unwrap();
 
@@ -285,7 +539,6 @@ mod tests {
 
     #[test]
     fn verify_load_from_file() {
-        // FIXME: This test sometimes fails. Why?
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -294,25 +547,251 @@ mod tests {
         let mut settings = File::create(&settings_path).unwrap();
 
         let expected_data_location = PathBuf::from("new_data_location");
-        let expected_xxhash_seed: i64 = rand::random();
+        let expected_xxhash_seed: u64 = rand::random();
         let expected_db_location = PathBuf::from("new_db_location");
 
         let expected = Config {
             data_location: expected_data_location,
+            version: CONFIG_VERSION,
             xxhash_seed: expected_xxhash_seed,
             db_location: expected_db_location,
             local_username: "Default".to_string(),
+            threads: 0,
+            chunk_threshold: 1024,
+            lock_timeout: 30,
+            default_backend: None,
+            backends: BTreeMap::new(),
+            save_backends: BTreeMap::new(),
         };
 
         let toml_str = toml::to_string(&expected).unwrap();
         settings.write_all(&toml_str.into_bytes()).unwrap();
 
-        manager.load_from_file().unwrap();
+        // Other tests replace the global config at the same time, so the file is read without it
+        let actual = manager.read().unwrap();
 
-        let actual = &*Config::static_config().unwrap();
+        test_dir.close().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn set_keeps_other_settings() {
+        let test_dir = TempDir::new().unwrap();
+        let settings_path = test_dir.path().join("settings.toml");
+        fs::write(&settings_path, "threads = 4\n").unwrap();
+
+        let manager = ConfigManager {
+            config_file_path: settings_path.clone(),
+        };
+        manager
+            .set("local_username", toml::Value::String("User1".to_string()))
+            .unwrap();
+
+        let table: toml::value::Table =
+            toml::from_str(&fs::read_to_string(&settings_path).unwrap()).unwrap();
 
         test_dir.close().unwrap();
-        assert_eq!(*actual, expected);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table["version"].as_integer(), Some(CONFIG_VERSION as i64));
+        assert_eq!(table["threads"].as_integer(), Some(4));
+        assert_eq!(table["local_username"].as_str(), Some("User1"));
+    }
+
+    #[test]
+    fn older_files_are_upgraded_on_load() {
+        let test_dir = TempDir::new().unwrap();
+        let settings_path = test_dir.path().join("settings.toml");
+
+        let old = r#"
+            db_location = "db_location"
+            data_location = "data_location"
+            xxhash_seed = -1
+            local_username = "User1"
+        "#;
+        fs::write(&settings_path, old).unwrap();
+
+        let manager = ConfigManager {
+            config_file_path: settings_path.clone(),
+        };
+        let config = manager.read().unwrap();
+        let upgraded: toml::value::Table =
+            toml::from_str(&fs::read_to_string(&settings_path).unwrap()).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.xxhash_seed, u64::MAX);
+        assert_eq!(config.local_username, "User1");
+        assert_eq!(
+            upgraded["version"].as_integer(),
+            Some(CONFIG_VERSION as i64)
+        );
+        assert_eq!(
+            upgraded["xxhash_seed"].as_str(),
+            Some("18446744073709551615")
+        );
+    }
+
+    #[test]
+    fn missing_settings_have_defaults() {
+        let actual: Config = toml::from_str("local_username = \"User1\"").unwrap();
+
+        assert_eq!(
+            actual,
+            Config {
+                local_username: "User1".to_string(),
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn large_seeds_round_trip() {
+        let config = Config {
+            xxhash_seed: u64::MAX - 1,
+            ..Config::default()
+        };
+
+        let toml_str = toml::to_string(&config).unwrap();
+        let actual: Config = toml::from_str(&toml_str).unwrap();
+
+        assert!(toml_str.contains("xxhash_seed = \"18446744073709551614\""));
+        assert_eq!(actual, config);
+    }
+
+    #[test]
+    fn validate_names_the_invalid_setting() {
+        let key = |config: Config| match config.validate() {
+            Err(ConfigError::InvalidValue(key, _)) => key,
+            result => panic!("expected an invalid value, got {:?}", result),
+        };
+
+        let config = Config {
+            chunk_threshold: 0,
+            ..Config::default()
+        };
+        assert_eq!(key(config), "chunk_threshold");
+
+        let mut config = Config::default();
+        config
+            .save_backends
+            .insert("Celeste".to_string(), "nas".to_string());
+        assert_eq!(key(config), "save_backends.Celeste");
+
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn threads_default_to_zero() {
+        let toml_str = r#"
+            db_location = "db_location"
+            data_location = "data_location"
+            xxhash_seed = 1337
+            local_username = "User1"
+        "#;
+
+        let actual: Config = toml::from_str(toml_str).unwrap();
+
+        assert_eq!(actual.threads, 0);
+    }
+
+    #[test]
+    fn chunk_threshold_has_default() {
+        let toml_str = r#"
+            db_location = "db_location"
+            data_location = "data_location"
+            xxhash_seed = 1337
+            local_username = "User1"
+        "#;
+
+        let actual: Config = toml::from_str(toml_str).unwrap();
+
+        assert_eq!(actual.chunk_threshold, 16 * 1024 * 1024);
+    }
+
+    #[test]
+    fn storage_for_prefers_uuid_then_name_then_default() {
+        let toml_str = r#"
+            db_location = "db_location"
+            data_location = "data_location"
+            xxhash_seed = 1337
+            local_username = "User1"
+            default_backend = "nas"
+
+            [backends.nas]
+            type = "local"
+            path = "/mnt/nas"
+
+            [backends.minio]
+            type = "s3"
+            endpoint = "http://127.0.0.1:9000"
+            region = "us-east-1"
+            bucket = "saves"
+            access_key = "minioadmin"
+            secret_key = "minioadmin"
+
+            [save_backends]
+            Celeste = "minio"
+            some-uuid = "nas"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let nas = config.backends["nas"].clone();
+        let minio = config.backends["minio"].clone();
+
+        let round_trip: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(
+            nas,
+            StorageConfig::Local {
+                path: Some(PathBuf::from("/mnt/nas"))
+            }
+        );
+        assert!(matches!(minio, StorageConfig::S3(_)));
+        assert_eq!(config.storage_for("other-uuid", "Celeste"), minio);
+        assert_eq!(config.storage_for("some-uuid", "Celeste"), nas);
+        assert_eq!(config.storage_for("other-uuid", "Hades"), nas);
+        assert_eq!(round_trip, config);
+    }
+
+    #[test]
+    fn rename_changes_storage_only_across_backends() {
+        let toml_str = r#"
+            db_location = "db_location"
+            data_location = "data_location"
+            xxhash_seed = 1337
+            local_username = "User1"
+
+            [backends.nas]
+            type = "local"
+            path = "/mnt/nas"
+
+            [backends.usb]
+            type = "local"
+            path = "/mnt/usb"
+
+            [save_backends]
+            Celeste = "nas"
+            Hades = "nas"
+            Bastion = "usb"
+            some-uuid = "usb"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+
+        assert!(!config.rename_changes_storage("other-uuid", "Celeste", "Hades"));
+        assert!(config.rename_changes_storage("other-uuid", "Celeste", "Bastion"));
+        assert!(config.rename_changes_storage("other-uuid", "Celeste", "Transistor"));
+        assert!(!config.rename_changes_storage("other-uuid", "Transistor", "Pyre"));
+
+        // A backend assigned to the UUID follows the save regardless of its name
+        assert!(!config.rename_changes_storage("some-uuid", "Celeste", "Transistor"));
+    }
+
+    #[test]
+    fn storage_defaults_to_data_location() {
+        let config = Config::default();
+
+        assert_eq!(config.storage_for("uuid", "name"), StorageConfig::default());
     }
 
     #[test]
@@ -0,0 +1,487 @@
+//! Builds a `Config` out of several layers, each of which only has to contain the values it changes.
+//!
+//! From lowest to highest precedence, `Layers::discover` reads:
+//!
+//! 1. The built-in defaults
+//! 2. The system file (`/etc/save-sync/settings.toml` on Unix, `%ProgramData%\save-sync\settings.toml` on Windows)
+//! 3. The user file (`settings.toml` in the config directory, or `SAVE_SYNC_CONFIG_PATH`)
+//! 4. The nearest `.save-sync.toml` in the current directory or one of its ancestors
+//! 5. `SAVE_SYNC_*` environment variables, like `SAVE_SYNC_DB_LOCATION` or `SAVE_SYNC_THREADS`
+//!
+//! Command line flags can be layered on top with `with_file` and `with_value`.
+//!
+//! # Examples
+//! ```
+//! use save_sync::config::layers::{Layers, Origin};
+//! # use std::path::PathBuf;
+//!
+//! let vars = vec![("SAVE_SYNC_THREADS".to_string(), "4".to_string())];
+//! let layers = Layers::new()
+//!     .with_env(vars)
+//!     .unwrap()
+//!     .with_value("db_location", "/tmp/saves.db".into(), Origin::Flag("--db".to_string()));
+//!
+//! let config = layers.config().unwrap();
+//!
+//! assert_eq!(config.threads, 4);
+//! assert_eq!(config.db_location, PathBuf::from("/tmp/saves.db"));
+//! assert_eq!(layers.origin("threads"), Some(&Origin::Environment("SAVE_SYNC_THREADS".to_string())));
+//! assert_eq!(layers.origin("local_username"), Some(&Origin::Default));
+//! ```
+use super::{migrations, Config, ConfigError, ConfigManager};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+
+/// The name of the file which applies to the directory it's in and every directory below it
+pub const DIRECTORY_FILE_NAME: &str = ".save-sync.toml";
+
+/// Settings which can be set with a `SAVE_SYNC_` environment variable, and whether they are numbers
+const ENV_KEYS: &[(&str, bool)] = &[
+    ("db_location", false),
+    ("data_location", false),
+    ("xxhash_seed", true),
+    ("local_username", false),
+    ("threads", true),
+    ("chunk_threshold", true),
+    ("lock_timeout", true),
+    ("default_backend", false),
+];
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Directory(PathBuf),
+    Environment(String),
+    /// A file which was passed with `--config`
+    File(PathBuf),
+    Flag(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::System(path) => write!(f, "system file {}", path.display()),
+            Origin::User(path) => write!(f, "user file {}", path.display()),
+            Origin::Directory(path) => write!(f, "directory file {}", path.display()),
+            Origin::Environment(name) => write!(f, "environment variable {}", name),
+            Origin::File(path) => write!(f, "--config file {}", path.display()),
+            Origin::Flag(flag) => write!(f, "{} flag", flag),
+        }
+    }
+}
+
+/// Settings merged from several layers, along with the layer every setting came from
+#[derive(Debug, Clone)]
+pub struct Layers {
+    table: Table,
+    origins: BTreeMap<String, Origin>,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layers {
+    /// Only the built-in defaults
+    pub fn new() -> Layers {
+        let mut layers = Layers {
+            table: Table::new(),
+            origins: BTreeMap::new(),
+        };
+
+        match Value::try_from(Config::default()) {
+            Ok(Value::Table(defaults)) => layers.merge(defaults, &Origin::Default),
+            _ => unreachable!("Config always serializes into a table"),
+        }
+
+        layers
+    }
+
+    /// The defaults, system file, user file, directory file and environment variables, in that order
+    pub fn discover() -> Result<Layers, ConfigError> {
+        let mut layers = Self::new();
+
+        if let Some(path) = Self::system_file() {
+            layers = layers.with_file(&path, Origin::System(path.clone()))?;
+        }
+
+        let manager = ConfigManager::default();
+        manager.upgrade()?;
+
+        let user = manager.config_file_path;
+        layers = layers.with_file(&user, Origin::User(user.clone()))?;
+
+        if let Some(path) = std::env::current_dir()
+            .ok()
+            .and_then(|dir| Self::directory_file(&dir))
+        {
+            layers = layers.with_file(&path, Origin::Directory(path.clone()))?;
+        }
+
+        // Variables which aren't valid unicode can't name a setting
+        let vars = std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+
+        layers.with_env(vars)
+    }
+
+    /// Layers the settings in the file at `path` on top. A file which doesn't exist is skipped
+    pub fn with_file<P: AsRef<Path>>(
+        mut self,
+        path: &P,
+        origin: Origin,
+    ) -> Result<Layers, ConfigError> {
+        let path = path.as_ref();
+
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(self),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut table: Table = toml::from_str(&text)
+            .map_err(|err| ConfigError::InvalidFile(path.to_string_lossy().to_string(), err))?;
+
+        // Files which only change a few settings are upgraded too, but only in memory
+        migrations::migrate(&mut table)?;
+
+        self.merge(table, &origin);
+        Ok(self)
+    }
+
+    /// Layers every `SAVE_SYNC_*` variable in `vars` which names a setting on top. Other variables are ignored
+    pub fn with_env<I>(mut self, vars: I) -> Result<Layers, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, text) in vars {
+            let key = match name.strip_prefix("SAVE_SYNC_") {
+                Some(key) => key.to_lowercase(),
+                None => continue,
+            };
+
+            let is_number = match ENV_KEYS.iter().find(|(env_key, _)| *env_key == key) {
+                Some((_, is_number)) => *is_number,
+                None => continue,
+            };
+
+            let value = if is_number {
+                // Seeds which are too large for a TOML integer are stored as strings
+                match (text.parse::<i64>(), text.parse::<u64>()) {
+                    (Ok(number), _) => Value::Integer(number),
+                    (_, Ok(_)) => Value::String(text),
+                    _ => return Err(ConfigError::InvalidVariable(name, text)),
+                }
+            } else {
+                Value::String(text)
+            };
+
+            self = self.with_value(&key, value, Origin::Environment(name));
+        }
+
+        Ok(self)
+    }
+
+    /// Layers a single setting on top
+    pub fn with_value(mut self, key: &str, value: Value, origin: Origin) -> Layers {
+        let mut table = Table::new();
+        table.insert(key.to_string(), value);
+
+        self.merge(table, &origin);
+        self
+    }
+
+    /// The settings of every layer combined
+    pub fn config(&self) -> Result<Config, ConfigError> {
+        Ok(Value::Table(self.table.clone()).try_into()?)
+    }
+
+    /// The layer the setting called `key` came from. Nested settings are separated by dots, like `backends.nas.path`
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        self.origins.get(key)
+    }
+
+    /// Every setting which isn't a table, along with the layer it came from, sorted by name
+    pub fn entries(&self) -> Vec<(String, &Value, &Origin)> {
+        let mut entries = vec![];
+        self.collect_entries("", &self.table, &mut entries);
+
+        entries
+    }
+
+    fn collect_entries<'a>(
+        &'a self,
+        prefix: &str,
+        table: &'a Table,
+        entries: &mut Vec<(String, &'a Value, &'a Origin)>,
+    ) {
+        for (key, value) in table {
+            let key = Self::join(prefix, key);
+
+            match value {
+                Value::Table(table) => self.collect_entries(&key, table, entries),
+                value => {
+                    if let Some(origin) = self.origins.get(&key) {
+                        entries.push((key, value, origin));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tables are merged key by key, anything else replaces what was there
+    fn merge(&mut self, overlay: Table, origin: &Origin) {
+        let mut table = std::mem::take(&mut self.table);
+        self.merge_into(&mut table, "", overlay, origin);
+        self.table = table;
+    }
+
+    fn merge_into(&mut self, base: &mut Table, prefix: &str, overlay: Table, origin: &Origin) {
+        for (key, value) in overlay {
+            let path = Self::join(prefix, &key);
+
+            match (base.get_mut(&key), value) {
+                (Some(Value::Table(base)), Value::Table(overlay)) => {
+                    self.merge_into(base, &path, overlay, origin)
+                }
+                (_, value) => {
+                    let nested = format!("{}.", path);
+                    self.origins
+                        .retain(|key, _| *key != path && !key.starts_with(&nested));
+                    self.record(&path, &value, origin);
+
+                    base.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, path: &str, value: &Value, origin: &Origin) {
+        match value {
+            Value::Table(table) => {
+                for (key, value) in table {
+                    self.record(&Self::join(path, key), value, origin);
+                }
+            }
+            _ => {
+                self.origins.insert(path.to_string(), origin.clone());
+            }
+        }
+    }
+
+    fn join(prefix: &str, key: &str) -> String {
+        match prefix.is_empty() {
+            true => key.to_string(),
+            false => format!("{}.{}", prefix, key),
+        }
+    }
+
+    /// The file every user of this machine shares
+    pub fn system_file() -> Option<PathBuf> {
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(|dir| PathBuf::from(dir).join("save-sync").join("settings.toml"))
+        } else if cfg!(unix) {
+            Some(PathBuf::from("/etc/save-sync/settings.toml"))
+        } else {
+            None
+        }
+    }
+
+    /// The nearest `.save-sync.toml` in `dir` or one of its ancestors
+    pub fn directory_file<P: AsRef<Path>>(dir: &P) -> Option<PathBuf> {
+        dir.as_ref()
+            .ancestors()
+            .map(|dir| dir.join(DIRECTORY_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageConfig;
+    use tempfile::TempDir;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn defaults_come_first() {
+        let layers = Layers::new();
+
+        assert_eq!(layers.config().unwrap(), Config::default());
+        assert!(layers
+            .entries()
+            .iter()
+            .all(|(_, _, origin)| **origin == Origin::Default));
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let test_dir = TempDir::new().unwrap();
+        let system = test_dir.path().join("system.toml");
+        let user = test_dir.path().join("user.toml");
+
+        fs::write(&system, "threads = 2\nlocal_username = \"Shared\"\n").unwrap();
+        fs::write(&user, "threads = 3\n").unwrap();
+
+        let layers = Layers::new()
+            .with_file(&system, Origin::System(system.clone()))
+            .unwrap()
+            .with_file(&user, Origin::User(user.clone()))
+            .unwrap()
+            .with_env(env(&[("SAVE_SYNC_CHUNK_THRESHOLD", "1024")]))
+            .unwrap()
+            .with_value(
+                "threads",
+                Value::Integer(8),
+                Origin::Flag("--threads".to_string()),
+            );
+
+        let config = layers.config().unwrap();
+
+        assert_eq!(config.threads, 8);
+        assert_eq!(config.local_username, "Shared");
+        assert_eq!(config.chunk_threshold, 1024);
+        assert_eq!(config.xxhash_seed, Config::default().xxhash_seed);
+
+        assert_eq!(
+            layers.origin("threads"),
+            Some(&Origin::Flag("--threads".to_string()))
+        );
+        assert_eq!(
+            layers.origin("local_username"),
+            Some(&Origin::System(system))
+        );
+        assert_eq!(
+            layers.origin("chunk_threshold"),
+            Some(&Origin::Environment(
+                "SAVE_SYNC_CHUNK_THRESHOLD".to_string()
+            ))
+        );
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_skipped() {
+        let test_dir = TempDir::new().unwrap();
+        let path = test_dir.path().join("settings.toml");
+
+        let layers = Layers::new()
+            .with_file(&path, Origin::User(path.clone()))
+            .unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(layers.config().unwrap(), Config::default());
+    }
+
+    #[test]
+    fn tables_are_merged() {
+        let test_dir = TempDir::new().unwrap();
+        let system = test_dir.path().join("system.toml");
+        let user = test_dir.path().join("user.toml");
+
+        fs::write(
+            &system,
+            "[backends.nas]\ntype = \"local\"\npath = \"/mnt/nas\"\n",
+        )
+        .unwrap();
+        fs::write(
+            &user,
+            "[backends.usb]\ntype = \"local\"\npath = \"/mnt/usb\"\n",
+        )
+        .unwrap();
+
+        let layers = Layers::new()
+            .with_file(&system, Origin::System(system.clone()))
+            .unwrap()
+            .with_file(&user, Origin::User(user.clone()))
+            .unwrap();
+
+        let config = layers.config().unwrap();
+
+        assert_eq!(config.backends.len(), 2);
+        assert_eq!(
+            config.backends["nas"],
+            StorageConfig::Local {
+                path: Some(PathBuf::from("/mnt/nas"))
+            }
+        );
+        assert_eq!(
+            layers.origin("backends.nas.path"),
+            Some(&Origin::System(system))
+        );
+        assert_eq!(
+            layers.origin("backends.usb.path"),
+            Some(&Origin::User(user))
+        );
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn unrelated_variables_are_ignored() {
+        let vars = env(&[
+            ("SAVE_SYNC_CONFIG_PATH", "/some/where"),
+            ("SAVE_SYNC_UNKNOWN", "value"),
+            ("HOME", "/home/user"),
+        ]);
+
+        let layers = Layers::new().with_env(vars).unwrap();
+
+        assert_eq!(layers.config().unwrap(), Config::default());
+    }
+
+    #[test]
+    fn invalid_numbers_are_rejected() {
+        let result = Layers::new().with_env(env(&[("SAVE_SYNC_THREADS", "many")]));
+
+        assert!(matches!(result, Err(ConfigError::InvalidVariable(..))));
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let test_dir = TempDir::new().unwrap();
+        let path = test_dir.path().join("settings.toml");
+        fs::write(&path, "threads = ").unwrap();
+
+        let result = Layers::new().with_file(&path, Origin::User(path.clone()));
+
+        test_dir.close().unwrap();
+        assert!(matches!(result, Err(ConfigError::InvalidFile(..))));
+    }
+
+    #[test]
+    fn nearest_directory_file_is_found() {
+        let test_dir = TempDir::new().unwrap();
+        let nested = test_dir.path().join("Games").join("Celeste");
+        fs::create_dir_all(&nested).unwrap();
+
+        let outer = test_dir.path().join(DIRECTORY_FILE_NAME);
+        let inner = test_dir.path().join("Games").join(DIRECTORY_FILE_NAME);
+        fs::write(&outer, "").unwrap();
+        fs::write(&inner, "").unwrap();
+
+        let found = Layers::directory_file(&nested);
+
+        test_dir.close().unwrap();
+        assert_eq!(found, Some(inner));
+    }
+}
@@ -0,0 +1,114 @@
+//! Upgrades config files which were written by older versions of save-sync.
+//!
+//! Every migration takes a file from one version to the next, so a file is upgraded by running every migration
+//! after its version in order. Files without a `version` were written before versions existed, and are version 1.
+use super::{ConfigError, CONFIG_VERSION};
+use toml::value::Table;
+use toml::Value;
+
+type Migration = fn(&mut Table) -> Result<(), ConfigError>;
+
+/// `MIGRATIONS[i]` upgrades a file from version `i + 1` to version `i + 2`
+const MIGRATIONS: &[Migration] = &[seed_as_unsigned];
+
+/// Upgrades `table` to `CONFIG_VERSION`. Returns whether anything had to be upgraded
+///
+/// # Examples
+/// ```
+/// use save_sync::config::migrations;
+/// use save_sync::config::CONFIG_VERSION;
+///
+/// let mut table = toml::from_str("xxhash_seed = -1").unwrap();
+///
+/// assert!(migrations::migrate(&mut table).unwrap());
+/// assert_eq!(table["xxhash_seed"].as_str(), Some("18446744073709551615"));
+/// assert_eq!(table["version"].as_integer(), Some(CONFIG_VERSION as i64));
+/// ```
+pub fn migrate(table: &mut Table) -> Result<bool, ConfigError> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) if *version >= 1 => *version,
+        Some(value) => {
+            let reason = format!("{} is not a config version", value);
+            return Err(ConfigError::InvalidValue("version".to_string(), reason));
+        }
+    };
+
+    if version > CONFIG_VERSION as i64 {
+        return Err(ConfigError::UnsupportedVersion(version, CONFIG_VERSION));
+    }
+
+    if version == CONFIG_VERSION as i64 {
+        return Ok(false);
+    }
+
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(table)?;
+    }
+
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION as i64));
+    Ok(true)
+}
+
+/// Version 1 stored `xxhash_seed` as an i64, so seeds larger than `i64::MAX` were written as negative numbers.
+/// Version 2 stores those seeds as a string instead
+fn seed_as_unsigned(table: &mut Table) -> Result<(), ConfigError> {
+    if let Some(Value::Integer(seed)) = table.get("xxhash_seed") {
+        if *seed < 0 {
+            let seed = (*seed as u64).to_string();
+            table.insert("xxhash_seed".to_string(), Value::String(seed));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_files_are_unchanged() {
+        let mut table: Table = toml::from_str("version = 2\nxxhash_seed = 1337").unwrap();
+        let expected = table.clone();
+
+        assert!(!migrate(&mut table).unwrap());
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn unversioned_files_are_upgraded() {
+        let mut table: Table = toml::from_str("xxhash_seed = 1337").unwrap();
+
+        assert!(migrate(&mut table).unwrap());
+        assert_eq!(table["xxhash_seed"].as_integer(), Some(1337));
+        assert_eq!(table["version"].as_integer(), Some(CONFIG_VERSION as i64));
+    }
+
+    #[test]
+    fn negative_seeds_keep_their_bits() {
+        let mut table: Table = toml::from_str("version = 1\nxxhash_seed = -2").unwrap();
+
+        migrate(&mut table).unwrap();
+
+        assert_eq!(table["xxhash_seed"].as_str(), Some("18446744073709551614"));
+    }
+
+    #[test]
+    fn newer_files_are_rejected() {
+        let mut table: Table = toml::from_str("version = 3").unwrap();
+
+        let result = migrate(&mut table);
+
+        assert!(matches!(result, Err(ConfigError::UnsupportedVersion(3, 2))));
+    }
+
+    #[test]
+    fn invalid_versions_are_rejected() {
+        let mut table: Table = toml::from_str("version = \"two\"").unwrap();
+
+        let result = migrate(&mut table);
+
+        assert!(matches!(result, Err(ConfigError::InvalidValue(key, _)) if key == "version"));
+    }
+}
@@ -0,0 +1,474 @@
+use crate::archive::query::{ChunkQuery, FileQuery, SaveQuery};
+use crate::archive::{Archive, ArchiveError, HASH_BUFFER_SIZE};
+use crate::config::{Config, ConfigError};
+use crate::database::{Database, DatabaseError};
+use crate::lock::{Lock, LockError, LockMode, SaveLock};
+use crate::models::{NewSetting, Save};
+use crate::progress::NoProgress;
+use crate::storage::{self, StorageBackend, StorageError};
+use crate::workers::{WorkerError, Workers};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContextError {
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+}
+
+/// A handle which owns a `Config` and the `Database` it points to.
+///
+/// Operations which are handed a `SaveSync` read everything from it instead of the global config,
+/// so several configurations can be used side by side in one process.
+///
+/// # Examples
+/// ```
+/// use save_sync::config::Config;
+/// use save_sync::SaveSync;
+/// # use tempfile::TempDir;
+///
+/// # let test_dir = TempDir::new().unwrap();
+/// let config = Config {
+///     db_location: test_dir.path().join("saves.db"),
+///     data_location: test_dir.path().join("data"),
+///     xxhash_seed: 11037,
+///     ..Config::default()
+/// };
+///
+/// let ctx = SaveSync::new(config).unwrap();
+///
+/// assert_eq!(ctx.hash_seed(), 11037);
+/// assert!(ctx.db().get_all_saves().is_none());
+/// ```
+pub struct SaveSync {
+    config: Config,
+    db: Database,
+}
+
+/// The directory in the data location which holds the lock files
+const LOCK_DIR: &str = "locks";
+/// Locked by every process, but only exclusively by the ones which work on every save at once
+const GLOBAL_LOCK: &str = "save-sync.lock";
+/// Locked exclusively while unused chunks are removed from storage
+const CHUNK_LOCK: &str = "chunks.lock";
+
+/// The setting in the database which remembers the seed its files were hashed with
+const SEED_SETTING: &str = "xxhash_seed";
+
+impl SaveSync {
+    /// Opens the database at the `db_location` of `config`
+    pub fn new(config: Config) -> Result<SaveSync, ContextError> {
+        config.validate()?;
+        let busy_timeout = Duration::from_secs(config.lock_timeout);
+        let db = Database::with_busy_timeout(&config.db_location, busy_timeout)?;
+
+        Self::with_database(config, db)
+    }
+
+    /// Fails if `config` is invalid, or if it has a different seed than the one the files in `db` were hashed with
+    pub fn with_database(config: Config, db: Database) -> Result<SaveSync, ContextError> {
+        config.validate()?;
+
+        let ctx = SaveSync { config, db };
+        ctx.check_seed()?;
+
+        Ok(ctx)
+    }
+
+    /// Takes a snapshot of the global config. Later changes to the global config are not seen by this handle
+    pub fn from_global() -> Result<SaveSync, ContextError> {
+        Self::new(Config::clone_config()?)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
+    /// The seed which every hash is calculated with
+    pub fn hash_seed(&self) -> u64 {
+        self.config.xxhash_seed
+    }
+
+    /// Hashes the file at `path` like `Archive::calc_hash` does, but with the seed of this handle
+    pub fn calc_hash<P: AsRef<Path>>(&self, path: &P) -> Result<u64, ArchiveError> {
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+
+        Archive::calc_hash_with(path, self.hash_seed(), &mut buffer, &NoProgress)
+    }
+
+    /// Creates a pool with as many threads as the config asks for
+    pub fn workers(&self) -> Result<Workers, WorkerError> {
+        Workers::new(self.config.threads)
+    }
+
+    /// Every hash in the database would look like a change if the seed changed, so that isn't allowed
+    /// while there are files in it
+    fn check_seed(&self) -> Result<(), ConfigError> {
+        let seed = self.hash_seed().to_string();
+
+        match self.db.get_setting(SEED_SETTING) {
+            Some(setting) if setting.value == seed => Ok(()),
+            Some(setting) if self.db.has_files() => {
+                let reason = format!(
+                    "the files in {} were hashed with {}",
+                    self.config.db_location.display(),
+                    setting.value
+                );
+
+                Err(ConfigError::InvalidValue(SEED_SETTING.to_string(), reason))
+            }
+            _ => {
+                self.db.set_setting(NewSetting {
+                    name: SEED_SETTING,
+                    value: &seed,
+                    modified_at: Utc::now().naive_utc(),
+                });
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Keeps other save-sync processes from working on the save with `uuid` until the returned lock is dropped.
+    ///
+    /// Readers take a `LockMode::Shared` lock, so that they only have to wait for writers.
+    /// Waits for up to `lock_timeout` seconds before giving up
+    pub fn lock_save(&self, uuid: &str, mode: LockMode) -> Result<SaveLock, LockError> {
+        let path = self
+            .config
+            .data_location
+            .join(LOCK_DIR)
+            .join(format!("{}.lock", uuid));
+
+        let global = self.lock(&self.global_lock_path(), LockMode::Shared)?;
+        let save = self.lock(&path, mode)?;
+
+        Ok(SaveLock::new(global, save))
+    }
+
+    /// Keeps other save-sync processes from working on any save until the returned lock is dropped
+    pub fn lock_all(&self) -> Result<Lock, LockError> {
+        self.lock(&self.global_lock_path(), LockMode::Exclusive)
+    }
+
+    /// Keeps chunks from being removed from storage until the returned lock is dropped.
+    ///
+    /// Chunks which are already in storage aren't written again, so a chunk which is being backed up must not be
+    /// removed before the File which uses it is in the database. Backups take a `LockMode::Shared` lock around
+    /// storing chunks and recording them, and only removing chunks takes a `LockMode::Exclusive` one
+    pub fn lock_chunks(&self, mode: LockMode) -> Result<Lock, LockError> {
+        let path = self.config.data_location.join(LOCK_DIR).join(CHUNK_LOCK);
+
+        self.lock(&path, mode)
+    }
+
+    /// Whether the chunk with `hash` is used by a File of a save which is stored in the same backend as `save`.
+    ///
+    /// Every backend has a chunk store of its own, so chunks which are only used in other backends aren't counted.
+    /// Files whose save can't be found are, since it's unknown which backend they're in
+    pub fn is_chunk_used(&self, save: &Save, hash: &[u8]) -> bool {
+        let storage = self.config.storage_for(&save.uuid, &save.friendly_name);
+        let chunks = self
+            .db
+            .get_chunks(ChunkQuery::new().with_hash(hash))
+            .unwrap_or_default();
+
+        chunks.iter().any(|chunk| {
+            let owner = self
+                .db
+                .get_file(FileQuery::new().with_id(chunk.file_id))
+                .and_then(|file| self.db.get_save(SaveQuery::new().with_id(file.save_id)));
+
+            match owner {
+                Some(other) => {
+                    self.config.storage_for(&other.uuid, &other.friendly_name) == storage
+                }
+                None => true,
+            }
+        })
+    }
+
+    fn global_lock_path(&self) -> PathBuf {
+        self.config.data_location.join(LOCK_DIR).join(GLOBAL_LOCK)
+    }
+
+    fn lock(&self, path: &Path, mode: LockMode) -> Result<Lock, LockError> {
+        Lock::acquire(&path, mode, Duration::from_secs(self.config.lock_timeout))
+    }
+
+    /// Opens the storage backend the config assigns to the save with `uuid` and `friendly_name`
+    pub fn storage_for(
+        &self,
+        uuid: &str,
+        friendly_name: &str,
+    ) -> Result<Arc<dyn StorageBackend>, StorageError> {
+        let storage_config = self.config.storage_for(uuid, friendly_name);
+
+        storage::open(&storage_config, &self.config.data_location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileType, NewChunk, NewFile, NewSave, NewUser};
+    use crate::storage::StorageConfig;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn config_in(dir: &Path, seed: u64) -> Config {
+        Config {
+            db_location: dir.join("saves.db"),
+            data_location: dir.join("data"),
+            xxhash_seed: seed,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn handles_do_not_share_config() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let file_path: PathBuf = [tmp_dir, &PathBuf::from("slot1.sav")].iter().collect();
+        fs::write(&file_path, b"Some save data").unwrap();
+
+        fs::create_dir(tmp_dir.join("first")).unwrap();
+        fs::create_dir(tmp_dir.join("second")).unwrap();
+
+        let first = SaveSync::new(config_in(&tmp_dir.join("first"), 1)).unwrap();
+        let second = SaveSync::new(config_in(&tmp_dir.join("second"), 2)).unwrap();
+
+        let first_hash = first.calc_hash(&file_path).unwrap();
+        let second_hash = second.calc_hash(&file_path).unwrap();
+
+        assert_eq!(
+            first_hash,
+            Archive::calc_hash_from_bytes_with(b"Some save data", 1)
+        );
+        assert_eq!(
+            second_hash,
+            Archive::calc_hash_from_bytes_with(b"Some save data", 2)
+        );
+        assert!(tmp_dir.join("first").join("saves.db").exists());
+        assert!(tmp_dir.join("second").join("saves.db").exists());
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn seed_cannot_change_once_files_are_hashed() {
+        use crate::models::{NewFile, NewSave, NewUser};
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let ctx = SaveSync::new(config_in(tmp_dir, 1)).unwrap();
+        drop(ctx);
+
+        // Nothing has been hashed yet, so the seed may still change
+        let ctx = SaveSync::new(config_in(tmp_dir, 2)).unwrap();
+        let time = Utc::now().naive_utc();
+
+        ctx.db()
+            .create_user(NewUser {
+                username: "User1",
+                created_at: time,
+                modified_at: time,
+            })
+            .unwrap();
+        ctx.db()
+            .create_save(NewSave {
+                friendly_name: "",
+                save_path: "/saves/game",
+                backup_path: "/data/uuid/game",
+                uuid: "uuid",
+                user_id: 1,
+                created_at: time,
+                modified_at: time,
+            })
+            .unwrap();
+        ctx.db()
+            .create_file(NewFile {
+                file_path: "/saves/game/slot1.sav",
+                file_hash: &[0; 8],
+                save_id: 1,
+                created_at: time,
+                modified_at: time,
+                file_type: 0,
+                link_target: None,
+                permissions: None,
+                file_mtime: None,
+                file_size: None,
+            })
+            .unwrap();
+        drop(ctx);
+
+        let same = SaveSync::new(config_in(tmp_dir, 2));
+        let changed = SaveSync::new(config_in(tmp_dir, 1));
+
+        test_dir.close().unwrap();
+        assert!(same.is_ok());
+        assert!(matches!(
+            changed,
+            Err(ContextError::ConfigError(ConfigError::InvalidValue(key, _))) if key == "xxhash_seed"
+        ));
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let test_dir = TempDir::new().unwrap();
+        let mut config = config_in(test_dir.path(), 1);
+        config.default_backend = Some("nas".to_string());
+
+        let result = SaveSync::new(config);
+
+        test_dir.close().unwrap();
+        assert!(matches!(
+            result,
+            Err(ContextError::ConfigError(ConfigError::InvalidValue(key, _))) if key == "default_backend"
+        ));
+    }
+
+    #[test]
+    fn saves_are_locked_one_at_a_time() {
+        let test_dir = TempDir::new().unwrap();
+        let mut config = config_in(test_dir.path(), 1);
+        config.lock_timeout = 0;
+        let ctx = SaveSync::new(config).unwrap();
+
+        let first = ctx.lock_save("first", LockMode::Exclusive).unwrap();
+        let same = ctx.lock_save("first", LockMode::Shared);
+        let other = ctx.lock_save("second", LockMode::Exclusive).unwrap();
+        let everything = ctx.lock_all();
+        drop((first, other));
+        let released = ctx.lock_all().is_ok();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+        assert!(matches!(same, Err(LockError::Timeout(_))));
+        assert!(matches!(everything, Err(LockError::Timeout(_))));
+        assert!(released);
+    }
+
+    #[test]
+    fn chunks_are_not_removed_while_stored() {
+        let test_dir = TempDir::new().unwrap();
+        let mut config = config_in(test_dir.path(), 1);
+        config.lock_timeout = 0;
+        let ctx = SaveSync::new(config).unwrap();
+
+        let storing = ctx.lock_chunks(LockMode::Shared).unwrap();
+        let also_storing = ctx.lock_chunks(LockMode::Shared).is_ok();
+        let removing = ctx.lock_chunks(LockMode::Exclusive);
+        drop(storing);
+        let released = ctx.lock_chunks(LockMode::Exclusive).is_ok();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+        assert!(also_storing);
+        assert!(matches!(removing, Err(LockError::Timeout(_))));
+        assert!(released);
+    }
+
+    #[test]
+    fn chunks_are_only_used_within_their_backend() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let mut config = config_in(tmp_dir, 1);
+        let usb = StorageConfig::Local {
+            path: Some(tmp_dir.join("usb")),
+        };
+
+        config.backends.insert("usb".to_string(), usb);
+        config
+            .save_backends
+            .insert("Hades".to_string(), "usb".to_string());
+
+        let ctx = SaveSync::new(config).unwrap();
+        let db = ctx.db();
+        let time = Utc::now().naive_utc();
+
+        db.create_user(NewUser {
+            username: "User1",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        for (id, name) in ["Celeste", "Hades", "Transistor"].iter().enumerate() {
+            let save_path = format!("/saves/{}", name);
+
+            db.create_save(NewSave {
+                friendly_name: name,
+                save_path: &save_path,
+                backup_path: &save_path,
+                uuid: name,
+                user_id: 1,
+                created_at: time,
+                modified_at: time,
+            })
+            .unwrap();
+            db.create_file(NewFile {
+                file_path: &format!("{}/big.sav", save_path),
+                file_hash: &[0; 8],
+                save_id: id as i32 + 1,
+                created_at: time,
+                modified_at: time,
+                file_type: FileType::File.into(),
+                link_target: None,
+                permissions: None,
+                file_mtime: None,
+                file_size: Some(1024),
+            })
+            .unwrap();
+        }
+
+        // Celeste and Transistor are both in the default backend, but only Hades uses the chunk
+        db.create_chunks(&[NewChunk {
+            file_id: 2,
+            position: 0,
+            chunk_hash: &[7; 8],
+            chunk_size: 1024,
+            created_at: time,
+            modified_at: time,
+        }]);
+
+        let saves = db.get_all_saves().unwrap();
+        let used: Vec<bool> = saves
+            .iter()
+            .map(|save| ctx.is_chunk_used(save, &[7; 8]))
+            .collect();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+        assert_eq!(used, vec![false, true, false]);
+    }
+
+    #[test]
+    fn storage_is_relative_to_own_data_location() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db = Database::new(&tmp_dir.join("saves.db")).unwrap();
+        let ctx = SaveSync::with_database(config_in(tmp_dir, 1), db).unwrap();
+
+        let backend = ctx.storage_for("uuid", "name").unwrap();
+        backend.put("uuid/slot1.sav", b"Some save data").unwrap();
+
+        let stored = fs::read(tmp_dir.join("data").join("uuid").join("slot1.sav")).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(stored, b"Some save data");
+    }
+}
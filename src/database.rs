@@ -1,12 +1,34 @@
-use crate::archive::query::{FileQuery, SaveQuery, UserQuery};
+use crate::archive::query::{
+    ChunkQuery, ExecutableQuery, FileQuery, NoteQuery, SaveQuery, TagQuery, UserQuery,
+};
 use crate::models::*;
+use crate::paths;
 use crate::schema;
+use diesel::connection::SimpleConnection;
+use diesel::expression::AsExpression;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::r2d2::{
+    ConnectionManager, CustomizeConnection, Error as R2D2Error, Pool, PooledConnection,
+};
+use diesel::sql_types::{Integer, Text};
+use diesel::sqlite::Sqlite;
 use diesel::SqliteConnection;
-use std::path::Path;
+use std::path::{Path, MAIN_SEPARATOR_STR};
+use std::time::Duration;
 use thiserror::Error;
 
+diesel_infix_operator!(Glob, " GLOB ", backend: Sqlite);
+sql_function!(fn substr(text: Text, start: Integer) -> Text);
+
+/// Matches `left` against a glob pattern using SQLite's case-sensitive `GLOB` operator
+fn glob<T, U>(left: T, right: U) -> Glob<T, U::Expression>
+where
+    T: Expression<SqlType = Text>,
+    U: AsExpression<Text>,
+{
+    Glob::new(left, right.as_expression())
+}
+
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error(transparent)]
@@ -19,22 +41,50 @@ pub enum DatabaseError {
     IllegalPath(String),
     #[error("Unable to determine the parent of {0}")]
     UnknownPathParent(String),
+    #[error("{0} is already in the database.")]
+    AlreadyExists(String),
+    #[error(transparent)]
+    QueryError(#[from] diesel::result::Error),
 }
 
 pub struct Database {
     pool: Pool<ConnectionManager<SqliteConnection>>,
 }
 
+/// How long a query waits for another process to finish writing to the database before it fails
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Applies the settings SQLite only remembers per connection to every connection in the pool
+#[derive(Debug)]
+struct ConnectionOptions {
+    busy_timeout: Duration,
+}
+
+impl CustomizeConnection<SqliteConnection, R2D2Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), R2D2Error> {
+        let pragma = format!("PRAGMA busy_timeout = {};", self.busy_timeout.as_millis());
+
+        conn.batch_execute(&pragma).map_err(R2D2Error::QueryError)
+    }
+}
+
 impl Database {
     pub fn new<P: AsRef<Path>>(db_url: &P) -> Result<Database, DatabaseError> {
+        Self::with_busy_timeout(db_url, BUSY_TIMEOUT)
+    }
+
+    /// Like `new`, but queries wait up to `busy_timeout` for other processes which are writing to the database
+    pub fn with_busy_timeout<P: AsRef<Path>>(
+        db_url: &P,
+        busy_timeout: Duration,
+    ) -> Result<Database, DatabaseError> {
         Self::check_db_path(db_url)?;
 
-        let manager = ConnectionManager::new(db_url.as_ref().to_str().ok_or_else(|| {
-            DatabaseError::IllegalPath(db_url.as_ref().to_string_lossy().to_string())
-        })?);
+        let manager = ConnectionManager::new(Self::connection_url(db_url.as_ref())?);
 
         let pool = Pool::builder()
             .max_size(15) // TODO: Make Configurable? Is this even necessary?
+            .connection_customizer(Box::new(ConnectionOptions { busy_timeout }))
             .build(manager)
             .map_err(|_| DatabaseError::R2D2Error)?; // https://github.com/diesel-rs/diesel/issues/1919
 
@@ -50,6 +100,33 @@ impl Database {
         embedded_migrations::run(conn).expect("Failed to run embedded database migrations.");
     }
 
+    /// What SQLite is told to open. Paths which aren't UTF-8 are passed as a percent-encoded URI
+    fn connection_url(path: &Path) -> Result<String, DatabaseError> {
+        if let Some(url) = path.to_str() {
+            return Ok(url.to_string());
+        }
+
+        #[cfg(unix)]
+        {
+            use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+            use std::os::unix::ffi::OsStrExt;
+
+            const KEEP: &AsciiSet = &NON_ALPHANUMERIC
+                .remove(b'/')
+                .remove(b'-')
+                .remove(b'_')
+                .remove(b'.');
+
+            let bytes = path.as_os_str().as_bytes();
+            Ok(format!("file:{}", percent_encode(bytes, KEEP)))
+        }
+
+        #[cfg(not(unix))]
+        Err(DatabaseError::IllegalPath(
+            path.to_string_lossy().to_string(),
+        ))
+    }
+
     fn check_db_path<P: AsRef<Path>>(path: &P) -> Result<(), DatabaseError> {
         // Quick Check to make sure the parent directory of the db file exists
         let path_string = path.as_ref().to_string_lossy().to_owned().to_string();
@@ -70,86 +147,52 @@ impl Database {
         self.pool
     }
 
+    /// Turns the violation of a UNIQUE constraint into `DatabaseError::AlreadyExists`, naming `what` was a duplicate
+    fn unique_error(err: diesel::result::Error, what: String) -> DatabaseError {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        match err {
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                DatabaseError::AlreadyExists(what)
+            }
+            err => DatabaseError::QueryError(err),
+        }
+    }
+
     fn get_conn(&self) -> PooledConnection<ConnectionManager<SqliteConnection>> {
         self.pool
             .get()
             .expect("Unable to get DB connection from pool.")
     }
 
-    fn does_save_exist(&self, path: &str) -> bool {
-        use schema::saves::dsl::*;
+    /// Fails with `DatabaseError::AlreadyExists` if a save with the same path or UUID is already tracked
+    pub fn create_save(&self, save: NewSave) -> Result<(), DatabaseError> {
+        use schema::saves;
 
         let conn = self.get_conn();
-        let list: Vec<Save> = saves
-            .filter(save_path.eq(path))
-            .load(&conn)
-            .expect("Unable to query database.");
-
-        !list.is_empty()
-    }
-
-    fn does_file_exist(&self, path: &str) -> bool {
-        use schema::files::dsl::*;
 
-        let conn = self.get_conn();
-        let list: Vec<File> = files
-            .filter(file_path.eq(path))
-            .load(&conn)
-            .expect("Unable to query database.");
+        diesel::insert_into(saves::table)
+            .values(&save)
+            .execute(&conn)
+            .map_err(|err| {
+                let path = paths::decode(save.save_path);
+                Self::unique_error(err, format!("The save at {}", path.to_string_lossy()))
+            })?;
 
-        !list.is_empty()
+        Ok(())
     }
 
-    fn does_user_exist(&self, uname: &str) -> bool {
-        use schema::users::dsl::*;
+    pub fn get_save(&self, query: SaveQuery) -> Option<Save> {
+        if !Self::is_save_filtered(&query) {
+            return None;
+        }
 
         let conn = self.get_conn();
-        let list: Vec<User> = users
-            .filter(username.eq(uname))
+        let list: Vec<Save> = Self::filter_saves(&query)
+            .ok()?
             .load(&conn)
             .expect("Unable to query database.");
 
-        !list.is_empty()
-    }
-
-    pub fn create_save(&self, save: NewSave) {
-        // TODO: Return Result
-        use schema::saves;
-
-        if !self.does_save_exist(save.save_path) {
-            let conn = self.get_conn();
-
-            diesel::insert_into(saves::table)
-                .values(&save)
-                .execute(&conn)
-                .expect("Failed to create save in database.");
-        }
-    }
-
-    pub fn get_save(&self, query: SaveQuery) -> Option<Save> {
-        use schema::saves::dsl::*;
-
-        let err_msg = "Unable to query database.";
-        let conn = self.get_conn();
-        let mut list: Vec<Save> = vec![];
-
-        if let Some(search_id) = query.id {
-            list = saves.filter(id.eq(search_id)).load(&conn).expect(err_msg);
-        } else if let Some(q_uuid) = query.uuid {
-            list = saves.filter(uuid.eq(q_uuid)).load(&conn).expect(err_msg);
-        } else if let Some(name) = query.friendly_name {
-            list = saves
-                .filter(friendly_name.eq(name))
-                .load(&conn)
-                .expect(err_msg);
-        } else if let Some(path) = query.path {
-            let path_str = path.to_str()?;
-            list = saves
-                .filter(save_path.eq(path_str))
-                .load(&conn)
-                .expect(err_msg);
-        }
-
         match list.len() {
             0 => None,
             1 => Some(list.first()?.clone()),
@@ -158,19 +201,31 @@ impl Database {
     }
 
     pub fn get_saves(&self, query: SaveQuery) -> Option<Vec<Save>> {
+        use crate::archive::query::{Order, SaveOrder};
         use schema::saves::dsl::*;
 
-        let err_msg = "Unable to query database.";
         let conn = self.get_conn();
-        let mut list: Vec<Save> = vec![];
+        let mut statement = Self::filter_saves(&query).ok()?;
+
+        statement = match query.order_by.unwrap_or((SaveOrder::Id, Order::Ascending)) {
+            (SaveOrder::Id, Order::Ascending) => statement.order(id.asc()),
+            (SaveOrder::Id, Order::Descending) => statement.order(id.desc()),
+            (SaveOrder::FriendlyName, Order::Ascending) => statement.order(friendly_name.asc()),
+            (SaveOrder::FriendlyName, Order::Descending) => statement.order(friendly_name.desc()),
+            (SaveOrder::Path, Order::Ascending) => statement.order(save_path.asc()),
+            (SaveOrder::Path, Order::Descending) => statement.order(save_path.desc()),
+            (SaveOrder::CreatedAt, Order::Ascending) => statement.order(created_at.asc()),
+            (SaveOrder::CreatedAt, Order::Descending) => statement.order(created_at.desc()),
+            (SaveOrder::ModifiedAt, Order::Ascending) => statement.order(modified_at.asc()),
+            (SaveOrder::ModifiedAt, Order::Descending) => statement.order(modified_at.desc()),
+        };
 
-        if let Some(search_user_id) = query.user_id {
-            list = saves
-                .filter(user_id.eq(search_user_id))
-                .load(&conn)
-                .expect(err_msg);
+        if let Some((n, skip)) = Self::limit_offset(query.limit, query.offset) {
+            statement = statement.limit(n).offset(skip);
         }
 
+        let list: Vec<Save> = statement.load(&conn).expect("Unable to query database.");
+
         if list.is_empty() {
             None
         } else {
@@ -191,17 +246,39 @@ impl Database {
         }
     }
 
+    /// Applies `edit` to a save. When `edit` moves the save to another `save_path`,
+    /// every File in the save is moved along with it
     pub fn update_save(&self, edit: EditSave) {
         // TODO: Return Result
+        use schema::files;
         use schema::saves::dsl::*;
 
         let conn = self.get_conn();
-        let save_id = edit.id;
-
-        diesel::update(saves.filter(id.eq(save_id)))
-            .set(&edit)
-            .execute(&conn)
-            .expect("Failed to update save in database.");
+        let edit_id = edit.id;
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            if let Some(new_root) = edit.save_path {
+                let old_root: String = saves
+                    .filter(id.eq(edit_id))
+                    .select(save_path)
+                    .first(&conn)?;
+
+                // Paths are encoded one character at a time, so the encoded root is a prefix of every encoded path
+                let rest = substr(files::file_path, old_root.chars().count() as i32 + 1);
+
+                diesel::update(files::table.filter(files::save_id.eq(edit_id)))
+                    .set((
+                        files::file_path.eq(new_root.into_sql::<Text>().concat(rest)),
+                        files::modified_at.eq(edit.modified_at),
+                    ))
+                    .execute(&conn)?;
+            }
+
+            diesel::update(saves.filter(id.eq(edit_id)))
+                .set(&edit)
+                .execute(&conn)
+        })
+        .expect("Failed to update save in database.");
     }
 
     pub fn delete_save(&self, query: SaveQuery) -> Result<(), DatabaseError> {
@@ -211,20 +288,14 @@ impl Database {
         let err_msg = "Unable to delete save from database.";
         let conn = self.get_conn();
 
-        if let Some(search_id) = query.id {
-            diesel::delete(saves.filter(id.eq(search_id)))
-                .execute(&conn)
-                .expect(err_msg);
-        } else if let Some(name) = query.friendly_name {
-            diesel::delete(saves.filter(friendly_name.eq(name)))
-                .execute(&conn)
+        // An empty query would otherwise delete every save
+        if Self::is_save_filtered(&query) {
+            let ids: Vec<i32> = Self::filter_saves(&query)?
+                .select(id)
+                .load(&conn)
                 .expect(err_msg);
-        } else if let Some(path) = query.path {
-            let path_string = path.to_string_lossy().to_string();
-            let path_str = path
-                .to_str()
-                .ok_or(DatabaseError::IllegalPath(path_string))?;
-            diesel::delete(saves.filter(save_path.eq(path_str)))
+
+            diesel::delete(saves.filter(id.eq_any(ids)))
                 .execute(&conn)
                 .expect(err_msg);
         }
@@ -239,46 +310,141 @@ impl Database {
         let err_msg = "Unable to delete saves from database.";
         let conn = self.get_conn();
 
-        if let Some(search_user_id) = query.user_id {
-            diesel::delete(saves.filter(user_id.eq(search_user_id)))
+        if !Self::is_save_filtered(&query) {
+            return;
+        }
+
+        if let Ok(statement) = Self::filter_saves(&query) {
+            let ids: Vec<i32> = statement.select(id).load(&conn).expect(err_msg);
+
+            diesel::delete(saves.filter(id.eq_any(ids)))
                 .execute(&conn)
                 .expect(err_msg);
         }
     }
 
-    pub fn create_file(&self, file: NewFile) {
-        // TODO: Return result
-        use schema::files;
+    /// Builds a statement which selects every save that matches all of the fields set in `query`
+    fn filter_saves<'a>(
+        query: &SaveQuery<'a>,
+    ) -> Result<schema::saves::BoxedQuery<'a, Sqlite>, DatabaseError> {
+        use schema::saves::dsl::*;
+        use schema::{notes, tags};
 
-        if !self.does_file_exist(file.file_path) {
-            let conn = self.get_conn();
+        let mut statement = saves.into_boxed();
 
-            diesel::insert_into(files::table)
-                .values(&file)
-                .execute(&conn)
-                .expect("Failed to create file in database.");
+        if let Some(search_id) = query.id {
+            statement = statement.filter(id.eq(search_id));
+        }
+
+        if let Some(q_uuid) = query.uuid {
+            statement = statement.filter(uuid.eq(q_uuid));
+        }
+
+        if let Some(name) = query.friendly_name {
+            statement = statement.filter(friendly_name.eq(name));
+        }
+
+        if let Some(path) = query.path {
+            statement = statement.filter(save_path.eq(paths::encode(&path)));
+        }
+
+        if let Some(path) = query.path_prefix {
+            let (path, pattern) = Self::prefix_pattern(&paths::encode(&path));
+            statement = statement.filter(save_path.eq(path).or(glob(save_path, pattern)));
+        }
+
+        if let Some(pattern) = query.path_glob {
+            statement = statement.filter(glob(save_path, pattern));
+        }
+
+        if let Some(search_user_id) = query.user_id {
+            statement = statement.filter(user_id.eq(search_user_id));
+        }
+
+        if let Some(tag) = query.tag {
+            let tagged = tags::table.select(tags::save_id).filter(tags::name.eq(tag));
+            statement = statement.filter(id.eq_any(tagged));
+        }
+
+        if let Some(text) = query.text {
+            let pattern = Self::like_pattern(text);
+            let tagged = tags::table
+                .select(tags::save_id)
+                .filter(tags::name.like(pattern.clone()).escape('\\'));
+            let noted = notes::table
+                .select(notes::save_id)
+                .filter(notes::body.like(pattern.clone()).escape('\\'));
+
+            statement = statement.filter(
+                friendly_name
+                    .like(pattern.clone())
+                    .escape('\\')
+                    .or(save_path.like(pattern).escape('\\'))
+                    .or(id.eq_any(tagged))
+                    .or(id.eq_any(noted)),
+            );
+        }
+
+        if let Some(time) = query.created_since {
+            statement = statement.filter(created_at.ge(time));
+        }
+
+        if let Some(time) = query.created_before {
+            statement = statement.filter(created_at.lt(time));
         }
+
+        if let Some(time) = query.modified_since {
+            statement = statement.filter(modified_at.ge(time));
+        }
+
+        if let Some(time) = query.modified_before {
+            statement = statement.filter(modified_at.lt(time));
+        }
+
+        Ok(statement)
     }
 
-    pub fn get_file(&self, query: FileQuery) -> Option<File> {
-        use schema::files::dsl::*;
+    /// Whether `query` narrows down which saves it matches at all
+    fn is_save_filtered(query: &SaveQuery) -> bool {
+        let unfiltered = SaveQuery {
+            order_by: query.order_by,
+            limit: query.limit,
+            offset: query.offset,
+            ..SaveQuery::new()
+        };
+
+        *query != unfiltered
+    }
+
+    /// The same path may belong to more than one save, but only once to each of them.
+    /// Fails with `DatabaseError::AlreadyExists` otherwise
+    pub fn create_file(&self, file: NewFile) -> Result<(), DatabaseError> {
+        use schema::files;
 
-        let err_msg = "Unable to query database.";
         let conn = self.get_conn();
-        let mut list: Vec<File> = vec![];
 
-        if let Some(search_id) = query.id {
-            list = files.filter(id.eq(search_id)).load(&conn).expect(err_msg);
-        } else if let Some(path) = query.path {
-            let path_str = path.to_str()?;
-            list = files
-                .filter(file_path.eq(path_str))
-                .load(&conn)
-                .expect(err_msg);
-        } else if let Some(hash) = query.hash {
-            list = files.filter(file_hash.eq(hash)).load(&conn).expect(err_msg);
+        diesel::insert_into(files::table)
+            .values(&file)
+            .execute(&conn)
+            .map_err(|err| {
+                let path = paths::decode(file.file_path);
+                Self::unique_error(err, format!("The file {}", path.to_string_lossy()))
+            })?;
+
+        Ok(())
+    }
+
+    pub fn get_file(&self, query: FileQuery) -> Option<File> {
+        if !Self::is_file_filtered(&query) {
+            return None;
         }
 
+        let conn = self.get_conn();
+        let list: Vec<File> = Self::filter_files(&query)
+            .ok()?
+            .load(&conn)
+            .expect("Unable to query database.");
+
         match list.len() {
             0 => None,
             1 => Some(list.first()?.clone()),
@@ -287,19 +453,29 @@ impl Database {
     }
 
     pub fn get_files(&self, query: FileQuery) -> Option<Vec<File>> {
+        use crate::archive::query::{FileOrder, Order};
         use schema::files::dsl::*;
 
-        let err_msg = "Unable to query database.";
         let conn = self.get_conn();
-        let mut list: Vec<File> = vec![];
+        let mut statement = Self::filter_files(&query).ok()?;
+
+        statement = match query.order_by.unwrap_or((FileOrder::Id, Order::Ascending)) {
+            (FileOrder::Id, Order::Ascending) => statement.order(id.asc()),
+            (FileOrder::Id, Order::Descending) => statement.order(id.desc()),
+            (FileOrder::Path, Order::Ascending) => statement.order(file_path.asc()),
+            (FileOrder::Path, Order::Descending) => statement.order(file_path.desc()),
+            (FileOrder::CreatedAt, Order::Ascending) => statement.order(created_at.asc()),
+            (FileOrder::CreatedAt, Order::Descending) => statement.order(created_at.desc()),
+            (FileOrder::ModifiedAt, Order::Ascending) => statement.order(modified_at.asc()),
+            (FileOrder::ModifiedAt, Order::Descending) => statement.order(modified_at.desc()),
+        };
 
-        if let Some(search_save_id) = query.save_id {
-            list = files
-                .filter(save_id.eq(search_save_id))
-                .load(&conn)
-                .expect(err_msg);
+        if let Some((n, skip)) = Self::limit_offset(query.limit, query.offset) {
+            statement = statement.limit(n).offset(skip);
         }
 
+        let list: Vec<File> = statement.load(&conn).expect("Unable to query database.");
+
         if list.is_empty() {
             None
         } else {
@@ -320,6 +496,18 @@ impl Database {
         }
     }
 
+    /// Whether any File has been hashed and stored in the database
+    pub fn has_files(&self) -> bool {
+        use diesel::dsl::exists;
+        use schema::files::dsl::*;
+
+        let conn = self.get_conn();
+
+        diesel::select(exists(files.select(id)))
+            .get_result(&conn)
+            .expect("Unable to query database.")
+    }
+
     pub fn update_file(&self, edit: EditFile) {
         // TODO: Return result
         use schema::files::dsl::*;
@@ -340,20 +528,14 @@ impl Database {
         let err_msg = "Unable to delete file from database.";
         let conn = self.get_conn();
 
-        if let Some(search_id) = query.id {
-            diesel::delete(files.filter(id.eq(search_id)))
-                .execute(&conn)
-                .expect(err_msg);
-        } else if let Some(path) = query.path {
-            let path_string = path.to_string_lossy().to_string();
-            let path_str = path
-                .to_str()
-                .ok_or(DatabaseError::IllegalPath(path_string))?;
-            diesel::delete(files.filter(file_path.eq(path_str)))
-                .execute(&conn)
+        // An empty query would otherwise delete every file
+        if Self::is_file_filtered(&query) {
+            let ids: Vec<i32> = Self::filter_files(&query)?
+                .select(id)
+                .load(&conn)
                 .expect(err_msg);
-        } else if let Some(hash) = query.hash {
-            diesel::delete(files.filter(file_hash.eq(hash)))
+
+            diesel::delete(files.filter(id.eq_any(ids)))
                 .execute(&conn)
                 .expect(err_msg);
         }
@@ -368,52 +550,123 @@ impl Database {
         let err_msg = "Unable to delete files from database.";
         let conn = self.get_conn();
 
-        if let Some(search_save_id) = query.save_id {
-            diesel::delete(files.filter(save_id.eq(search_save_id)))
+        if !Self::is_file_filtered(&query) {
+            return;
+        }
+
+        if let Ok(statement) = Self::filter_files(&query) {
+            let ids: Vec<i32> = statement.select(id).load(&conn).expect(err_msg);
+
+            diesel::delete(files.filter(id.eq_any(ids)))
                 .execute(&conn)
                 .expect(err_msg);
         }
     }
 
-    pub fn create_user(&self, user: NewUser) {
-        // TODO: Return result
-        use schema::users;
+    /// Builds a statement which selects every file that matches all of the fields set in `query`
+    fn filter_files<'a>(
+        query: &FileQuery<'a>,
+    ) -> Result<schema::files::BoxedQuery<'a, Sqlite>, DatabaseError> {
+        use schema::files::dsl::*;
 
-        if !self.does_user_exist(user.username) {
-            let conn = self.get_conn();
+        let mut statement = files.into_boxed();
 
-            diesel::insert_into(users::table)
-                .values(&user)
-                .execute(&conn)
-                .expect("Failed to create file in database.");
+        if let Some(search_id) = query.id {
+            statement = statement.filter(id.eq(search_id));
         }
-    }
 
-    pub fn get_user(&self, query: UserQuery) -> Option<User> {
-        use schema::users::dsl::*;
+        if let Some(path) = query.path {
+            statement = statement.filter(file_path.eq(paths::encode(&path)));
+        }
 
-        let err_msg = "Unable to query database.";
-        let conn = self.get_conn();
-        let mut list: Vec<User> = vec![];
+        if let Some(path) = query.path_prefix {
+            let (path, pattern) = Self::prefix_pattern(&paths::encode(&path));
+            statement = statement.filter(file_path.eq(path).or(glob(file_path, pattern)));
+        }
 
-        if let Some(search_id) = query.id {
-            list = users.filter(id.eq(search_id)).load(&conn).expect(err_msg);
-        } else if let Some(uname) = query.username {
-            list = users.filter(username.eq(uname)).load(&conn).expect(err_msg)
+        if let Some(pattern) = query.path_glob {
+            statement = statement.filter(glob(file_path, pattern));
         }
 
-        match list.len() {
-            0 => None,
-            1 => Some(list.first()?.clone()),
-            _ => panic!("Expected 1 user to be found, but found multiple."),
+        if let Some(hash) = query.hash {
+            statement = statement.filter(file_hash.eq(hash));
+        }
+
+        if let Some(search_save_id) = query.save_id {
+            statement = statement.filter(save_id.eq(search_save_id));
+        }
+
+        if let Some(time) = query.created_since {
+            statement = statement.filter(created_at.ge(time));
+        }
+
+        if let Some(time) = query.created_before {
+            statement = statement.filter(created_at.lt(time));
+        }
+
+        if let Some(time) = query.modified_since {
+            statement = statement.filter(modified_at.ge(time));
         }
+
+        if let Some(time) = query.modified_before {
+            statement = statement.filter(modified_at.lt(time));
+        }
+
+        Ok(statement)
     }
 
-    pub fn get_all_users(&self) -> Option<Vec<User>> {
-        use schema::users::dsl::*;
+    /// Whether `query` narrows down which files it matches at all
+    fn is_file_filtered(query: &FileQuery) -> bool {
+        let unfiltered = FileQuery {
+            order_by: query.order_by,
+            limit: query.limit,
+            offset: query.offset,
+            ..FileQuery::new()
+        };
+
+        *query != unfiltered
+    }
+
+    /// Inserts every chunk of a file at once, since large files are made up of thousands of them
+    pub fn create_chunks(&self, new_chunks: &[NewChunk]) {
+        // TODO: Return result
+        use schema::chunks;
 
         let conn = self.get_conn();
-        let list: Vec<User> = users.load(&conn).expect("Unable to query database.");
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            for chunk in new_chunks {
+                diesel::insert_into(chunks::table)
+                    .values(chunk)
+                    .execute(&conn)?;
+            }
+
+            Ok(())
+        })
+        .expect("Failed to create chunks in database.");
+    }
+
+    pub fn get_chunks(&self, query: ChunkQuery) -> Option<Vec<Chunk>> {
+        use schema::chunks::dsl::*;
+
+        let err_msg = "Unable to query database.";
+        let conn = self.get_conn();
+        let mut list: Vec<Chunk> = vec![];
+
+        if let Some(search_id) = query.id {
+            list = chunks.filter(id.eq(search_id)).load(&conn).expect(err_msg);
+        } else if let Some(search_file_id) = query.file_id {
+            list = chunks
+                .filter(file_id.eq(search_file_id))
+                .order(position)
+                .load(&conn)
+                .expect(err_msg);
+        } else if let Some(search_hash) = query.hash {
+            list = chunks
+                .filter(chunk_hash.eq(search_hash))
+                .load(&conn)
+                .expect(err_msg);
+        }
 
         if list.is_empty() {
             None
@@ -422,63 +675,1727 @@ impl Database {
         }
     }
 
-    pub fn update_user(&self, edit: EditUser) {
+    pub fn delete_chunks(&self, query: ChunkQuery) {
         // TODO: Return result
-        use schema::users::dsl::*;
-
-        let conn = self.get_conn();
-        let user_id = edit.id;
-
-        diesel::update(users.filter(id.eq(user_id)))
-            .set(&edit)
-            .execute(&conn)
-            .expect("Failed to update user in database.");
-    }
+        use schema::chunks::dsl::*;
 
-    pub fn delete_user(&self, query: UserQuery) {
-        use schema::users::dsl::*;
-
-        let err_msg = "Unable to delete user from database.";
+        let err_msg = "Unable to delete chunks from database.";
         let conn = self.get_conn();
 
         if let Some(search_id) = query.id {
-            diesel::delete(users.filter(id.eq(search_id)))
+            diesel::delete(chunks.filter(id.eq(search_id)))
                 .execute(&conn)
                 .expect(err_msg);
-        } else if let Some(uname) = query.username {
-            diesel::delete(users.filter(username.eq(uname)))
+        } else if let Some(search_file_id) = query.file_id {
+            diesel::delete(chunks.filter(file_id.eq(search_file_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        } else if let Some(search_hash) = query.hash {
+            diesel::delete(chunks.filter(chunk_hash.eq(search_hash)))
                 .execute(&conn)
                 .expect(err_msg);
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*; // imports archive queries, model structs diesel prelude etc.
-    use chrono::Utc;
-    use rand;
-    use std::path::PathBuf;
-    use tempfile::TempDir;
+    pub fn create_executable(&self, executable: NewExecutable) {
+        // TODO: Return result
+        use schema::executables;
 
-    #[test]
-    fn write_to_and_migrate_database() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        let conn = self.get_conn();
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        diesel::insert_into(executables::table)
+            .values(&executable)
+            .execute(&conn)
+            .expect("Failed to create executable in database.");
+    }
 
-        let result = db_path.exists();
+    pub fn get_executables(&self, query: ExecutableQuery) -> Option<Vec<Executable>> {
+        use schema::executables::dsl::*;
+
+        let err_msg = "Unable to query database.";
+        let conn = self.get_conn();
+        let mut list: Vec<Executable> = vec![];
+
+        if let Some(search_id) = query.id {
+            list = executables
+                .filter(id.eq(search_id))
+                .load(&conn)
+                .expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            list = executables
+                .filter(save_id.eq(search_save_id))
+                .load(&conn)
+                .expect(err_msg);
+        } else if let Some(exe_name) = query.name {
+            list = executables
+                .filter(name.eq(exe_name))
+                .load(&conn)
+                .expect(err_msg);
+        }
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    pub fn delete_executables(&self, query: ExecutableQuery) {
+        // TODO: Return result
+        use schema::executables::dsl::*;
+
+        let err_msg = "Unable to delete executables from database.";
+        let conn = self.get_conn();
+
+        if let Some(search_id) = query.id {
+            diesel::delete(executables.filter(id.eq(search_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            diesel::delete(executables.filter(save_id.eq(search_save_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        }
+    }
+
+    pub fn create_tag(&self, tag: NewTag) {
+        // TODO: Return result
+        use schema::tags::dsl::*;
+
+        let conn = self.get_conn();
+        let existing: i64 = tags
+            .filter(save_id.eq(tag.save_id))
+            .filter(name.eq(tag.name))
+            .count()
+            .get_result(&conn)
+            .expect("Unable to query database.");
+
+        // A save is either tagged with something or it isn't
+        if existing == 0 {
+            diesel::insert_into(tags)
+                .values(&tag)
+                .execute(&conn)
+                .expect("Failed to create tag in database.");
+        }
+    }
+
+    pub fn get_tags(&self, query: TagQuery) -> Option<Vec<Tag>> {
+        use schema::tags::dsl::*;
+
+        let err_msg = "Unable to query database.";
+        let conn = self.get_conn();
+        let mut list: Vec<Tag> = vec![];
+
+        if let Some(search_id) = query.id {
+            list = tags.filter(id.eq(search_id)).load(&conn).expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            list = tags
+                .filter(save_id.eq(search_save_id))
+                .order(name)
+                .load(&conn)
+                .expect(err_msg);
+        } else if let Some(tag_name) = query.name {
+            list = tags.filter(name.eq(tag_name)).load(&conn).expect(err_msg);
+        }
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    pub fn get_all_tags(&self) -> Option<Vec<Tag>> {
+        use schema::tags::dsl::*;
+
+        let conn = self.get_conn();
+        let list: Vec<Tag> = tags
+            .order(name)
+            .load(&conn)
+            .expect("Unable to query database.");
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    pub fn delete_tags(&self, query: TagQuery) {
+        // TODO: Return result
+        use schema::tags::dsl::*;
+
+        let err_msg = "Unable to delete tags from database.";
+        let conn = self.get_conn();
+
+        if let Some(search_id) = query.id {
+            diesel::delete(tags.filter(id.eq(search_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            match query.name {
+                Some(tag_name) => diesel::delete(
+                    tags.filter(save_id.eq(search_save_id))
+                        .filter(name.eq(tag_name)),
+                )
+                .execute(&conn)
+                .expect(err_msg),
+                None => diesel::delete(tags.filter(save_id.eq(search_save_id)))
+                    .execute(&conn)
+                    .expect(err_msg),
+            };
+        }
+    }
+
+    pub fn create_note(&self, note: NewNote) {
+        // TODO: Return result
+        use schema::notes;
+
+        let conn = self.get_conn();
+
+        diesel::insert_into(notes::table)
+            .values(&note)
+            .execute(&conn)
+            .expect("Failed to create note in database.");
+    }
+
+    pub fn get_notes(&self, query: NoteQuery) -> Option<Vec<Note>> {
+        use schema::notes::dsl::*;
+
+        let err_msg = "Unable to query database.";
+        let conn = self.get_conn();
+        let mut list: Vec<Note> = vec![];
+
+        if let Some(search_id) = query.id {
+            list = notes.filter(id.eq(search_id)).load(&conn).expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            list = notes
+                .filter(save_id.eq(search_save_id))
+                .order(id)
+                .load(&conn)
+                .expect(err_msg);
+        }
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    pub fn delete_notes(&self, query: NoteQuery) {
+        // TODO: Return result
+        use schema::notes::dsl::*;
+
+        let err_msg = "Unable to delete notes from database.";
+        let conn = self.get_conn();
+
+        if let Some(search_id) = query.id {
+            diesel::delete(notes.filter(id.eq(search_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        } else if let Some(search_save_id) = query.save_id {
+            diesel::delete(notes.filter(save_id.eq(search_save_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        }
+    }
+
+    /// Turns `text` into a LIKE pattern which matches anything containing `text`
+    fn like_pattern(text: &str) -> String {
+        format!("%{}%", Self::escape_like(text))
+    }
+
+    /// Returns `path` without trailing separators, along with a GLOB pattern
+    /// which matches everything inside of `path`.
+    ///
+    /// GLOB is used rather than LIKE, since LIKE ignores the case of ASCII letters
+    fn prefix_pattern(path: &str) -> (String, String) {
+        let trimmed = path.trim_end_matches(['/', '\\']);
+        let path = if trimmed.is_empty() { path } else { trimmed };
+        let pattern = format!("{}{}*", Self::escape_glob(trimmed), MAIN_SEPARATOR_STR);
+
+        (path.to_string(), pattern)
+    }
+
+    /// GLOB has no escape character, but a metacharacter inside of brackets matches itself
+    fn escape_glob(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+
+        for c in text.chars() {
+            match c {
+                '*' | '?' | '[' => {
+                    escaped.push('[');
+                    escaped.push(c);
+                    escaped.push(']');
+                }
+                _ => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
+    fn escape_like(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+
+        for c in text.chars() {
+            if matches!(c, '%' | '_' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+
+        escaped
+    }
+
+    /// SQLite needs a LIMIT whenever there is an OFFSET, where -1 means that there is no limit
+    fn limit_offset(limit: Option<i64>, offset: Option<i64>) -> Option<(i64, i64)> {
+        match (limit, offset) {
+            (None, None) => None,
+            (limit, offset) => Some((limit.unwrap_or(-1), offset.unwrap_or(0))),
+        }
+    }
+
+    /// Creates the setting, or replaces it if a setting with the same name already exists
+    pub fn set_setting(&self, setting: NewSetting) {
+        use schema::settings;
+
+        let conn = self.get_conn();
+
+        diesel::replace_into(settings::table)
+            .values(&setting)
+            .execute(&conn)
+            .expect("Failed to store setting in database.");
+    }
+
+    pub fn get_setting(&self, setting_name: &str) -> Option<Setting> {
+        use schema::settings::dsl::*;
+
+        let conn = self.get_conn();
+
+        settings
+            .filter(name.eq(setting_name))
+            .first(&conn)
+            .optional()
+            .expect("Unable to query database.")
+    }
+
+    pub fn create_update(&self, update: NewUpdate) {
+        use schema::updates;
+
+        let conn = self.get_conn();
+
+        diesel::insert_into(updates::table)
+            .values(&update)
+            .execute(&conn)
+            .expect("Failed to record update in database.");
+    }
+
+    /// Every Update of the Save with `search_save_id`, oldest first
+    pub fn get_updates(&self, search_save_id: i32) -> Option<Vec<Update>> {
+        use schema::updates::dsl::*;
+
+        let conn = self.get_conn();
+        let list: Vec<Update> = updates
+            .filter(save_id.eq(search_save_id))
+            .order(created_at)
+            .load(&conn)
+            .expect("Unable to query database.");
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    /// Fails with `DatabaseError::AlreadyExists` if there already is a user with the same name
+    pub fn create_user(&self, user: NewUser) -> Result<(), DatabaseError> {
+        use schema::users;
+
+        let conn = self.get_conn();
+
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(&conn)
+            .map_err(|err| Self::unique_error(err, format!("The user {}", user.username)))?;
+
+        Ok(())
+    }
+
+    pub fn get_user(&self, query: UserQuery) -> Option<User> {
+        use schema::users::dsl::*;
+
+        let err_msg = "Unable to query database.";
+        let conn = self.get_conn();
+        let mut list: Vec<User> = vec![];
+
+        if let Some(search_id) = query.id {
+            list = users.filter(id.eq(search_id)).load(&conn).expect(err_msg);
+        } else if let Some(uname) = query.username {
+            list = users.filter(username.eq(uname)).load(&conn).expect(err_msg)
+        }
+
+        match list.len() {
+            0 => None,
+            1 => Some(list.first()?.clone()),
+            _ => panic!("Expected 1 user to be found, but found multiple."),
+        }
+    }
+
+    pub fn get_all_users(&self) -> Option<Vec<User>> {
+        use schema::users::dsl::*;
+
+        let conn = self.get_conn();
+        let list: Vec<User> = users.load(&conn).expect("Unable to query database.");
+
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    pub fn update_user(&self, edit: EditUser) {
+        // TODO: Return result
+        use schema::users::dsl::*;
+
+        let conn = self.get_conn();
+        let user_id = edit.id;
+
+        diesel::update(users.filter(id.eq(user_id)))
+            .set(&edit)
+            .execute(&conn)
+            .expect("Failed to update user in database.");
+    }
+
+    pub fn delete_user(&self, query: UserQuery) {
+        use schema::users::dsl::*;
+
+        let err_msg = "Unable to delete user from database.";
+        let conn = self.get_conn();
+
+        if let Some(search_id) = query.id {
+            diesel::delete(users.filter(id.eq(search_id)))
+                .execute(&conn)
+                .expect(err_msg);
+        } else if let Some(uname) = query.username {
+            diesel::delete(users.filter(username.eq(uname)))
+                .execute(&conn)
+                .expect(err_msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; // imports archive queries, model structs diesel prelude etc.
+    use chrono::Utc;
+    use rand;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// The result of `PRAGMA busy_timeout`
+    struct BusyTimeout {
+        timeout: i64,
+    }
+
+    // Written by hand, since the derive of diesel 1.4 puts the impl inside of a function
+    impl diesel::query_source::QueryableByName<Sqlite> for BusyTimeout {
+        fn build<R: diesel::row::NamedRow<Sqlite>>(row: &R) -> diesel::deserialize::Result<Self> {
+            let timeout = row.get::<diesel::sql_types::BigInt, i64>("timeout")?;
+
+            Ok(BusyTimeout { timeout })
+        }
+    }
+
+    #[test]
+    fn write_to_and_migrate_database() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let result = db_path.exists();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(result);
+    }
+
+    #[test]
+    fn connections_wait_while_busy() {
+        let test_dir = TempDir::new().unwrap();
+        let db_path = test_dir.path().join("test.db");
+        let db = Database::with_busy_timeout(&db_path, Duration::from_millis(1500)).unwrap();
+
+        let conn = db.get_conn();
+        let result: BusyTimeout = diesel::sql_query("PRAGMA busy_timeout")
+            .get_result(&conn)
+            .unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(result.timeout, 1500);
+    }
+
+    #[test]
+    fn create_new_save() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let expected = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        db.create_save(expected).unwrap();
+
+        let path = expected.save_path;
+        let list: Vec<Save> = {
+            use crate::schema::saves::dsl::*;
+            saves.filter(save_path.eq(path)).load(&conn).unwrap()
+        };
+        let actual = list.first().unwrap().clone();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(list.len() == 1);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn create_save_rejects_duplicates() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let expected = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let same_path = NewSave {
+            friendly_name: "same_path",
+            uuid: "{other_uuid}",
+            ..expected
+        };
+
+        let same_uuid = NewSave {
+            friendly_name: "same_uuid",
+            save_path: "/home/user/Documents/other_game",
+            ..expected
+        };
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        db.create_save(expected).unwrap();
+        let path_result = db.create_save(same_path);
+        let uuid_result = db.create_save(same_uuid);
+
+        let list = db.get_all_saves().unwrap();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(matches!(path_result, Err(DatabaseError::AlreadyExists(_))));
+        assert!(matches!(uuid_result, Err(DatabaseError::AlreadyExists(_))));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0], expected);
+    }
+
+    #[test]
+    fn create_file_same_path_in_two_saves() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let save2 = NewSave {
+            friendly_name: "test_game_profile",
+            save_path: "/home/user/Documents/test_game/profile",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/profile",
+            uuid: "{other_uuid}",
+            ..save1
+        };
+
+        let file1 = NewFile {
+            file_path: "/home/user/Documents/test_game/profile/00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        let file2 = NewFile {
+            save_id: 2,
+            ..file1
+        };
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(save1).unwrap();
+        db.create_save(save2).unwrap();
+
+        db.create_file(file1).unwrap();
+        db.create_file(file2).unwrap();
+        let duplicate = db.create_file(file1); // Already part of the first save
+
+        let list = db.get_all_files().unwrap();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(matches!(duplicate, Err(DatabaseError::AlreadyExists(_))));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0], file1);
+        assert_eq!(list[1], file2);
+    }
+
+    #[test]
+    fn create_user_rejects_duplicates() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let user = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        db.create_user(user).unwrap();
+        let duplicate = db.create_user(user);
+
+        let list = db.get_all_users().unwrap();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(matches!(duplicate, Err(DatabaseError::AlreadyExists(_))));
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn delete_save_cascades() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+
+        let save = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let file = NewFile {
+            file_path: "/home/user/Documents/test_game/00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(save).unwrap();
+        db.create_file(file).unwrap();
+        db.create_chunks(&[NewChunk {
+            file_id: 1,
+            position: 0,
+            chunk_hash: &hash,
+            chunk_size: 1024,
+            created_at: time,
+            modified_at: time,
+        }]);
+        db.create_executable(NewExecutable {
+            name: "game.exe",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        });
+        db.create_tag(NewTag {
+            name: "speedrun",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        });
+        db.create_note(NewNote {
+            body: "Before the final boss",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        });
+        db.create_update(NewUpdate {
+            save_id: 1,
+            files_changed: 1,
+            created_at: time,
+        });
+
+        let updated = db.get_updates(1).map(|list| list.len());
+        db.delete_save(SaveQuery::new().with_id(1)).unwrap();
+
+        let files = db.get_all_files();
+        let chunks = db.get_chunks(ChunkQuery::new().with_file_id(1));
+        let executables = db.get_executables(ExecutableQuery::new().with_save_id(1));
+        let tags = db.get_all_tags();
+        let notes = db.get_notes(NoteQuery::new().with_save_id(1));
+        let updates = db.get_updates(1);
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(updated, Some(1));
+        assert!(files.is_none());
+        assert!(chunks.is_none());
+        assert!(executables.is_none());
+        assert!(tags.is_none());
+        assert!(notes.is_none());
+        assert!(updates.is_none());
+    }
+
+    #[test]
+    fn get_save_success() {
+        // FIXME: With get_save_success, get_file_success and get_user_success
+        // we only test one out of many different queries we could come up with
+        // We might want to consider writing tests for all of those conditions,
+        // no matter how tedious it may be
+
+        use crate::schema::saves;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let expected = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&expected)
+            .execute(&conn)
+            .unwrap();
+
+        let query = SaveQuery::new().with_friendly_name("test_game");
+        let actual = db.get_save(query).unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn get_save_uuid_success() {
+        use crate::schema::saves;
+        use uuid::Uuid;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let mut uuid_buf = Uuid::encode_buffer();
+
+        let uuid = Uuid::new_v4().to_hyphenated().encode_lower(&mut uuid_buf);
+
+        let expected = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid,
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&expected)
+            .execute(&conn)
+            .unwrap();
+
+        let query = SaveQuery::new().with_uuid(uuid);
+        let actual = db.get_save(query).unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn get_save_failure() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let query = SaveQuery::new().with_friendly_name("not_in_db");
+        let option = db.get_save(query);
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(option.is_none());
+    }
+
+    #[test]
+    fn get_saves_success() {
+        use crate::schema::saves;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let expected1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let time = Utc::now().naive_utc();
+
+        let expected2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/other_game",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        // Batch Inserts are not supported in diesel (when it comes to SQlite)
+        diesel::insert_into(saves::table)
+            .values(&expected1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&expected2)
+            .execute(&conn)
+            .unwrap();
+
+        let query = SaveQuery::new().with_user_id(1);
+        let saves = db.get_saves(query).unwrap();
+        let actual1: Save = saves.get(0).unwrap().clone();
+        let actual2: Save = saves.get(1).unwrap().clone();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(saves.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
+    }
+
+    #[test]
+    fn get_saves_combined_success() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let later = time + chrono::Duration::days(1);
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: later,
+        };
+
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/test_game_2",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/test_game_2",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: later,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
+            .execute(&conn)
+            .unwrap();
+
+        // A prefix has to match whole path components
+        let prefix = Path::new("/home/user/Documents/test_game/");
+        let query = SaveQuery::new().with_user_id(1).with_path_prefix(&prefix);
+        let by_prefix = db.get_saves(query).unwrap();
+
+        let query = SaveQuery::new().with_path_glob("*/test_game_?");
+        let by_glob = db.get_saves(query).unwrap();
+
+        let query = SaveQuery::new()
+            .with_created_since(later)
+            .with_modified_before(later);
+        let by_date = db.get_saves(query).unwrap();
+
+        // Every field has to match, not just the first one which is set
+        let query = SaveQuery::new().with_id(1).with_friendly_name("other_game");
+        let none = db.get_save(query);
+
+        let query = SaveQuery::new().with_path_prefix(&"/home/user/Doc");
+        let partial = db.get_saves(query);
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(by_prefix, vec![save1]);
+        assert_eq!(by_glob, vec![save2]);
+        assert_eq!(by_date, vec![save2]);
+        assert!(none.is_none());
+        assert!(partial.is_none());
+    }
+
+    #[test]
+    fn get_saves_ordered_success() {
+        use crate::archive::query::{Order, SaveOrder};
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let later = time + chrono::Duration::days(1);
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: later,
+        };
+
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/test_game_2",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/test_game_2",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: later,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
+            .execute(&conn)
+            .unwrap();
+
+        let query = SaveQuery::new().with_order(SaveOrder::FriendlyName, Order::Ascending);
+        let by_name = db.get_saves(query).unwrap();
+
+        let query = SaveQuery::new()
+            .with_order(SaveOrder::ModifiedAt, Order::Descending)
+            .with_limit(1);
+        let first_page = db.get_saves(query).unwrap();
+
+        let query = SaveQuery::new()
+            .with_order(SaveOrder::ModifiedAt, Order::Descending)
+            .with_offset(1);
+        let second_page = db.get_saves(query).unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(by_name, vec![save2, save1]);
+        assert_eq!(first_page, vec![save1]);
+        assert_eq!(second_page, vec![save2]);
+    }
+
+    #[test]
+    fn delete_saves_by_prefix_success() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let later = time + chrono::Duration::days(1);
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: later,
+        };
+
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/test_game_2",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/test_game_2",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: later,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
+            .execute(&conn)
+            .unwrap();
+
+        let prefix = Path::new("/home/user/Documents/test_game_2");
+        db.delete_saves(SaveQuery::new().with_path_prefix(&prefix));
+
+        // An empty query must not delete anything
+        db.delete_saves(SaveQuery::new());
+        let remaining = db.get_saves(SaveQuery::new().with_user_id(1)).unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(remaining, vec![save1]);
+    }
+
+    #[test]
+    fn path_prefix_is_case_sensitive() {
+        let test_dir = TempDir::new().unwrap();
+        let db_path = test_dir.path().join("test.db");
+        let db = Database::new(&db_path).unwrap();
+        let time = Utc::now().naive_utc();
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        for (uuid, path) in &[
+            ("upper", "/x/Saves"),
+            ("lower", "/x/saves"),
+            ("glob", "/x/[s]*"),
+        ] {
+            db.create_save(NewSave {
+                friendly_name: uuid,
+                save_path: path,
+                backup_path: "/backups",
+                uuid,
+                user_id: 1,
+                created_at: time,
+                modified_at: time,
+            })
+            .unwrap();
+        }
+
+        for (save_id, path) in &[(1, "/x/Saves/slot1.sav"), (2, "/x/saves/slot1.sav")] {
+            db.create_file(NewFile {
+                file_path: path,
+                file_hash: &[0; 8],
+                save_id: *save_id,
+                created_at: time,
+                modified_at: time,
+                file_type: FileType::File.into(),
+                link_target: None,
+                permissions: None,
+                file_mtime: None,
+                file_size: None,
+            })
+            .unwrap();
+        }
+
+        let name = |save: &Save| save.friendly_name.clone();
+        let prefix = Path::new("/x/Saves");
+        let saves = db
+            .get_saves(SaveQuery::new().with_path_prefix(&prefix))
+            .unwrap();
+        let files = db
+            .get_files(FileQuery::new().with_path_prefix(&prefix))
+            .unwrap();
+
+        // Glob metacharacters in the prefix only match themselves
+        let metachars = Path::new("/x/[s]*");
+        let by_metachars = db
+            .get_saves(SaveQuery::new().with_path_prefix(&metachars))
+            .unwrap();
+
+        db.delete_saves(SaveQuery::new().with_path_prefix(&Path::new("/x/saves")));
+        let remaining = db.get_saves(SaveQuery::new()).unwrap();
+
+        drop(db);
+        test_dir.close().unwrap();
+
+        assert_eq!(saves.iter().map(name).collect::<Vec<_>>(), vec!["upper"]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_path, "/x/Saves/slot1.sav");
+        assert_eq!(
+            by_metachars.iter().map(name).collect::<Vec<_>>(),
+            vec!["glob"]
+        );
+        assert_eq!(
+            remaining.iter().map(name).collect::<Vec<_>>(),
+            vec!["upper", "glob"]
+        );
+    }
+
+    #[test]
+    fn get_saves_failure() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let query = SaveQuery::new().with_user_id(1);
+        let saves = db.get_saves(query);
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(saves.is_none());
+    }
+
+    #[test]
+    fn get_all_saves_success() {
+        use crate::schema::saves;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let expected1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let time = Utc::now().naive_utc();
+
+        let expected2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/other_game",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&expected1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&expected2)
+            .execute(&conn)
+            .unwrap();
+
+        let save_list = db.get_all_saves().unwrap();
+        let actual1 = save_list.get(0).unwrap().clone();
+        let actual2 = save_list.get(1).unwrap().clone();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(save_list.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
+    }
+
+    #[test]
+    fn get_all_saves_failure() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let save_list = db.get_all_saves();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(save_list.is_none());
+    }
+
+    #[test]
+    fn update_save_success() {
+        use crate::schema::saves;
+        use crate::schema::saves::dsl::*;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+
+        let new_save = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(saves::table)
+            .values(&new_save)
+            .execute(&conn)
+            .unwrap();
+
+        let save_list: Vec<Save> = saves
+            .filter(save_path.eq(&new_save.save_path))
+            .load(&conn)
+            .unwrap();
+
+        let full_save = save_list.first().unwrap().clone();
+
+        let changed_friendly_name = "sample_test_game";
+        let time = Utc::now().naive_utc();
+
+        let edit = EditSave {
+            id: full_save.id,
+            friendly_name: Some(changed_friendly_name),
+            save_path: None,
+            user_id: None,
+            modified_at: time,
+        };
+
+        db.update_save(edit);
+
+        let save_list: Vec<Save> = saves.filter(id.eq(full_save.id)).load(&conn).unwrap();
+        let changed_save = save_list.first().unwrap().clone();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(changed_friendly_name, changed_save.friendly_name);
+        assert_eq!(time, changed_save.modified_at);
+        assert_ne!(full_save, changed_save);
+    }
+
+    #[test]
+    fn update_save_moves_files() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+        let time = Utc::now().naive_utc();
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "Game",
+            save_path: "/home/user/Gäme",
+            backup_path: "/data/uuid/Gäme",
+            uuid: "uuid",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        for path in &["/home/user/Gäme/slot1.sav", "/home/user/Gäme/Sub/slot2.sav"] {
+            db.create_file(NewFile {
+                file_path: path,
+                file_hash: &[0; 8],
+                save_id: 1,
+                created_at: time,
+                modified_at: time,
+                file_type: 0,
+                link_target: None,
+                permissions: None,
+                file_mtime: None,
+                file_size: None,
+            })
+            .unwrap();
+        }
+
+        let later = Utc::now().naive_utc();
+        db.update_save(EditSave {
+            id: 1,
+            friendly_name: None,
+            save_path: Some("/mnt/games/Game"),
+            user_id: None,
+            modified_at: later,
+        });
+
+        let save = db.get_save(SaveQuery::new().with_id(1)).unwrap();
+        let files = db.get_files(FileQuery::new().with_save_id(1)).unwrap();
+        let mut file_paths: Vec<&str> = files.iter().map(|file| file.file_path.as_str()).collect();
+        file_paths.sort_unstable();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(save.save_path, "/mnt/games/Game");
+        assert_eq!(save.friendly_name, "Game");
+        assert_eq!(save.modified_at, later);
+        assert_eq!(
+            file_paths,
+            vec!["/mnt/games/Game/Sub/slot2.sav", "/mnt/games/Game/slot1.sav"]
+        );
+        assert!(files.iter().all(|file| file.modified_at == later));
+    }
+
+    #[test]
+    #[ignore]
+    fn update_save_failure() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_save_success() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_save_failure() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_saves_success() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_saves_failure() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn create_new_file() {
+        unimplemented!()
+    }
+
+    #[test]
+    fn get_file_success() {
+        use crate::schema::files;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+
+        let expected = NewFile {
+            file_path: "/home/user/Documents/test_game/00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&expected)
+            .execute(&conn)
+            .unwrap();
+
+        let path = Path::new("/home/user/Documents/test_game/00.sav");
+        let query = FileQuery::new().with_path(&path);
+
+        let actual = db.get_file(query).unwrap();
+
+        drop(conn);
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths() {
+        use crate::paths;
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        // Even the database itself may live somewhere which isn't UTF-8
+        let db_dir = tmp_dir.join(OsStr::from_bytes(b"Sauvegard\xe9"));
+        let db = Database::new(&db_dir.join("test.db")).unwrap();
+
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+        let save_path = Path::new(OsStr::from_bytes(b"/home/user/.wine/Sauvegard\xe9"));
+        let file_path = save_path.join(OsStr::from_bytes(b"\xff.sav"));
+        let save_text = paths::encode(&save_path);
+        let file_text = paths::encode(&file_path);
+
+        db.create_user(NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "test_game",
+            save_path: &save_text,
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_file(NewFile {
+            file_path: &file_text,
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: Some(&file_text),
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        })
+        .unwrap();
+
+        let save = db.get_save(SaveQuery::new().with_path(&save_path)).unwrap();
+        let file = db.get_file(FileQuery::new().with_path(&file_path)).unwrap();
+        let inside = db
+            .get_files(FileQuery::new().with_path_prefix(&save_path))
+            .unwrap();
+
+        drop(db);
+
+        let exists = db_dir.join("test.db").exists();
+        test_dir.close().unwrap();
+
+        assert!(exists);
+        assert!(save == *save_path);
+        assert!(file == *file_path);
+        assert_eq!(paths::decode(file.link_target.as_ref().unwrap()), file_path);
+        assert_eq!(inside, vec![file]);
+    }
+
+    #[test]
+    fn get_file_failure() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+
+        let hash: [u8; 32] = rand::random();
+        let query = FileQuery::new().with_hash(&hash);
+        let option = db.get_file(query);
 
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(result);
+        assert!(option.is_none());
     }
 
     #[test]
-    fn does_save_exist_true() {
+    fn get_file_with_save_id_success() {
+        use crate::schema::files;
+
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -486,14 +2403,26 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
+        let later = time + chrono::Duration::days(1);
+        let hash: [u8; 32] = rand::random();
 
-        let save = NewSave {
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
             uuid: "{uuid}",
             user_id: 1,
             created_at: time,
+            modified_at: later,
+        };
+
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/test_game_2",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/test_game_2",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: later,
             modified_at: time,
         };
 
@@ -510,35 +2439,71 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        db.create_save(save);
-        let result = db.does_save_exist(save.save_path);
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
 
-        drop(conn);
-        drop(db);
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
+            .execute(&conn)
+            .unwrap();
 
-        test_dir.close().unwrap();
-        assert!(result);
-    }
+        // Both saves track a file at the same (relative) path
+        let file1 = NewFile {
+            file_path: "00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
 
-    #[test]
-    fn does_save_exist_false() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        let file2 = NewFile {
+            save_id: 2,
+            modified_at: later,
+            ..file1
+        };
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        diesel::insert_into(files::table)
+            .values(&file1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&file2)
+            .execute(&conn)
+            .unwrap();
+
+        let path = Path::new("00.sav");
+        let actual = db.get_file(FileQuery::new().with_path(&path).with_save_id(2));
 
-        let path = "/home/user/Documents/test_game";
-        let result = db.does_file_exist(path);
+        let query = FileQuery::new().with_hash(&hash).with_modified_since(later);
+        let recent = db.get_files(query).unwrap();
 
+        db.delete_file(FileQuery::new().with_path(&path).with_save_id(1))
+            .unwrap();
+        let remaining = db
+            .get_files(FileQuery::new().with_path_glob("*.sav"))
+            .unwrap();
+
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(!result);
+        assert_eq!(actual.unwrap(), file2);
+        assert_eq!(recent, vec![file2]);
+        assert_eq!(remaining, vec![file2]);
     }
 
     #[test]
-    fn does_file_exist_true() {
+    fn get_files_success() {
+        use crate::schema::files;
+
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -547,19 +2512,32 @@ mod tests {
 
         let time = Utc::now().naive_utc();
         let hash: [u8; 32] = rand::random();
-
-        let file = NewFile {
+        let expected1 = NewFile {
             file_path: "/home/user/Documents/test_game/00.sav",
             file_hash: &hash,
             save_id: 1,
             created_at: time,
             modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
         };
 
-        let user1 = NewUser {
-            username: "DarkFlameMaster",
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+        let expected2 = NewFile {
+            file_path: "/home/user/Documents/test_game/01.sav",
+            file_hash: &hash,
+            save_id: 1,
             created_at: time,
             modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
         };
 
         let save1 = NewSave {
@@ -572,6 +2550,12 @@ mod tests {
             modified_at: time,
         };
 
+        let user1 = NewUser {
+            username: "DarkFlameMaster",
+            created_at: time,
+            modified_at: time,
+        };
+
         let conn = db.get_conn();
 
         diesel::insert_into(schema::users::table)
@@ -584,35 +2568,52 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        db.create_file(file);
-        let result = db.does_file_exist(file.file_path);
+        // Batch Inserts are not supported in diesel (when it comes to SQlite)
+        diesel::insert_into(files::table)
+            .values(&expected1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&expected2)
+            .execute(&conn)
+            .unwrap();
+
+        let query = FileQuery::new().with_save_id(1);
+        let files = db.get_files(query).unwrap();
+        let actual1 = files.get(0).unwrap().clone();
+        let actual2 = files.get(1).unwrap().clone();
 
         drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(result);
+        assert!(files.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
     }
 
     #[test]
-    fn does_file_exist_false() {
+    fn get_files_failure() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
         let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
         let db = Database::new(&db_path).unwrap();
 
-        let path = "/home/user/Documents/test_game/00.sav";
-        let result = db.does_file_exist(path);
+        let query = FileQuery::new().with_save_id(1);
+        let files = db.get_files(query);
 
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(!result);
+        assert!(files.is_none());
     }
 
     #[test]
-    fn does_user_exist_true() {
+    fn get_all_files_success() {
+        use crate::schema::files;
+
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -620,41 +2621,109 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
 
-        let user = NewUser {
+        let expected1 = NewFile {
+            file_path: "/home/user/Documents/test_game/00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
+
+        let expected2 = NewFile {
+            file_path: "/home/user/Documents/test_game/01.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        let save1 = NewSave {
+            friendly_name: "test_game",
+            save_path: "/home/user/Documents/test_game",
+            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
+            uuid: "{uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let user1 = NewUser {
             username: "DarkFlameMaster",
             created_at: time,
             modified_at: time,
         };
 
-        db.create_user(user);
-        let result = db.does_user_exist(user.username);
+        let conn = db.get_conn();
+
+        diesel::insert_into(schema::users::table)
+            .values(&user1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&expected1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&expected2)
+            .execute(&conn)
+            .unwrap();
+
+        let file_list = db.get_all_files().unwrap();
+        let actual2 = file_list.get(1).unwrap().clone();
+        let actual1 = file_list.get(0).unwrap().clone();
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(result);
+        assert!(file_list.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
     }
 
     #[test]
-    fn does_user_exist_false() {
+    fn get_all_files_failure() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
         let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
         let db = Database::new(&db_path).unwrap();
 
-        let username = "DarkFlameMaster";
-        let result = db.does_user_exist(username);
+        let file_list = db.get_all_files();
 
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(!result);
+        assert!(file_list.is_none());
     }
 
     #[test]
-    fn create_new_save() {
+    fn update_file_success() {
+        use crate::schema::files;
+        use crate::schema::files::dsl::*;
+
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -662,8 +2731,22 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
+        let hash: [u8; 32] = rand::random();
 
-        let expected = NewSave {
+        let new_file = NewFile {
+            file_path: "/home/user/Documents/test_game/00.sav",
+            file_hash: &hash,
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: None,
+        };
+
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
@@ -686,32 +2769,86 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        db.create_save(expected);
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
+            .execute(&conn)
+            .unwrap();
+
+        diesel::insert_into(files::table)
+            .values(&new_file)
+            .execute(&conn)
+            .unwrap();
 
-        let path = expected.save_path;
-        let list: Vec<Save> = {
-            use crate::schema::saves::dsl::*;
-            saves.filter(save_path.eq(path)).load(&conn).unwrap()
+        let file_list: Vec<File> = files
+            .filter(file_path.eq(&new_file.file_path))
+            .load(&conn)
+            .unwrap();
+
+        let full_file = file_list.first().unwrap().clone();
+
+        let changed_file_hash: [u8; 32] = rand::random();
+        let time = Utc::now().naive_utc();
+
+        let edit = EditFile {
+            id: full_file.id,
+            file_hash: &changed_file_hash,
+            modified_at: time,
+            file_type: None,
+            link_target: None,
+            permissions: Some(0o600),
+            file_mtime: None,
+            file_size: Some(4096),
         };
-        let actual = list.first().unwrap().clone();
+
+        db.update_file(edit);
+
+        let file_list: Vec<File> = files.filter(id.eq(full_file.id)).load(&conn).unwrap();
+        let changed_file = file_list.first().unwrap().clone();
 
         drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(list.len() == 1);
-        assert_eq!(actual, expected);
+        assert_eq!(changed_file_hash.to_vec(), changed_file.file_hash);
+        assert_eq!(time, changed_file.modified_at);
+        assert_eq!(Some(0o600), changed_file.permissions);
+        assert_eq!(Some(4096), changed_file.file_size);
+        assert_eq!(full_file.file_type, changed_file.file_type);
+        assert_ne!(full_file, changed_file);
     }
 
     #[test]
-    fn get_save_success() {
-        // FIXME: With get_save_success, get_file_success and get_user_success
-        // we only test one out of many different queries we could come up with
-        // We might want to consider writing tests for all of those conditions,
-        // no matter how tedious it may be
+    #[ignore]
+    fn update_file_failure() {
+        unimplemented!()
+    }
 
-        use crate::schema::saves;
+    #[test]
+    #[ignore]
+    fn delete_file_success() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_file_failure() {
+        unimplemented!()
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_files_success() {
+        unimplemented!()
+    }
 
+    #[test]
+    #[ignore]
+    fn delete_files_failure() {
+        unimplemented!()
+    }
+
+    #[test]
+    fn get_executables_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -720,7 +2857,21 @@ mod tests {
 
         let time = Utc::now().naive_utc();
 
-        let expected = NewSave {
+        let expected1 = NewExecutable {
+            name: "game.exe",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let expected2 = NewExecutable {
+            name: "game-launcher",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
@@ -743,26 +2894,30 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(saves::table)
-            .values(&expected)
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
             .execute(&conn)
             .unwrap();
 
-        let query = SaveQuery::new().with_friendly_name("test_game");
-        let actual = db.get_save(query).unwrap();
+        db.create_executable(expected1);
+        db.create_executable(expected2);
+
+        let query = ExecutableQuery::new().with_save_id(1);
+        let list = db.get_executables(query).unwrap();
+        let actual1 = list.first().unwrap().clone();
+        let actual2 = list.get(1).unwrap().clone();
 
         drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert_eq!(actual, expected);
+        assert!(list.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
     }
 
     #[test]
-    fn get_save_uuid_success() {
-        use crate::schema::saves;
-        use uuid::Uuid;
-
+    fn delete_executables_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -770,15 +2925,19 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
-        let mut uuid_buf = Uuid::encode_buffer();
 
-        let uuid = Uuid::new_v4().to_hyphenated().encode_lower(&mut uuid_buf);
+        let exe = NewExecutable {
+            name: "game.exe",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
 
-        let expected = NewSave {
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
-            uuid,
+            uuid: "{uuid}",
             user_id: 1,
             created_at: time,
             modified_at: time,
@@ -797,32 +2956,17 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(saves::table)
-            .values(&expected)
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
             .execute(&conn)
             .unwrap();
 
-        let query = SaveQuery::new().with_uuid(uuid);
-        let actual = db.get_save(query).unwrap();
-
-        drop(conn);
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert_eq!(actual, expected);
-    }
-
-    #[test]
-    fn get_save_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
-
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        db.create_executable(exe);
+        db.delete_executables(ExecutableQuery::new().with_save_id(1));
 
-        let query = SaveQuery::new().with_friendly_name("not_in_db");
-        let option = db.get_save(query);
+        let option = db.get_executables(ExecutableQuery::new().with_save_id(1));
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
@@ -830,9 +2974,7 @@ mod tests {
     }
 
     #[test]
-    fn get_saves_success() {
-        use crate::schema::saves;
-
+    fn get_tags_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -841,7 +2983,7 @@ mod tests {
 
         let time = Utc::now().naive_utc();
 
-        let expected1 = NewSave {
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
@@ -851,9 +2993,7 @@ mod tests {
             modified_at: time,
         };
 
-        let time = Utc::now().naive_utc();
-
-        let expected2 = NewSave {
+        let save2 = NewSave {
             friendly_name: "other_game",
             save_path: "/home/user/Documents/other_game",
             backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
@@ -876,52 +3016,49 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        // Batch Inserts are not supported in diesel (when it comes to SQlite)
-        diesel::insert_into(saves::table)
-            .values(&expected1)
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(saves::table)
-            .values(&expected2)
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
             .execute(&conn)
             .unwrap();
 
-        let query = SaveQuery::new().with_user_id(1);
-        let saves = db.get_saves(query).unwrap();
-        let actual1: Save = saves.get(0).unwrap().clone();
-        let actual2: Save = saves.get(1).unwrap().clone();
-
-        drop(conn);
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert!(saves.len() == 2);
-        assert_eq!(actual1, expected1);
-        assert_eq!(actual2, expected2);
-    }
+        let expected1 = NewTag {
+            name: "100%",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
 
-    #[test]
-    fn get_saves_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        let expected2 = NewTag {
+            name: "speedrun",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        db.create_tag(expected2);
+        db.create_tag(expected1);
+        db.create_tag(expected2); // Duplicate tags are ignored
 
-        let query = SaveQuery::new().with_user_id(1);
-        let saves = db.get_saves(query);
+        let list = db.get_tags(TagQuery::new().with_save_id(1)).unwrap();
+        let actual1 = list.first().unwrap().clone();
+        let actual2 = list.get(1).unwrap().clone();
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(saves.is_none());
+        assert!(list.len() == 2);
+        assert_eq!(actual1, expected1);
+        assert_eq!(actual2, expected2);
     }
 
     #[test]
-    fn get_all_saves_success() {
-        use crate::schema::saves;
-
+    fn delete_tags_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -930,7 +3067,7 @@ mod tests {
 
         let time = Utc::now().naive_utc();
 
-        let expected1 = NewSave {
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
@@ -940,9 +3077,7 @@ mod tests {
             modified_at: time,
         };
 
-        let time = Utc::now().naive_utc();
-
-        let expected2 = NewSave {
+        let save2 = NewSave {
             friendly_name: "other_game",
             save_path: "/home/user/Documents/other_game",
             backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
@@ -965,50 +3100,43 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(saves::table)
-            .values(&expected1)
+        diesel::insert_into(schema::saves::table)
+            .values(&save1)
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(saves::table)
-            .values(&expected2)
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
             .execute(&conn)
             .unwrap();
 
-        let save_list = db.get_all_saves().unwrap();
-        let actual1 = save_list.get(0).unwrap().clone();
-        let actual2 = save_list.get(1).unwrap().clone();
-
-        drop(conn);
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert!(save_list.len() == 2);
-        assert_eq!(actual1, expected1);
-        assert_eq!(actual2, expected2);
-    }
-
-    #[test]
-    fn get_all_saves_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        for tag in &["speedrun", "switch"] {
+            db.create_tag(NewTag {
+                name: tag,
+                save_id: 1,
+                created_at: time,
+                modified_at: time,
+            });
+        }
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        let query = TagQuery::new().with_save_id(1).with_name("switch");
+        db.delete_tags(query);
+        let remaining = db.get_tags(TagQuery::new().with_save_id(1)).unwrap();
 
-        let save_list = db.get_all_saves();
+        db.delete_tags(TagQuery::new().with_save_id(1));
+        let option = db.get_tags(TagQuery::new().with_save_id(1));
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(save_list.is_none());
+        assert!(remaining.len() == 1);
+        assert_eq!(remaining[0].name, "speedrun");
+        assert!(option.is_none());
     }
 
     #[test]
-    fn update_save_success() {
-        use crate::schema::saves;
-        use crate::schema::saves::dsl::*;
-
+    fn get_notes_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -1017,7 +3145,7 @@ mod tests {
 
         let time = Utc::now().naive_utc();
 
-        let new_save = NewSave {
+        let save1 = NewSave {
             friendly_name: "test_game",
             save_path: "/home/user/Documents/test_game",
             backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
@@ -1027,117 +3155,11 @@ mod tests {
             modified_at: time,
         };
 
-        let user1 = NewUser {
-            username: "DarkFlameMaster",
-            created_at: time,
-            modified_at: time,
-        };
-
-        let conn = db.get_conn();
-
-        diesel::insert_into(schema::users::table)
-            .values(&user1)
-            .execute(&conn)
-            .unwrap();
-
-        diesel::insert_into(saves::table)
-            .values(&new_save)
-            .execute(&conn)
-            .unwrap();
-
-        let save_list: Vec<Save> = saves
-            .filter(save_path.eq(&new_save.save_path))
-            .load(&conn)
-            .unwrap();
-
-        let full_save = save_list.first().unwrap().clone();
-
-        let changed_friendly_name = "sample_test_game";
-        let time = Utc::now().naive_utc();
-
-        let edit = EditSave {
-            id: full_save.id,
-            friendly_name: Some(changed_friendly_name),
-            save_path: None,
-            modified_at: time,
-        };
-
-        db.update_save(edit);
-
-        let save_list: Vec<Save> = saves.filter(id.eq(full_save.id)).load(&conn).unwrap();
-        let changed_save = save_list.first().unwrap().clone();
-
-        drop(conn);
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert_eq!(changed_friendly_name, changed_save.friendly_name);
-        assert_eq!(time, changed_save.modified_at);
-        assert_ne!(full_save, changed_save);
-    }
-
-    #[test]
-    #[ignore]
-    fn update_save_failure() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_save_success() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_save_failure() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_saves_success() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_saves_failure() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn create_new_file() {
-        unimplemented!()
-    }
-
-    #[test]
-    fn get_file_success() {
-        use crate::schema::files;
-
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
-
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
-
-        let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-
-        let expected = NewFile {
-            file_path: "/home/user/Documents/test_game/00.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
-
-        let save1 = NewSave {
-            friendly_name: "test_game",
-            save_path: "/home/user/Documents/test_game",
-            backup_path: "/home/user/.local/share/save-sync/{uuid}/test_game",
-            uuid: "{uuid}",
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/other_game",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
+            uuid: "{other_uuid}",
             user_id: 1,
             created_at: time,
             modified_at: time,
@@ -1161,45 +3183,39 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(files::table)
-            .values(&expected)
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
             .execute(&conn)
             .unwrap();
 
-        let path = Path::new("/home/user/Documents/test_game/00.sav");
-        let query = FileQuery::new().with_path(&path);
-
-        let actual = db.get_file(query).unwrap();
-
-        drop(conn);
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert_eq!(actual, expected);
-    }
+        let expected = NewNote {
+            body: "Before the final boss, don't overwrite",
+            save_id: 2,
+            created_at: time,
+            modified_at: time,
+        };
 
-    #[test]
-    fn get_file_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        db.create_note(expected);
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        let list = db.get_notes(NoteQuery::new().with_save_id(2)).unwrap();
+        let actual = list.first().unwrap().clone();
+        let none = db.get_notes(NoteQuery::new().with_save_id(1));
 
-        let hash: [u8; 32] = rand::random();
-        let query = FileQuery::new().with_hash(&hash);
-        let option = db.get_file(query);
+        db.delete_notes(NoteQuery::new().with_id(actual.id));
+        let deleted = db.get_notes(NoteQuery::new().with_save_id(2));
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(option.is_none());
+        assert!(list.len() == 1);
+        assert_eq!(actual, expected);
+        assert!(none.is_none());
+        assert!(deleted.is_none());
     }
 
     #[test]
-    fn get_files_success() {
-        use crate::schema::files;
-
+    fn get_chunks_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -1207,24 +3223,6 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-        let expected1 = NewFile {
-            file_path: "/home/user/Documents/test_game/00.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
-
-        let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-        let expected2 = NewFile {
-            file_path: "/home/user/Documents/test_game/01.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
 
         let save1 = NewSave {
             friendly_name: "test_game",
@@ -1242,6 +3240,19 @@ mod tests {
             modified_at: time,
         };
 
+        let file1 = NewFile {
+            file_path: "/home/user/Documents/test_game/00.sav",
+            file_hash: &[0x0A, 0x0B],
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: 0,
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: Some(3072),
+        };
+
         let conn = db.get_conn();
 
         diesel::insert_into(schema::users::table)
@@ -1254,52 +3265,51 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        // Batch Inserts are not supported in diesel (when it comes to SQlite)
-        diesel::insert_into(files::table)
-            .values(&expected1)
-            .execute(&conn)
-            .unwrap();
-
-        diesel::insert_into(files::table)
-            .values(&expected2)
+        diesel::insert_into(schema::files::table)
+            .values(&file1)
             .execute(&conn)
             .unwrap();
 
-        let query = FileQuery::new().with_save_id(1);
-        let files = db.get_files(query).unwrap();
-        let actual1 = files.get(0).unwrap().clone();
-        let actual2 = files.get(1).unwrap().clone();
-
-        drop(conn);
-        drop(db);
+        let new_chunk = |position: i32, hash: &'static [u8]| NewChunk {
+            file_id: 1,
+            position,
+            chunk_hash: hash,
+            chunk_size: 1024,
+            created_at: time,
+            modified_at: time,
+        };
 
-        test_dir.close().unwrap();
-        assert!(files.len() == 2);
-        assert_eq!(actual1, expected1);
-        assert_eq!(actual2, expected2);
-    }
+        // Inserted out of order, and the first chunk is repeated at the end
+        let expected = vec![
+            new_chunk(1, &[0x02]),
+            new_chunk(0, &[0x01]),
+            new_chunk(2, &[0x01]),
+        ];
 
-    #[test]
-    fn get_files_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
+        db.create_chunks(&expected);
 
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
+        let list = db.get_chunks(ChunkQuery::new().with_file_id(1)).unwrap();
+        let positions: Vec<i32> = list.iter().map(|chunk| chunk.position).collect();
+        let shared = db.get_chunks(ChunkQuery::new().with_hash(&[0x01])).unwrap();
+        let none = db.get_chunks(ChunkQuery::new().with_file_id(2));
 
-        let query = FileQuery::new().with_save_id(1);
-        let files = db.get_files(query);
+        db.delete_chunks(ChunkQuery::new().with_file_id(1));
+        let deleted = db.get_chunks(ChunkQuery::new().with_file_id(1));
 
+        drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(files.is_none());
+        assert_eq!(positions, vec![0, 1, 2]);
+        assert_eq!(list[0], expected[1]);
+        assert_eq!(list[1], expected[0]);
+        assert_eq!(shared.len(), 2);
+        assert!(none.is_none());
+        assert!(deleted.is_none());
     }
 
     #[test]
-    fn get_all_files_success() {
-        use crate::schema::files;
-
+    fn get_saves_by_tag_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -1307,26 +3317,6 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-
-        let expected1 = NewFile {
-            file_path: "/home/user/Documents/test_game/00.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
-
-        let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-
-        let expected2 = NewFile {
-            file_path: "/home/user/Documents/test_game/01.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
 
         let save1 = NewSave {
             friendly_name: "test_game",
@@ -1338,6 +3328,16 @@ mod tests {
             modified_at: time,
         };
 
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/other_game",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
         let user1 = NewUser {
             username: "DarkFlameMaster",
             created_at: time,
@@ -1356,50 +3356,33 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(files::table)
-            .values(&expected1)
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(files::table)
-            .values(&expected2)
-            .execute(&conn)
-            .unwrap();
+        db.create_tag(NewTag {
+            name: "speedrun",
+            save_id: 2,
+            created_at: time,
+            modified_at: time,
+        });
 
-        let file_list = db.get_all_files().unwrap();
-        let actual2 = file_list.get(1).unwrap().clone();
-        let actual1 = file_list.get(0).unwrap().clone();
+        let query = SaveQuery::new().with_user_id(1).with_tag("speedrun");
+        let list = db.get_saves(query).unwrap();
+        let none = db.get_saves(SaveQuery::new().with_tag("100%"));
 
         drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert!(file_list.len() == 2);
-        assert_eq!(actual1, expected1);
-        assert_eq!(actual2, expected2);
-    }
-
-    #[test]
-    fn get_all_files_failure() {
-        let test_dir = TempDir::new().unwrap();
-        let tmp_dir = test_dir.path();
-
-        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
-        let db = Database::new(&db_path).unwrap();
-
-        let file_list = db.get_all_files();
-
-        drop(db);
-
-        test_dir.close().unwrap();
-        assert!(file_list.is_none());
+        assert!(list.len() == 1);
+        assert_eq!(list[0], save2);
+        assert!(none.is_none());
     }
 
     #[test]
-    fn update_file_success() {
-        use crate::schema::files;
-        use crate::schema::files::dsl::*;
-
+    fn get_saves_by_text_success() {
         let test_dir = TempDir::new().unwrap();
         let tmp_dir = test_dir.path();
 
@@ -1407,15 +3390,6 @@ mod tests {
         let db = Database::new(&db_path).unwrap();
 
         let time = Utc::now().naive_utc();
-        let hash: [u8; 32] = rand::random();
-
-        let new_file = NewFile {
-            file_path: "/home/user/Documents/test_game/00.sav",
-            file_hash: &hash,
-            save_id: 1,
-            created_at: time,
-            modified_at: time,
-        };
 
         let save1 = NewSave {
             friendly_name: "test_game",
@@ -1427,6 +3401,16 @@ mod tests {
             modified_at: time,
         };
 
+        let save2 = NewSave {
+            friendly_name: "other_game",
+            save_path: "/home/user/Documents/other_game",
+            backup_path: "/home/user/.local/share/save-sync/{other_uuid}/other_game",
+            uuid: "{other_uuid}",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        };
+
         let user1 = NewUser {
             username: "DarkFlameMaster",
             created_at: time,
@@ -1445,69 +3429,43 @@ mod tests {
             .execute(&conn)
             .unwrap();
 
-        diesel::insert_into(files::table)
-            .values(&new_file)
+        diesel::insert_into(schema::saves::table)
+            .values(&save2)
             .execute(&conn)
             .unwrap();
 
-        let file_list: Vec<File> = files
-            .filter(file_path.eq(&new_file.file_path))
-            .load(&conn)
-            .unwrap();
-
-        let full_file = file_list.first().unwrap().clone();
-
-        let changed_file_hash: [u8; 32] = rand::random();
-        let time = Utc::now().naive_utc();
-
-        let edit = EditFile {
-            id: full_file.id,
-            file_hash: &changed_file_hash,
+        db.create_tag(NewTag {
+            name: "switch",
+            save_id: 2,
+            created_at: time,
             modified_at: time,
-        };
+        });
 
-        db.update_file(edit);
+        db.create_note(NewNote {
+            body: "Got 100% on this one",
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+        });
 
-        let file_list: Vec<File> = files.filter(id.eq(full_file.id)).load(&conn).unwrap();
-        let changed_file = file_list.first().unwrap().clone();
+        let by_name = db.get_saves(SaveQuery::new().with_text("OTHER")).unwrap();
+        let by_tag = db.get_saves(SaveQuery::new().with_text("witc")).unwrap();
+        let by_note = db.get_saves(SaveQuery::new().with_text("100%")).unwrap();
+        let by_path = db
+            .get_saves(SaveQuery::new().with_text("Documents"))
+            .unwrap();
+        // % and _ have to be matched literally
+        let none = db.get_saves(SaveQuery::new().with_text("0_%"));
 
         drop(conn);
         drop(db);
 
         test_dir.close().unwrap();
-        assert_eq!(changed_file_hash.to_vec(), changed_file.file_hash);
-        assert_eq!(time, changed_file.modified_at);
-        assert_ne!(full_file, changed_file);
-    }
-
-    #[test]
-    #[ignore]
-    fn update_file_failure() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_file_success() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_file_failure() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_files_success() {
-        unimplemented!()
-    }
-
-    #[test]
-    #[ignore]
-    fn delete_files_failure() {
-        unimplemented!()
+        assert_eq!(by_name, vec![save2]);
+        assert_eq!(by_tag, vec![save2]);
+        assert_eq!(by_note, vec![save1]);
+        assert!(by_path.len() == 2);
+        assert!(none.is_none());
     }
 
     #[test]
@@ -1692,6 +3650,35 @@ mod tests {
         assert_ne!(full_user, changed_user);
     }
 
+    #[test]
+    fn set_and_get_setting() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let db_path: PathBuf = [tmp_dir, &PathBuf::from("test.db")].iter().collect();
+        let db = Database::new(&db_path).unwrap();
+        let time = Utc::now().naive_utc();
+
+        let missing = db.get_setting("xxhash_seed");
+
+        for value in &["1337", "11037"] {
+            db.set_setting(NewSetting {
+                name: "xxhash_seed",
+                value,
+                modified_at: time,
+            });
+        }
+
+        let setting = db.get_setting("xxhash_seed").unwrap();
+
+        drop(db);
+
+        test_dir.close().unwrap();
+        assert!(missing.is_none());
+        assert_eq!(setting.value, "11037");
+        assert_eq!(setting.modified_at, time);
+    }
+
     #[test]
     #[ignore]
     fn update_user_failure() {
@@ -0,0 +1,170 @@
+use similar::TextDiff;
+use std::ops::Range;
+use std::str;
+
+/// Lines of unchanged text shown around every change
+const CONTEXT_LINES: usize = 3;
+
+/// How the backed up contents of a file differ from the contents on disk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diff {
+    Same,
+    /// A unified diff, for when both versions are text
+    Text(String),
+    Binary(BinaryDiff),
+}
+
+/// Summarizes the changes between two versions of a binary file
+///
+/// # Properties
+/// * `old_size` - The size of the old version in bytes
+/// * `new_size` - The size of the new version in bytes
+/// * `ranges` - The byte ranges which differ, in ascending order. Bytes past the end of the shorter version
+///   count as changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryDiff {
+    pub old_size: u64,
+    pub new_size: u64,
+    pub ranges: Vec<Range<u64>>,
+}
+
+impl BinaryDiff {
+    /// Compares `old` and `new` byte by byte. Bytes are only compared to the byte at the same offset,
+    /// so inserting a single byte marks everything after it as changed
+    pub fn new(old: &[u8], new: &[u8]) -> BinaryDiff {
+        let mut ranges: Vec<Range<u64>> = vec![];
+        let common = old.len().min(new.len());
+
+        for (i, (a, b)) in old.iter().zip(new).enumerate() {
+            if a == b {
+                continue;
+            }
+
+            let i = i as u64;
+
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+
+        if old.len() != new.len() {
+            let tail = common as u64..old.len().max(new.len()) as u64;
+
+            match ranges.last_mut() {
+                Some(range) if range.end == tail.start => range.end = tail.end,
+                _ => ranges.push(tail),
+            }
+        }
+
+        BinaryDiff {
+            old_size: old.len() as u64,
+            new_size: new.len() as u64,
+            ranges,
+        }
+    }
+
+    /// The number of bytes which differ
+    pub fn changed_bytes(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+}
+
+/// Compares `old` to `new`. Files which are valid UTF-8 and don't contain any NUL bytes are diffed line by line,
+/// everything else is treated as binary.
+///
+/// `old_name` and `new_name` end up in the header of a unified diff.
+///
+/// # Examples
+/// ```
+/// use save_sync::diff::{self, Diff};
+///
+/// let old = b"[Player]\nlevel=1\nname=Link\n";
+/// let new = b"[Player]\nlevel=2\nname=Link\n";
+///
+/// match diff::diff(old, new, "backup", "live") {
+///     Diff::Text(text) => assert!(text.contains("-level=1\n+level=2\n")),
+///     other => panic!("expected a text diff, got {:?}", other),
+/// }
+/// ```
+pub fn diff(old: &[u8], new: &[u8], old_name: &str, new_name: &str) -> Diff {
+    if old == new {
+        return Diff::Same;
+    }
+
+    match (as_text(old), as_text(new)) {
+        (Some(old), Some(new)) => {
+            let text = TextDiff::from_lines(old, new)
+                .unified_diff()
+                .context_radius(CONTEXT_LINES)
+                .header(old_name, new_name)
+                .to_string();
+
+            Diff::Text(text)
+        }
+        _ => Diff::Binary(BinaryDiff::new(old, new)),
+    }
+}
+
+/// `data` as text, unless it looks like a binary file
+fn as_text(data: &[u8]) -> Option<&str> {
+    if data.contains(&0) {
+        return None;
+    }
+
+    str::from_utf8(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_files_are_the_same() {
+        assert_eq!(diff(b"{}", b"{}", "a", "b"), Diff::Same);
+        assert_eq!(diff(&[0, 1, 2], &[0, 1, 2], "a", "b"), Diff::Same);
+    }
+
+    #[test]
+    fn text_files_get_a_unified_diff() {
+        let old = "{\n  \"level\": 1,\n  \"gold\": 20\n}\n";
+        let new = "{\n  \"level\": 2,\n  \"gold\": 20\n}\n";
+
+        let result = diff(old.as_bytes(), new.as_bytes(), "backup", "live");
+
+        let expected = "--- backup\n+++ live\n@@ -1,4 +1,4 @@\n {\n-  \"level\": 1,\n+  \"level\": 2,\n   \"gold\": 20\n }\n";
+        assert_eq!(result, Diff::Text(expected.to_string()));
+    }
+
+    #[test]
+    fn files_with_nul_bytes_are_binary() {
+        let result = diff(b"slot\x001", b"slot\x002", "a", "b");
+
+        assert!(matches!(result, Diff::Binary(_)));
+    }
+
+    #[test]
+    fn adjacent_changes_are_merged() {
+        let old = [0, 0, 0, 0, 0, 0, 0, 0];
+        let new = [0, 1, 1, 0, 0, 1, 0, 0];
+
+        let binary = BinaryDiff::new(&old, &new);
+
+        assert_eq!(binary.ranges, vec![1..3, 5..6]);
+        assert_eq!(binary.changed_bytes(), 3);
+    }
+
+    #[test]
+    fn size_changes_are_ranges() {
+        let grown = BinaryDiff::new(&[0, 0, 0], &[0, 0, 1, 7, 7]);
+        let shrunk = BinaryDiff::new(&[0, 0, 0, 0], &[0, 0]);
+
+        assert_eq!(grown.ranges, vec![2..5]);
+        assert_eq!((grown.old_size, grown.new_size), (3, 5));
+        assert_eq!(shrunk.ranges, vec![2..4]);
+        assert_eq!((shrunk.old_size, shrunk.new_size), (4, 2));
+    }
+}
@@ -0,0 +1,559 @@
+use crate::archive::query::{ChunkQuery, FileQuery, UserQuery};
+use crate::archive::{Archive, ArchiveError};
+use crate::chunking::{ChunkError, ChunkStore};
+use crate::context::SaveSync;
+use crate::database::DatabaseError;
+use crate::lock::{LockError, LockMode};
+use crate::models::{Chunk, EditFile, EditSave, File, FileType, NewUser, Save, User};
+use crate::paths;
+use crate::storage::{self, StorageConfig, StorageError};
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum DoctorError {
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+    #[error(transparent)]
+    StorageError(#[from] StorageError),
+    #[error(transparent)]
+    ChunkError(#[from] ChunkError),
+    #[error(transparent)]
+    ArchiveError(#[from] ArchiveError),
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+    #[error(transparent)]
+    LockError(#[from] LockError),
+    #[error("There is no storage backend called {0}")]
+    UnknownBackend(String),
+}
+
+/// Hashes are 8 bytes long, so this never matches the hash of a file
+const STALE_HASH: &[u8] = &[0];
+
+/// Something which is wrong with the database or the backups it describes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// Files of a Save whose backups aren't in storage anymore
+    MissingBackups { save: Save, files: Vec<File> },
+    /// A directory in the data location which is named after a UUID no Save has
+    OrphanedBackup(PathBuf),
+    /// Backups in a configured storage backend which are stored under a UUID no Save has
+    OrphanedStorage { backend: String, uuid: String },
+    /// A File which isn't inside of the path of the Save it belongs to
+    StrayFile { save: Save, file: File },
+    /// A Save which belongs to a User that doesn't exist
+    MissingOwner(Save),
+}
+
+impl Problem {
+    /// What `repair` does about this problem
+    pub fn remedy(&self) -> &'static str {
+        match self {
+            Problem::MissingBackups { .. } => "back the files up again during the next update",
+            Problem::OrphanedBackup(_) => "delete the directory",
+            Problem::OrphanedStorage { .. } => "delete the backups from the backend",
+            Problem::StrayFile { .. } => "forget about the file and delete its backup",
+            Problem::MissingOwner(_) => "hand the save to the local user",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingBackups { save, files } => write!(
+                f,
+                "{} file(s) of {} are missing from its backup",
+                files.len(),
+                label(save)
+            ),
+            Problem::OrphanedBackup(path) => {
+                write!(f, "{} does not belong to any save", path.to_string_lossy())
+            }
+            Problem::OrphanedStorage { backend, uuid } => write!(
+                f,
+                "{}/ in storage backend {} does not belong to any save",
+                uuid, backend
+            ),
+            Problem::StrayFile { save, file } => write!(
+                f,
+                "{} is not inside of {}",
+                paths::decode(&file.file_path).to_string_lossy(),
+                label(save)
+            ),
+            Problem::MissingOwner(save) => write!(
+                f,
+                "{} belongs to user #{}, who does not exist",
+                label(save),
+                save.user_id
+            ),
+        }
+    }
+}
+
+fn label(save: &Save) -> String {
+    if save.friendly_name.is_empty() {
+        paths::decode(&save.save_path).to_string_lossy().into()
+    } else {
+        save.friendly_name.clone()
+    }
+}
+
+/// Looks for every kind of `Problem` in the database of `ctx`, its data location and every storage backend
+/// in its config
+pub fn diagnose(ctx: &SaveSync) -> Result<Vec<Problem>, DoctorError> {
+    let db = ctx.db();
+    let saves = db.get_all_saves().unwrap_or_default();
+    let mut problems = vec![];
+
+    for save in &saves {
+        let files = db
+            .get_files(FileQuery::new().with_save_id(save.id))
+            .unwrap_or_default();
+        let save_path = paths::decode(&save.save_path);
+
+        let (inside, stray): (Vec<File>, Vec<File>) = files
+            .into_iter()
+            .partition(|file| paths::decode(&file.file_path).starts_with(&save_path));
+
+        for file in stray {
+            problems.push(Problem::StrayFile {
+                save: save.clone(),
+                file,
+            });
+        }
+
+        let missing = missing_backups(ctx, save, inside)?;
+
+        if !missing.is_empty() {
+            problems.push(Problem::MissingBackups {
+                save: save.clone(),
+                files: missing,
+            });
+        }
+
+        if db
+            .get_user(UserQuery::new().with_id(save.user_id))
+            .is_none()
+        {
+            problems.push(Problem::MissingOwner(save.clone()));
+        }
+    }
+
+    let data_location = &ctx.config().data_location;
+
+    let entries = match fs::read_dir(data_location) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>()?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+        Err(err) => return Err(err.into()),
+    };
+
+    for entry in entries {
+        let name = entry.file_name();
+
+        // Anything which isn't named after a UUID wasn't put there by a save
+        let uuid = match name.to_str().map(Uuid::parse_str) {
+            Some(Ok(_)) => name.to_string_lossy(),
+            _ => continue,
+        };
+
+        if entry.file_type()?.is_dir() && !saves.iter().any(|save| save.uuid == uuid) {
+            problems.push(Problem::OrphanedBackup(entry.path()));
+        }
+    }
+
+    // Other backends can't be read like a directory, so their keys are grouped by the UUID they start with
+    for (name, config) in &ctx.config().backends {
+        if is_data_location(config, data_location) {
+            continue;
+        }
+
+        let keys = storage::open(config, data_location)?.list("")?;
+        let mut uuids: Vec<&str> = keys
+            .iter()
+            .filter_map(|key| key.split('/').next())
+            .filter(|part| Uuid::parse_str(part).is_ok())
+            .collect();
+
+        // Keys are listed in order, so the keys of a UUID are next to each other
+        uuids.dedup();
+
+        for uuid in uuids {
+            if !saves.iter().any(|save| save.uuid == uuid) {
+                problems.push(Problem::OrphanedStorage {
+                    backend: name.clone(),
+                    uuid: uuid.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Whether `config` stores its backups in the data location, which `diagnose` reads directly
+fn is_data_location(config: &StorageConfig, data_location: &Path) -> bool {
+    match config {
+        StorageConfig::Local { path } => path.as_deref().is_none_or(|path| path == data_location),
+        _ => false,
+    }
+}
+
+/// The regular files in `files` which aren't in the storage backend of `save`
+fn missing_backups(
+    ctx: &SaveSync,
+    save: &Save,
+    files: Vec<File>,
+) -> Result<Vec<File>, DoctorError> {
+    let db = ctx.db();
+    let save_path = paths::decode(&save.save_path);
+    let backup_path = paths::decode(&save.backup_path);
+    let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+    let store = ChunkStore::new(backend.clone());
+    let mut missing = vec![];
+
+    for file in files {
+        if file.kind() != FileType::File {
+            continue;
+        }
+
+        let is_stored = match db.get_chunks(ChunkQuery::new().with_file_id(file.id)) {
+            Some(chunks) => chunks.iter().try_fold(true, |is_stored, chunk| {
+                let hash = Archive::byte_vec_to_u64(&chunk.chunk_hash)?;
+                Ok::<_, DoctorError>(is_stored && store.contains(hash)?)
+            })?,
+            None => {
+                let file_path = paths::decode(&file.file_path);
+                backend.exists(&storage::backup_key(&save_path, &backup_path, &file_path)?)?
+            }
+        };
+
+        if !is_stored {
+            missing.push(file);
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Fixes `problem`, as described by `Problem::remedy`
+pub fn repair(ctx: &SaveSync, problem: &Problem) -> Result<(), DoctorError> {
+    let db = ctx.db();
+    let time = Utc::now().naive_utc();
+
+    match problem {
+        Problem::MissingBackups { files, .. } => {
+            for file in files {
+                // A size which no file can have makes the next update hash the file, and the hash
+                // can't match either, so the file is backed up again
+                db.update_file(EditFile {
+                    id: file.id,
+                    file_hash: STALE_HASH,
+                    modified_at: time,
+                    file_type: None,
+                    link_target: None,
+                    permissions: None,
+                    file_mtime: None,
+                    file_size: Some(-1),
+                });
+            }
+        }
+        Problem::OrphanedBackup(path) => fs::remove_dir_all(path)?,
+        Problem::OrphanedStorage { backend, uuid } => {
+            let config = ctx
+                .config()
+                .backends
+                .get(backend)
+                .ok_or_else(|| DoctorError::UnknownBackend(backend.clone()))?;
+
+            storage::open(config, &ctx.config().data_location)?
+                .delete_prefix(&format!("{}/", uuid))?;
+        }
+        Problem::StrayFile { save, file } => {
+            let chunks = db
+                .get_chunks(ChunkQuery::new().with_file_id(file.id))
+                .unwrap_or_default();
+
+            // The chunks of the file are deleted along with it, but not while they may be backed up again
+            let _chunk_lock = ctx.lock_chunks(LockMode::Exclusive)?;
+            db.delete_file(FileQuery::new().with_id(file.id))?;
+            release_chunks(ctx, save, &chunks)?;
+            delete_untracked_backups(ctx, save)?;
+        }
+        Problem::MissingOwner(save) => {
+            let owner = local_user(ctx)?;
+
+            db.update_save(EditSave {
+                id: save.id,
+                friendly_name: None,
+                save_path: None,
+                user_id: Some(owner.id),
+                modified_at: time,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every chunk in `chunks` from the storage of `save` which no File in that storage uses anymore.
+///
+/// The caller has to hold the chunk lock exclusively
+fn release_chunks(ctx: &SaveSync, save: &Save, chunks: &[Chunk]) -> Result<(), DoctorError> {
+    let store = ChunkStore::new(ctx.storage_for(&save.uuid, &save.friendly_name)?);
+
+    for chunk in chunks {
+        if !ctx.is_chunk_used(save, &chunk.chunk_hash) {
+            store.remove(Archive::byte_vec_to_u64(&chunk.chunk_hash)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes everything in the backup of `save` which isn't the backup of one of its files.
+///
+/// The key of a stray file can't be derived from its path, since the path isn't inside of the save anymore
+fn delete_untracked_backups(ctx: &SaveSync, save: &Save) -> Result<(), DoctorError> {
+    let save_path = paths::decode(&save.save_path);
+    let backup_path = paths::decode(&save.backup_path);
+    let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+    let prefix = storage::backup_key(&save_path, &backup_path, &save_path)?;
+
+    let tracked: HashSet<String> = ctx
+        .db()
+        .get_files(FileQuery::new().with_save_id(save.id))
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|file| {
+            let file_path = paths::decode(&file.file_path);
+            storage::backup_key(&save_path, &backup_path, &file_path).ok()
+        })
+        .collect();
+
+    for key in backend.list(&format!("{}/", prefix))? {
+        if !tracked.contains(&key) {
+            backend.delete(&key)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The user named `local_username` in the config, who is created if they don't exist yet
+fn local_user(ctx: &SaveSync) -> Result<User, DoctorError> {
+    let db = ctx.db();
+    let username = &ctx.config().local_username;
+    let time = Utc::now().naive_utc();
+
+    if let Some(user) = db.get_user(UserQuery::new().with_username(username)) {
+        return Ok(user);
+    }
+
+    db.create_user(NewUser {
+        username,
+        created_at: time,
+        modified_at: time,
+    })?;
+
+    Ok(db
+        .get_user(UserQuery::new().with_username(username))
+        .expect("The local user was just created"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::query::SaveQuery;
+    use crate::config::Config;
+    use crate::models::NewSave;
+    use crate::test_utils::create_file;
+    use diesel::{Connection, SqliteConnection};
+    use tempfile::TempDir;
+
+    #[test]
+    fn diagnose_and_repair() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let data_dir = tmp_dir.join("data");
+
+        let ctx = SaveSync::new(Config {
+            db_location: tmp_dir.join("saves.db"),
+            data_location: data_dir.clone(),
+            local_username: "User1".to_string(),
+            ..Config::default()
+        })
+        .unwrap();
+        let db = ctx.db();
+        let time = Utc::now().naive_utc();
+        let uuid = Uuid::new_v4().to_string();
+        let orphan = data_dir.join(Uuid::new_v4().to_string());
+        let backup_path = paths::encode(&data_dir.join(&uuid).join("game"));
+
+        db.create_user(NewUser {
+            username: "Gone",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "game",
+            save_path: "/saves/game",
+            backup_path: &backup_path,
+            uuid: &uuid,
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        create_file(&ctx, "/saves/game/slot1.sav", 4, time);
+        create_file(&ctx, "/saves/game/slot2.sav", 4, time);
+        create_file(&ctx, "/elsewhere/slot3.sav", 4, time);
+
+        let backend = ctx.storage_for(&uuid, "game").unwrap();
+        backend
+            .put(&format!("{}/game/slot1.sav", uuid), b"save")
+            .unwrap();
+
+        // slot3.sav was backed up before its path in the database stopped matching the save
+        backend
+            .put(&format!("{}/game/slot3.sav", uuid), b"save")
+            .unwrap();
+
+        // The owner is deleted the way someone poking around in the database might, without foreign keys
+        let conn =
+            SqliteConnection::establish(&tmp_dir.join("saves.db").to_string_lossy()).unwrap();
+        conn.execute("PRAGMA foreign_keys = OFF").unwrap();
+        conn.execute("DELETE FROM users").unwrap();
+
+        fs::create_dir_all(orphan.join("game")).unwrap();
+        fs::create_dir_all(data_dir.join("not-a-uuid")).unwrap();
+
+        let problems = diagnose(&ctx).unwrap();
+
+        for problem in &problems {
+            repair(&ctx, problem).unwrap();
+        }
+
+        let remaining = diagnose(&ctx).unwrap();
+        let save = db.get_save(SaveQuery::new().with_uuid(&uuid)).unwrap();
+        let owner = db.get_user(UserQuery::new().with_id(save.user_id));
+        let slot2 = db
+            .get_file(FileQuery::new().with_path(&"/saves/game/slot2.sav"))
+            .unwrap();
+        let stray = db.get_file(FileQuery::new().with_path(&"/elsewhere/slot3.sav"));
+        let is_slot1_kept = backend.exists(&format!("{}/game/slot1.sav", uuid)).unwrap();
+        let is_stray_deleted = !backend.exists(&format!("{}/game/slot3.sav", uuid)).unwrap();
+        let is_orphan_deleted = !orphan.exists();
+        let is_other_kept = data_dir.join("not-a-uuid").exists();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+
+        assert_eq!(problems.len(), 4);
+        assert!(
+            matches!(&problems[0], Problem::StrayFile { file, .. } if file.file_path == "/elsewhere/slot3.sav")
+        );
+        assert!(
+            matches!(&problems[1], Problem::MissingBackups { files, .. } if files.len() == 1 && files[0].file_path == "/saves/game/slot2.sav")
+        );
+        assert!(matches!(&problems[2], Problem::MissingOwner(_)));
+        assert_eq!(problems[3], Problem::OrphanedBackup(orphan));
+
+        // The missing backup is only fixed by the next update
+        assert!(matches!(&remaining[..], [Problem::MissingBackups { .. }]));
+        assert_eq!(owner.map(|user| user.username), Some("User1".to_string()));
+        assert_eq!(slot2.file_size, Some(-1));
+        assert!(stray.is_none());
+        assert!(is_slot1_kept);
+        assert!(is_stray_deleted);
+        assert!(is_orphan_deleted);
+        assert!(is_other_kept);
+    }
+
+    #[test]
+    fn orphans_in_other_backends() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let data_dir = tmp_dir.join("data");
+        let mut config = Config {
+            db_location: tmp_dir.join("saves.db"),
+            data_location: data_dir.clone(),
+            ..Config::default()
+        };
+        let usb = StorageConfig::Local {
+            path: Some(tmp_dir.join("usb")),
+        };
+
+        config.backends.insert("usb".to_string(), usb);
+        config
+            .backends
+            .insert("local".to_string(), StorageConfig::default());
+
+        let ctx = SaveSync::new(config).unwrap();
+        let db = ctx.db();
+        let time = Utc::now().naive_utc();
+        let uuid = Uuid::new_v4().to_string();
+        let orphan = Uuid::new_v4().to_string();
+        let backup_path = paths::encode(&data_dir.join(&uuid).join("game"));
+
+        db.create_user(NewUser {
+            username: "User1",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "game",
+            save_path: "/saves/game",
+            backup_path: &backup_path,
+            uuid: &uuid,
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        let backend = storage::open(&ctx.config().backends["usb"], &data_dir).unwrap();
+
+        for key in &[
+            format!("{}/game/slot1.sav", uuid),
+            format!("{}/game/slot1.sav", orphan),
+            format!("{}/game/slot2.sav", orphan),
+            "chunks/00/0000000000000000".to_string(),
+        ] {
+            backend.put(key, b"save").unwrap();
+        }
+
+        let problems = diagnose(&ctx).unwrap();
+
+        for problem in &problems {
+            repair(&ctx, problem).unwrap();
+        }
+
+        let remaining = diagnose(&ctx).unwrap();
+        let keys = backend.list("").unwrap();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+
+        assert_eq!(
+            problems,
+            vec![Problem::OrphanedStorage {
+                backend: "usb".to_string(),
+                uuid: orphan,
+            }]
+        );
+        assert!(remaining.is_empty());
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&"chunks/00/0000000000000000".to_string()));
+        assert!(keys.contains(&format!("{}/game/slot1.sav", uuid)));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::models::{File, FileType};
+use crate::paths;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use filetime::FileTime;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Represents the metadata of an entry on disk which save-sync keeps track of
+///
+/// # Properties
+/// * `file_type` - Whether the entry is a regular file, a directory or a symlink
+/// * `link_target` - The path a symlink points to. `None` for files and directories
+/// * `permissions` - The permission bits of the entry. `None` for symlinks
+/// * `mtime` - The time the entry was last modified
+/// * `size` - The size of the entry in bytes. `None` for directories and symlinks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub file_type: FileType,
+    pub link_target: Option<PathBuf>,
+    pub permissions: Option<i32>,
+    pub mtime: Option<NaiveDateTime>,
+    pub size: Option<i64>,
+}
+
+impl Entry {
+    /// Reads the metadata of `path`. Symlinks are **not** followed.
+    pub fn read<P: AsRef<Path>>(path: &P) -> io::Result<Entry> {
+        let path = path.as_ref();
+        let metadata = fs::symlink_metadata(path)?;
+        let mtime = metadata.modified().ok().map(Self::to_naive);
+
+        if metadata.file_type().is_symlink() {
+            return Ok(Entry {
+                file_type: FileType::Symlink,
+                link_target: Some(fs::read_link(path)?),
+                permissions: None,
+                mtime,
+                size: None,
+            });
+        }
+
+        let (file_type, size) = if metadata.is_dir() {
+            (FileType::Directory, None)
+        } else {
+            (FileType::File, Some(metadata.len() as i64))
+        };
+
+        Ok(Entry {
+            file_type,
+            link_target: None,
+            permissions: Some(Self::mode(&metadata.permissions())),
+            mtime,
+            size,
+        })
+    }
+
+    /// Builds an Entry out of the metadata stored alongside a File in the database
+    pub fn from_file(file: &File) -> Entry {
+        Entry {
+            file_type: file.kind(),
+            link_target: file.link_target.as_deref().map(paths::decode),
+            permissions: file.permissions,
+            mtime: file.file_mtime,
+            size: file.file_size,
+        }
+    }
+
+    /// Whether the size and modification time of this entry match the ones recorded in `file`.
+    ///
+    /// Files whose metadata hasn't changed are assumed to have the same contents, which lets us skip hashing them.
+    /// Nothing is assumed about files which didn't have their metadata recorded yet.
+    pub fn matches_cached(&self, file: &File) -> bool {
+        self.file_type == FileType::File
+            && file.kind() == FileType::File
+            && self.size.is_some()
+            && self.mtime.is_some()
+            && self.size == file.file_size
+            && self.mtime == file.file_mtime
+    }
+
+    /// Creates a directory or symlink at `path` as described by this Entry.
+    ///
+    /// Regular files are left to the caller since their contents live in the backup.
+    pub fn create<P: AsRef<Path>>(&self, path: &P) -> io::Result<()> {
+        let path = path.as_ref();
+
+        match self.file_type {
+            FileType::File => Ok(()),
+            FileType::Directory => fs::create_dir_all(path),
+            FileType::Symlink => {
+                let target = self.link_target.as_ref().ok_or_else(|| {
+                    let msg = format!("No link target was recorded for {}", path.display());
+                    io::Error::new(io::ErrorKind::InvalidData, msg)
+                })?;
+
+                if fs::symlink_metadata(path).is_ok() {
+                    fs::remove_file(path)?;
+                }
+
+                Self::symlink(target, path)
+            }
+        }
+    }
+
+    /// Applies the recorded permissions and modification time to `path`
+    pub fn restore_metadata<P: AsRef<Path>>(&self, path: &P) -> io::Result<()> {
+        let path = path.as_ref();
+
+        if let Some(mode) = self.permissions {
+            if self.file_type != FileType::Symlink {
+                fs::set_permissions(path, Self::permissions(mode, path)?)?;
+            }
+        }
+
+        if let Some(mtime) = self.mtime {
+            let time = FileTime::from_system_time(Utc.from_utc_datetime(&mtime).into());
+
+            match self.file_type {
+                FileType::Symlink => filetime::set_symlink_file_times(path, time, time)?,
+                _ => filetime::set_file_mtime(path, time)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_naive(time: SystemTime) -> NaiveDateTime {
+        DateTime::<Utc>::from(time).naive_utc()
+    }
+
+    #[cfg(unix)]
+    fn mode(permissions: &fs::Permissions) -> i32 {
+        use std::os::unix::fs::PermissionsExt;
+        (permissions.mode() & 0o7777) as i32
+    }
+
+    #[cfg(not(unix))]
+    fn mode(permissions: &fs::Permissions) -> i32 {
+        // Read-only is the only permission other Operating Systems let us restore
+        if permissions.readonly() {
+            0o444
+        } else {
+            0o666
+        }
+    }
+
+    #[cfg(unix)]
+    fn permissions(mode: i32, _path: &Path) -> io::Result<fs::Permissions> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(fs::Permissions::from_mode(mode as u32))
+    }
+
+    #[cfg(not(unix))]
+    fn permissions(mode: i32, path: &Path) -> io::Result<fs::Permissions> {
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_readonly(mode & 0o222 == 0);
+        Ok(permissions)
+    }
+
+    #[cfg(unix)]
+    fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, path)
+    }
+
+    #[cfg(windows)]
+    fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+        if parent.join(target).is_dir() {
+            std::os::windows::fs::symlink_dir(target, path)
+        } else {
+            std::os::windows::fs::symlink_file(target, path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_file_and_directory() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let file_path: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        let dir_path: PathBuf = [tmp_dir, &PathBuf::from("screenshots")].iter().collect();
+        fs::write(&file_path, "save data").unwrap();
+        fs::create_dir(&dir_path).unwrap();
+
+        let file = Entry::read(&file_path).unwrap();
+        let dir = Entry::read(&dir_path).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(file.file_type, FileType::File);
+        assert_eq!(dir.file_type, FileType::Directory);
+        assert!(file.permissions.is_some());
+        assert!(file.mtime.is_some());
+        assert!(file.link_target.is_none());
+        assert_eq!(file.size, Some(9));
+        assert_eq!(dir.size, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn read_and_create_symlink() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let link_path: PathBuf = [tmp_dir, &PathBuf::from("current.sav")].iter().collect();
+        let copy_path: PathBuf = [tmp_dir, &PathBuf::from("copy.sav")].iter().collect();
+        std::os::unix::fs::symlink("slot1/00.sav", &link_path).unwrap();
+
+        // The target doesn't exist, which must not matter since symlinks aren't followed
+        let expected = Entry::read(&link_path).unwrap();
+        expected.create(&copy_path).unwrap();
+        let actual = fs::read_link(&copy_path).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(expected.file_type, FileType::Symlink);
+        assert_eq!(expected.link_target, Some(PathBuf::from("slot1/00.sav")));
+        assert_eq!(actual, PathBuf::from("slot1/00.sav"));
+    }
+
+    #[test]
+    fn matches_cached_metadata() {
+        let mtime = Utc.timestamp_opt(1_500_000_000, 0).unwrap().naive_utc();
+        let entry = Entry {
+            file_type: FileType::File,
+            link_target: None,
+            permissions: Some(0o644),
+            mtime: Some(mtime),
+            size: Some(9),
+        };
+
+        let file = File {
+            id: 1,
+            file_path: "00.sav".to_string(),
+            file_hash: vec![],
+            save_id: 1,
+            created_at: mtime,
+            modified_at: mtime,
+            file_type: FileType::File.into(),
+            link_target: None,
+            permissions: Some(0o644),
+            file_mtime: Some(mtime),
+            file_size: Some(9),
+        };
+
+        let resized = File {
+            file_size: Some(10),
+            ..file.clone()
+        };
+
+        let uncached = File {
+            file_size: None,
+            file_mtime: None,
+            ..file.clone()
+        };
+
+        assert!(entry.matches_cached(&file));
+        assert!(!entry.matches_cached(&resized));
+        assert!(!entry.matches_cached(&uncached));
+    }
+
+    #[test]
+    fn restore_metadata_roundtrip() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let file_path: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        fs::write(&file_path, "save data").unwrap();
+
+        let mtime = Utc.timestamp_opt(1_500_000_000, 0).unwrap().naive_utc();
+        let expected = Entry {
+            file_type: FileType::File,
+            link_target: None,
+            permissions: Some(0o444),
+            mtime: Some(mtime),
+            size: Some(9),
+        };
+
+        expected.restore_metadata(&file_path).unwrap();
+        let actual = Entry::read(&file_path).unwrap();
+
+        // Make sure TempDir is able to clean up after itself
+        let writable = Entry {
+            permissions: Some(0o644),
+            mtime: None,
+            ..expected.clone()
+        };
+        writable.restore_metadata(&file_path).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(actual, expected);
+    }
+}
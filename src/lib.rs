@@ -7,10 +7,28 @@ extern crate diesel_migrations;
 
 pub use archive::Archive;
 pub use config::ConfigManager;
+pub use context::SaveSync;
 pub use database::Database;
 
 pub mod archive;
+pub mod atomic;
+pub mod chunking;
 pub mod config;
+pub mod context;
 pub mod database;
+pub mod diff;
+pub mod doctor;
+pub mod entry;
+pub mod lock;
+pub mod ludusavi;
 pub mod models;
+pub mod paths;
+pub mod process;
+pub mod progress;
+pub mod schedule;
 mod schema;
+pub mod stats;
+pub mod storage;
+#[cfg(test)]
+mod test_utils;
+pub mod workers;
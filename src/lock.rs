@@ -0,0 +1,180 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// How often a lock which is held by another process is tried again
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+    #[error("{0} is locked by another save-sync process")]
+    Timeout(PathBuf),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockMode {
+    /// Any number of processes may hold a shared lock at the same time, but not alongside an exclusive one
+    Shared,
+    Exclusive,
+}
+
+/// An advisory lock on a lock file, which is released when it is dropped.
+///
+/// The lock is held by the operating system, so it is also released when the process dies.
+///
+/// # Examples
+/// ```
+/// use save_sync::lock::{Lock, LockError, LockMode};
+/// use std::time::Duration;
+/// # let dir = tempfile::TempDir::new().unwrap();
+/// # let path = dir.path().join("save.lock");
+///
+/// let lock = Lock::acquire(&path, LockMode::Exclusive, Duration::from_secs(0)).unwrap();
+/// let other = Lock::acquire(&path, LockMode::Shared, Duration::from_secs(0));
+///
+/// assert!(matches!(other, Err(LockError::Timeout(_))));
+/// ```
+#[derive(Debug)]
+pub struct Lock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Locks the file at `path`, creating it and its parents if they don't exist yet.
+    ///
+    /// Waits for up to `timeout` if another process holds a conflicting lock
+    pub fn acquire<P: AsRef<Path>>(
+        path: &P,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<Lock, LockError> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let result = match mode {
+                LockMode::Shared => FileExt::try_lock_shared(&file),
+                LockMode::Exclusive => FileExt::try_lock_exclusive(&file),
+            };
+
+            match result {
+                Ok(()) => break,
+                Err(err) if err.kind() != fs2::lock_contended_error().kind() => {
+                    return Err(err.into())
+                }
+                Err(_) if Instant::now() >= deadline => {
+                    return Err(LockError::Timeout(path.to_path_buf()))
+                }
+                Err(_) => thread::sleep(RETRY_INTERVAL),
+            }
+        }
+
+        Ok(Lock {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock as well, this only makes it explicit
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Keeps other processes away from a single save, while still allowing them to work on other saves
+///
+/// Holds the global lock as shared, so that operations on every save at once have to wait for it
+#[derive(Debug)]
+pub struct SaveLock {
+    _global: Lock,
+    _save: Lock,
+}
+
+impl SaveLock {
+    pub(crate) fn new(global: Lock, save: Lock) -> SaveLock {
+        SaveLock {
+            _global: global,
+            _save: save,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NO_WAIT: Duration = Duration::from_secs(0);
+
+    #[test]
+    fn shared_locks_coexist() {
+        let test_dir = TempDir::new().unwrap();
+        let path = test_dir.path().join("locks").join("save.lock");
+
+        let first = Lock::acquire(&path, LockMode::Shared, NO_WAIT).unwrap();
+        let second = Lock::acquire(&path, LockMode::Shared, NO_WAIT).unwrap();
+        let exclusive = Lock::acquire(&path, LockMode::Exclusive, NO_WAIT);
+
+        drop((first, second));
+        test_dir.close().unwrap();
+
+        assert!(matches!(exclusive, Err(LockError::Timeout(_))));
+    }
+
+    #[test]
+    fn dropping_releases_the_lock() {
+        let test_dir = TempDir::new().unwrap();
+        let path = test_dir.path().join("save.lock");
+
+        let lock = Lock::acquire(&path, LockMode::Exclusive, NO_WAIT).unwrap();
+        let blocked = Lock::acquire(&path, LockMode::Exclusive, NO_WAIT);
+        drop(lock);
+        let released = Lock::acquire(&path, LockMode::Exclusive, NO_WAIT);
+
+        test_dir.close().unwrap();
+        assert!(matches!(blocked, Err(LockError::Timeout(_))));
+        assert!(released.is_ok());
+    }
+
+    #[test]
+    fn waits_for_the_lock() {
+        let test_dir = TempDir::new().unwrap();
+        let path = test_dir.path().join("save.lock");
+
+        let lock = Lock::acquire(&path, LockMode::Exclusive, NO_WAIT).unwrap();
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+
+        let waited = Lock::acquire(&path, LockMode::Exclusive, Duration::from_secs(10));
+        releaser.join().unwrap();
+
+        test_dir.close().unwrap();
+        assert!(waited.is_ok());
+    }
+}
@@ -0,0 +1,431 @@
+//! Reads backups which were made by [Ludusavi](https://github.com/mtkennerly/ludusavi).
+//!
+//! Ludusavi keeps one folder per game in its backup directory. Every folder has a `mapping.yaml`, which lists
+//! the full backups of the game along with their differential backups, and the backed up files themselves,
+//! stored below one `drive-*` folder per drive they came from.
+use chrono::{DateTime, NaiveDateTime};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The name of the file which describes the backups of a game
+pub const MAPPING_FILE: &str = "mapping.yaml";
+
+/// A full backup with this name is stored in the game's folder itself
+const INLINE_BACKUP: &str = ".";
+
+#[derive(Error, Debug)]
+pub enum LudusaviError {
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Mapping(PathBuf, serde_yaml::Error),
+    #[error("{0} does not contain any backups")]
+    NoBackups(PathBuf),
+    #[error("{0} is a zip backup, only plain folder backups can be imported")]
+    ZipBackup(PathBuf),
+    #[error("Unable to determine which drive {0} was backed up from")]
+    UnknownDrive(String),
+    #[error("{0} is listed in the mapping but missing from the backup")]
+    MissingFile(PathBuf),
+}
+
+#[derive(Deserialize, Debug)]
+struct Mapping {
+    name: String,
+    #[serde(default)]
+    drives: BTreeMap<String, String>,
+    #[serde(default)]
+    backups: Vec<FullBackup>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FullBackup {
+    name: String,
+    when: Option<String>,
+    #[serde(default)]
+    files: BTreeMap<String, IgnoredAny>,
+    #[serde(default)]
+    children: Vec<DifferentialBackup>,
+}
+
+/// Only lists the files which changed since the full backup. Files which were removed map to nothing
+#[derive(Deserialize, Debug)]
+struct DifferentialBackup {
+    name: String,
+    when: Option<String>,
+    #[serde(default)]
+    files: BTreeMap<String, Option<IgnoredAny>>,
+}
+
+/// The latest state of a game, as Ludusavi backed it up
+///
+/// # Properties
+/// * `name` - The name of the game
+/// * `dir` - The folder Ludusavi keeps the backups of the game in
+/// * `files` - Every file as of the latest backup
+/// * `backups` - Every backup of the game, oldest first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    pub name: String,
+    pub dir: PathBuf,
+    pub files: Vec<GameFile>,
+    pub backups: Vec<Backup>,
+}
+
+/// # Properties
+/// * `path` - Where the file was when it was backed up
+/// * `source` - Where Ludusavi stored the file
+/// * `backed_up_at` - When the backup the file was read from was made. `None` for backups from Ludusavi
+///   versions which didn't record this
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameFile {
+    pub path: PathBuf,
+    pub source: PathBuf,
+    pub backed_up_at: Option<NaiveDateTime>,
+}
+
+/// # Properties
+/// * `when` - When the backup was made, if Ludusavi recorded it
+/// * `files` - Where the files the backup contains were. Differential backups only contain files which changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    pub when: Option<NaiveDateTime>,
+    pub files: Vec<PathBuf>,
+}
+
+impl Game {
+    /// Reads the `mapping.yaml` in `dir` and resolves the files of the latest backup it lists
+    pub fn read<P: AsRef<Path>>(dir: &P) -> Result<Game, LudusaviError> {
+        let dir = dir.as_ref();
+        let mapping_path = dir.join(MAPPING_FILE);
+        let text = fs::read_to_string(&mapping_path)?;
+        let mapping: Mapping = serde_yaml::from_str(&text)
+            .map_err(|err| LudusaviError::Mapping(mapping_path.clone(), err))?;
+
+        let full = mapping
+            .backups
+            .last()
+            .ok_or_else(|| LudusaviError::NoBackups(dir.to_path_buf()))?;
+
+        let mut files: BTreeMap<&str, (&str, Option<NaiveDateTime>)> = full
+            .files
+            .keys()
+            .map(|path| (path.as_str(), (full.name.as_str(), parse_time(&full.when))))
+            .collect();
+
+        // Only the newest differential backup matters, since each one is relative to the full backup
+        if let Some(child) = full.children.last() {
+            let when = parse_time(&child.when);
+
+            for (path, file) in &child.files {
+                match file {
+                    Some(_) => files.insert(path, (&child.name, when)),
+                    None => files.remove(path.as_str()),
+                };
+            }
+        }
+
+        let files = files
+            .into_iter()
+            .map(|(path, (backup, backed_up_at))| {
+                let source = stored_path(dir, backup, &mapping.drives, path)?;
+
+                if !source.is_file() {
+                    return Err(LudusaviError::MissingFile(source));
+                }
+
+                Ok(GameFile {
+                    path: PathBuf::from(path),
+                    source,
+                    backed_up_at,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let backups = mapping
+            .backups
+            .iter()
+            .flat_map(|full| {
+                let children = full.children.iter().map(|child| Backup {
+                    when: parse_time(&child.when),
+                    files: child.files.keys().map(PathBuf::from).collect(),
+                });
+
+                std::iter::once(Backup {
+                    when: parse_time(&full.when),
+                    files: full.files.keys().map(PathBuf::from).collect(),
+                })
+                .chain(children)
+            })
+            .collect();
+
+        Ok(Game {
+            name: mapping.name,
+            dir: dir.to_path_buf(),
+            files,
+            backups,
+        })
+    }
+
+    /// The directories the files of the game are in. Directories which are inside another one are left out,
+    /// so every file is below exactly one of them
+    pub fn roots(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<&Path> = self
+            .files
+            .iter()
+            .filter_map(|file| file.path.parent())
+            .collect();
+
+        // Parents sort before their children, so each directory only has to be checked against the kept ones
+        dirs.sort();
+        dirs.dedup();
+
+        let mut roots: Vec<PathBuf> = vec![];
+
+        for dir in dirs {
+            if !roots.iter().any(|root| dir.starts_with(root)) {
+                roots.push(dir.to_path_buf());
+            }
+        }
+
+        roots
+    }
+}
+
+/// Lists the folders below `dir` which contain the backups of a game. If `dir` itself is the folder of a single
+/// game, only `dir` is returned
+pub fn find_games<P: AsRef<Path>>(dir: &P) -> Result<Vec<PathBuf>, LudusaviError> {
+    let dir = dir.as_ref();
+
+    if dir.join(MAPPING_FILE).is_file() {
+        return Ok(vec![dir.to_path_buf()]);
+    }
+
+    let mut games = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.join(MAPPING_FILE).is_file() {
+            games.push(path);
+        }
+    }
+
+    games.sort();
+    Ok(games)
+}
+
+/// Where Ludusavi stored the file which was at `path` in the backup called `backup`
+fn stored_path(
+    dir: &Path,
+    backup: &str,
+    drives: &BTreeMap<String, String>,
+    path: &str,
+) -> Result<PathBuf, LudusaviError> {
+    if backup.ends_with(".zip") {
+        return Err(LudusaviError::ZipBackup(dir.join(backup)));
+    }
+
+    // The longest matching drive wins, so that e.g. a network share isn't mistaken for the root
+    let (folder, rest) = drives
+        .iter()
+        .filter_map(|(folder, drive)| {
+            let rest = path.strip_prefix(drive.as_str())?;
+
+            match rest.strip_prefix('/') {
+                Some(rest) => Some((folder, drive.len(), rest)),
+                None if rest.is_empty() => Some((folder, drive.len(), rest)),
+                None => None,
+            }
+        })
+        .max_by_key(|(_, len, _)| *len)
+        .map(|(folder, _, rest)| (folder, rest))
+        .ok_or_else(|| LudusaviError::UnknownDrive(path.to_string()))?;
+
+    let backup_dir = match backup {
+        INLINE_BACKUP => dir.to_path_buf(),
+        name => dir.join(name),
+    };
+
+    Ok(backup_dir.join(folder).join(rest))
+}
+
+/// Ludusavi records times as RFC 3339, times which can't be parsed are treated as unknown
+fn parse_time(when: &Option<String>) -> Option<NaiveDateTime> {
+    let when = when.as_deref()?;
+    DateTime::parse_from_rfc3339(when)
+        .ok()
+        .map(|time| time.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MAPPING: &str = r#"
+name: Celeste
+drives:
+  drive-0: ""
+  drive-C: "C:"
+backups:
+  - name: backup-20200101T120000Z
+    when: "2020-01-01T12:00:00Z"
+    files:
+      "/home/user/.local/share/Celeste/Saves/0.celeste":
+        hash: 6c0b
+        size: 4
+    children: []
+  - name: "."
+    when: "2020-02-01T12:00:00Z"
+    files:
+      "/home/user/.local/share/Celeste/Saves/0.celeste":
+        hash: 6c0b
+        size: 4
+      "/home/user/.local/share/Celeste/Saves/settings.celeste":
+        hash: 1a2b
+        size: 8
+      "C:/Celeste/Saves/1.celeste":
+        hash: 9f8e
+        size: 2
+    children:
+      - name: backup-20200301T120000Z-diff
+        when: "2020-03-01T12:00:00Z"
+        files:
+          "/home/user/.local/share/Celeste/Saves/settings.celeste": ~
+      - name: backup-20200401T120000Z-diff
+        when: "2020-04-01T12:00:00Z"
+        files:
+          "/home/user/.local/share/Celeste/Saves/0.celeste":
+            hash: 7d1c
+            size: 6
+          "/home/user/.local/share/Celeste/Saves/settings.celeste": ~
+"#;
+
+    fn write(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"save").unwrap();
+    }
+
+    fn time(month: u32) -> Option<NaiveDateTime> {
+        parse_time(&Some(format!("2020-{:02}-01T12:00:00Z", month)))
+    }
+
+    #[test]
+    fn read_latest_backup() {
+        let test_dir = TempDir::new().unwrap();
+        let game_dir = test_dir.path().join("Celeste");
+        let diff_dir = game_dir.join("backup-20200401T120000Z-diff");
+
+        fs::create_dir(&game_dir).unwrap();
+        fs::write(game_dir.join(MAPPING_FILE), MAPPING).unwrap();
+        write(&game_dir.join("drive-0/home/user/.local/share/Celeste/Saves/0.celeste"));
+        write(&game_dir.join("drive-C/Celeste/Saves/1.celeste"));
+        write(&diff_dir.join("drive-0/home/user/.local/share/Celeste/Saves/0.celeste"));
+
+        let games = find_games(&test_dir.path()).unwrap();
+        let game = Game::read(&game_dir).unwrap();
+
+        test_dir.close().unwrap();
+
+        assert_eq!(games, vec![game_dir.clone()]);
+        assert_eq!(game.name, "Celeste");
+
+        let expected = vec![
+            GameFile {
+                path: PathBuf::from("/home/user/.local/share/Celeste/Saves/0.celeste"),
+                source: diff_dir.join("drive-0/home/user/.local/share/Celeste/Saves/0.celeste"),
+                backed_up_at: time(4),
+            },
+            GameFile {
+                path: PathBuf::from("C:/Celeste/Saves/1.celeste"),
+                source: game_dir.join("drive-C/Celeste/Saves/1.celeste"),
+                backed_up_at: time(2),
+            },
+        ];
+        assert_eq!(game.files, expected);
+
+        let when: Vec<_> = game.backups.iter().map(|backup| backup.when).collect();
+        assert_eq!(when, vec![time(1), time(2), time(3), time(4)]);
+        assert_eq!(game.backups[1].files.len(), 3);
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let test_dir = TempDir::new().unwrap();
+        let game_dir = test_dir.path();
+
+        fs::write(game_dir.join(MAPPING_FILE), MAPPING).unwrap();
+
+        let result = Game::read(&game_dir);
+        test_dir.close().unwrap();
+
+        assert!(matches!(result, Err(LudusaviError::MissingFile(_))));
+    }
+
+    #[test]
+    fn zip_backups_are_unsupported() {
+        let drives: BTreeMap<_, _> = vec![("drive-0".to_string(), "".to_string())]
+            .into_iter()
+            .collect();
+
+        let result = stored_path(
+            Path::new("/backups/Celeste"),
+            "backup-20200101T120000Z.zip",
+            &drives,
+            "/home/user/0.celeste",
+        );
+
+        assert!(matches!(result, Err(LudusaviError::ZipBackup(_))));
+    }
+
+    #[test]
+    fn longest_drive_wins() {
+        let drives: BTreeMap<_, _> = vec![
+            ("drive-0".to_string(), "".to_string()),
+            ("drive-1".to_string(), "/mnt".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let dir = Path::new("/backups/Game");
+
+        let root = stored_path(dir, ".", &drives, "/mnt2/save.dat").unwrap();
+        let mount = stored_path(dir, ".", &drives, "/mnt/save.dat").unwrap();
+        let unknown = stored_path(dir, ".", &drives, "D:/save.dat");
+
+        assert_eq!(root, dir.join("drive-0/mnt2/save.dat"));
+        assert_eq!(mount, dir.join("drive-1/save.dat"));
+        assert!(matches!(unknown, Err(LudusaviError::UnknownDrive(_))));
+    }
+
+    #[test]
+    fn roots_leave_out_nested_directories() {
+        let file = |path: &str| GameFile {
+            path: PathBuf::from(path),
+            source: PathBuf::new(),
+            backed_up_at: None,
+        };
+        let game = Game {
+            name: "Game".to_string(),
+            dir: PathBuf::new(),
+            files: vec![
+                file("/home/user/.config/game/settings.ini"),
+                file("/home/user/.local/share/game/saves/1.sav"),
+                file("/home/user/.local/share/game/2.sav"),
+                file("/home/user/.config/game/keys/bindings.ini"),
+            ],
+            backups: vec![],
+        };
+
+        let expected = vec![
+            PathBuf::from("/home/user/.config/game"),
+            PathBuf::from("/home/user/.local/share/game"),
+        ];
+        assert_eq!(game.roots(), expected);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::schema::{files, saves, users};
+use crate::paths;
+use crate::schema::{chunks, executables, files, notes, saves, settings, tags, updates, users};
 use chrono::naive::NaiveDateTime;
 
 /// Represents a Save in the Database
@@ -6,8 +7,8 @@ use chrono::naive::NaiveDateTime;
 /// # Properties
 /// * `id` - The ID of the Save in the Database
 /// * `friendly_name` - A Convenient name of the save which will be useful when manually querying the database as a user
-/// * `save_path` - A UTF-8 String which represents the root of the **original** save files
-/// * `backup_path` - A UTF-8 String which represents the root of the **local** backup of save files
+/// * `save_path` - The root of the **original** save files, encoded with `paths::encode`
+/// * `backup_path` - The root of the **local** backup of save files, encoded with `paths::encode`
 /// * `uuid` - The UUID associated with this Save
 /// * `created_at` - A timestamp which represents when this save was created in the database
 /// * `modified_at` - A timestamp which represents when this save was last edited in the database
@@ -26,11 +27,7 @@ pub struct Save {
 // Allows for a comparison between a Path and a Save using the `==` operator
 impl PartialEq<std::path::Path> for Save {
     fn eq(&self, other: &std::path::Path) -> bool {
-        if let Some(path) = other.to_str() {
-            self.save_path == path
-        } else {
-            false
-        }
+        self.save_path == paths::encode(&other)
     }
 }
 
@@ -38,8 +35,8 @@ impl PartialEq<std::path::Path> for Save {
 /// Note: With the exception of `created_at` and `modified_at` every property in this struct contains borrowed data.
 /// # Properties
 /// * `friendly_name` - A Convenient name of the save which will be useful when manually querying the database as a user
-/// * `save_path` - A UTF-8 String which represents the root of the **original** save files
-/// * `backup_path` - A UTF-8 String which represents the root of the **local** backup of save files
+/// * `save_path` - The root of the **original** save files, encoded with `paths::encode`
+/// * `backup_path` - The root of the **local** backup of save files, encoded with `paths::encode`
 /// * `uuid` - The UUID associated with this Save
 /// * `created_at` - A timestamp which represents when this save was created in the database
 /// * `modified_at` - A timestamp which represents when this save was last edited in the database
@@ -60,7 +57,8 @@ pub struct NewSave<'a> {
 /// # Properties
 /// * `id` - The ID of the Save in the Database
 /// * `friendly_name` - A Convenient name of the save which will be useful when manually querying the database as a user
-/// * `save_path` - A UTF-8 String which represents the root of the **original** save files
+/// * `save_path` - The root of the **original** save files, encoded with `paths::encode`
+/// * `user_id` - The ID of the User who owns this save
 /// * `modified_at` - A timestamp which represents when this save was last edited in the database
 #[derive(Clone, Copy, Debug, AsChangeset)]
 #[table_name = "saves"]
@@ -68,6 +66,7 @@ pub struct EditSave<'a> {
     pub id: i32,
     pub friendly_name: Option<&'a str>,
     pub save_path: Option<&'a str>,
+    pub user_id: Option<i32>,
     pub modified_at: NaiveDateTime,
 }
 
@@ -84,14 +83,44 @@ impl PartialEq<NewSave<'_>> for Save {
     }
 }
 
+/// Represents the kind of entry on disk a File was created from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FileType {
+    File = 0,
+    Directory = 1,
+    Symlink = 2,
+}
+
+impl FileType {
+    pub fn from_i32(num: i32) -> Option<FileType> {
+        match num {
+            0 => Some(FileType::File),
+            1 => Some(FileType::Directory),
+            2 => Some(FileType::Symlink),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileType> for i32 {
+    fn from(file_type: FileType) -> i32 {
+        file_type as i32
+    }
+}
+
 /// Represents a File in the Databse
 /// # Properties
 /// * `id` - The ID of the File in the Database
-/// * `file_path` - A UTF-8 String that represents the **original** location of the file
+/// * `file_path` - The **original** location of the file, encoded with `paths::encode`
 /// * `file_hash` - A u64 (calculated using xx_hash) which has been turned into a little endian byte array
 /// * `save_id` - The ID of which this File belongs to
 /// * `created_at` - A timestamp that represents when this File was created in the database
 /// * `modified_at` - A timestamp that represents when this File as last modified in the database.
+/// * `file_type` - Whether this File is a regular file, a directory or a symlink (see `FileType`)
+/// * `link_target` - The path a symlink points to, encoded with `paths::encode`. `None` for files and directories
+/// * `permissions` - The permission bits of the file at the time of the last backup
+/// * `file_mtime` - The time the file was last modified on disk at the time of the last backup
+/// * `file_size` - The size of the file in bytes at the time of the last backup
 #[derive(Clone, Debug, Eq, PartialEq, Queryable, Insertable)]
 pub struct File {
     pub id: i32,
@@ -100,27 +129,40 @@ pub struct File {
     pub save_id: i32,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub file_type: i32,
+    pub link_target: Option<String>,
+    pub permissions: Option<i32>,
+    pub file_mtime: Option<NaiveDateTime>,
+    pub file_size: Option<i64>,
+}
+
+impl File {
+    /// Unknown values are treated as regular files
+    pub fn kind(&self) -> FileType {
+        FileType::from_i32(self.file_type).unwrap_or(FileType::File)
+    }
 }
 
 // Allows for a comparison between a Path and a File using the `==` operator
 impl PartialEq<std::path::Path> for File {
     fn eq(&self, other: &std::path::Path) -> bool {
-        if let Some(path) = other.to_str() {
-            self.file_path == path
-        } else {
-            false
-        }
+        self.file_path == paths::encode(&other)
     }
 }
 
 /// Represents a (to-be) newly created File
 /// Note: With the exception of `created_at` and `modified_at`, all properties in this struct contain borrowed data.
 /// # Properties
-/// * `file_path` - A UTF-8 String that represents the **original** location of the file
+/// * `file_path` - The **original** location of the file, encoded with `paths::encode`
 /// * `file_hash` - A u64 (calculated using xx_hash) which has been turned into a little endian byte array
 /// * `save_id` - The ID of which this File belongs to
 /// * `created_at` - A timestamp that represents when this File was created in the database
 /// * `modified_at` - A timestamp that represents when this File as last modified in the database.
+/// * `file_type` - Whether this File is a regular file, a directory or a symlink (see `FileType`)
+/// * `link_target` - The path a symlink points to, encoded with `paths::encode`. `None` for files and directories
+/// * `permissions` - The permission bits of the file on disk
+/// * `file_mtime` - The time the file was last modified on disk
+/// * `file_size` - The size of the file in bytes
 #[derive(Clone, Copy, Debug, Insertable)]
 #[table_name = "files"]
 pub struct NewFile<'a> {
@@ -129,19 +171,34 @@ pub struct NewFile<'a> {
     pub save_id: i32,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub file_type: i32,
+    pub link_target: Option<&'a str>,
+    pub permissions: Option<i32>,
+    pub file_mtime: Option<NaiveDateTime>,
+    pub file_size: Option<i64>,
 }
 
 /// Represents a ChangeList of a File
-/// # Note: With the exception of `modified_at`
+/// # Note: With the exception of `modified_at`, properties which are `None` are left untouched
 /// * `id` - The ID of the File in the Database
 /// * `file_hash` - A u64 (calculated using xx_hash) which has been turned into a little endian byte array
 /// * `modified_at` - A timestamp that represents when this File was last modified in the database.
+/// * `file_type` - Whether this File is a regular file, a directory or a symlink (see `FileType`)
+/// * `link_target` - The path a symlink points to, encoded with `paths::encode`
+/// * `permissions` - The permission bits of the file on disk
+/// * `file_mtime` - The time the file was last modified on disk
+/// * `file_size` - The size of the file in bytes
 #[derive(Clone, Copy, Debug, AsChangeset)]
 #[table_name = "files"]
 pub struct EditFile<'a> {
     pub id: i32,
     pub file_hash: &'a [u8],
     pub modified_at: NaiveDateTime,
+    pub file_type: Option<i32>,
+    pub link_target: Option<&'a str>,
+    pub permissions: Option<i32>,
+    pub file_mtime: Option<NaiveDateTime>,
+    pub file_size: Option<i64>,
 }
 
 // Allows for a comparison between a NewFile and an existing file using the `==` operator
@@ -152,6 +209,11 @@ impl PartialEq<NewFile<'_>> for File {
             && self.save_id == other.save_id
             && self.created_at == other.created_at
             && self.modified_at == other.modified_at
+            && self.file_type == other.file_type
+            && self.link_target.as_deref() == other.link_target
+            && self.permissions == other.permissions
+            && self.file_mtime == other.file_mtime
+            && self.file_size == other.file_size
     }
 }
 
@@ -205,3 +267,234 @@ impl PartialEq<NewUser<'_>> for User {
             && self.modified_at == other.modified_at
     }
 }
+
+/// Represents a piece of a large File which is stored in the chunk store
+/// # Properties
+/// * `id` - The ID of the Chunk in the database
+/// * `file_id` - The ID of the File which this Chunk is a part of
+/// * `position` - Where this Chunk lies in the File, starting at 0
+/// * `chunk_hash` - A u64 (calculated using xx_hash) which has been turned into a little endian byte array
+/// * `chunk_size` - The size of the Chunk in bytes
+/// * `created_at` - A timestamp that represents when this Chunk was created in the database
+/// * `modified_at` - A timestamp that represents when this Chunk was last modified in the database
+#[derive(Clone, Debug, Eq, PartialEq, Queryable, Insertable)]
+pub struct Chunk {
+    pub id: i32,
+    pub file_id: i32,
+    pub position: i32,
+    pub chunk_hash: Vec<u8>,
+    pub chunk_size: i64,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly created Chunk
+/// Note: chunk_hash is a property that contains borrowed data
+/// # Properties
+/// * `file_id` - The ID of the File which this Chunk is a part of
+/// * `position` - Where this Chunk lies in the File, starting at 0
+/// * `chunk_hash` - A u64 (calculated using xx_hash) which has been turned into a little endian byte array
+/// * `chunk_size` - The size of the Chunk in bytes
+/// * `created_at` - A timestamp that represents when this Chunk was created in the database
+/// * `modified_at` - A timestamp that represents when this Chunk was last modified in the database
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "chunks"]
+pub struct NewChunk<'a> {
+    pub file_id: i32,
+    pub position: i32,
+    pub chunk_hash: &'a [u8],
+    pub chunk_size: i64,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+// Allows for a comparison between a NewChunk and an existing Chunk using the `==` operator
+impl PartialEq<NewChunk<'_>> for Chunk {
+    fn eq(&self, other: &NewChunk) -> bool {
+        self.file_id == other.file_id
+            && self.position == other.position
+            && self.chunk_hash == other.chunk_hash
+            && self.chunk_size == other.chunk_size
+            && self.created_at == other.created_at
+            && self.modified_at == other.modified_at
+    }
+}
+
+/// Represents an Executable which is associated with a Save
+/// # Properties
+/// * `id` - The ID of the Executable in the database
+/// * `name` - The file name of the executable (e.g. `game.exe`) which writes to the Save
+/// * `save_id` - The ID of the Save which this Executable belongs to
+/// * `created_at` - A timestamp that represents when this Executable was created in the database
+/// * `modified_at` - A timestamp that represents when this Executable was last modified in the database
+#[derive(Clone, Debug, Eq, PartialEq, Queryable, Insertable)]
+pub struct Executable {
+    pub id: i32,
+    pub name: String,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly created Executable
+/// Note: name is a property that contains borrowed data
+/// # Properties
+/// * `name` - The file name of the executable (e.g. `game.exe`) which writes to the Save
+/// * `save_id` - The ID of the Save which this Executable belongs to
+/// * `created_at` - A timestamp that represents when this Executable was created in the database
+/// * `modified_at` - A timestamp that represents when this Executable was last modified in the database
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "executables"]
+pub struct NewExecutable<'a> {
+    pub name: &'a str,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+// Allows for a comparison between a NewExecutable and an existing Executable using the `==` operator
+impl PartialEq<NewExecutable<'_>> for Executable {
+    fn eq(&self, other: &NewExecutable) -> bool {
+        self.name == other.name
+            && self.save_id == other.save_id
+            && self.created_at == other.created_at
+            && self.modified_at == other.modified_at
+    }
+}
+
+/// Represents a Tag which has been attached to a Save
+/// # Properties
+/// * `id` - The ID of the Tag in the database
+/// * `name` - The Tag itself (e.g. `speedrun` or `100%`)
+/// * `save_id` - The ID of the Save which this Tag belongs to
+/// * `created_at` - A timestamp that represents when this Tag was created in the database
+/// * `modified_at` - A timestamp that represents when this Tag was last modified in the database
+#[derive(Clone, Debug, Eq, PartialEq, Queryable, Insertable)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly created Tag
+/// Note: name is a property that contains borrowed data
+/// # Properties
+/// * `name` - The Tag itself (e.g. `speedrun` or `100%`)
+/// * `save_id` - The ID of the Save which this Tag belongs to
+/// * `created_at` - A timestamp that represents when this Tag was created in the database
+/// * `modified_at` - A timestamp that represents when this Tag was last modified in the database
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "tags"]
+pub struct NewTag<'a> {
+    pub name: &'a str,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+// Allows for a comparison between a NewTag and an existing Tag using the `==` operator
+impl PartialEq<NewTag<'_>> for Tag {
+    fn eq(&self, other: &NewTag) -> bool {
+        self.name == other.name
+            && self.save_id == other.save_id
+            && self.created_at == other.created_at
+            && self.modified_at == other.modified_at
+    }
+}
+
+/// Represents a free-form Note which has been written about a Save
+/// # Properties
+/// * `id` - The ID of the Note in the database
+/// * `body` - The contents of the Note
+/// * `save_id` - The ID of the Save which this Note belongs to
+/// * `created_at` - A timestamp that represents when this Note was created in the database
+/// * `modified_at` - A timestamp that represents when this Note was last modified in the database
+#[derive(Clone, Debug, Eq, PartialEq, Queryable, Insertable)]
+pub struct Note {
+    pub id: i32,
+    pub body: String,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly created Note
+/// Note: body is a property that contains borrowed data
+/// # Properties
+/// * `body` - The contents of the Note
+/// * `save_id` - The ID of the Save which this Note belongs to
+/// * `created_at` - A timestamp that represents when this Note was created in the database
+/// * `modified_at` - A timestamp that represents when this Note was last modified in the database
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "notes"]
+pub struct NewNote<'a> {
+    pub body: &'a str,
+    pub save_id: i32,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+// Allows for a comparison between a NewNote and an existing Note using the `==` operator
+impl PartialEq<NewNote<'_>> for Note {
+    fn eq(&self, other: &NewNote) -> bool {
+        self.body == other.body
+            && self.save_id == other.save_id
+            && self.created_at == other.created_at
+            && self.modified_at == other.modified_at
+    }
+}
+
+/// Represents a value which belongs to the database as a whole rather than to a Save
+/// # Properties
+/// * `name` - The name of the Setting, which is unique
+/// * `value` - The value of the Setting
+/// * `modified_at` - A timestamp that represents when this Setting was last modified in the database
+#[derive(Clone, Debug, Eq, PartialEq, Queryable)]
+pub struct Setting {
+    pub name: String,
+    pub value: String,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly created or replaced Setting
+/// Note: name and value are properties that contain borrowed data
+/// # Properties
+/// * `name` - The name of the Setting, which is unique
+/// * `value` - The value of the Setting
+/// * `modified_at` - A timestamp that represents when this Setting was last modified in the database
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "settings"]
+pub struct NewSetting<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Represents a single time a Save was updated because some of its files had changed
+/// # Properties
+/// * `id` - The ID of the Update in the database
+/// * `save_id` - The ID of the Save which was updated
+/// * `files_changed` - How many files were new, changed or missing
+/// * `created_at` - A timestamp that represents when the Save was updated
+#[derive(Clone, Debug, Eq, PartialEq, Queryable)]
+pub struct Update {
+    pub id: i32,
+    pub save_id: i32,
+    pub files_changed: i32,
+    pub created_at: NaiveDateTime,
+}
+
+/// Represents a (to-be) newly recorded Update
+/// # Properties
+/// * `save_id` - The ID of the Save which was updated
+/// * `files_changed` - How many files were new, changed or missing
+/// * `created_at` - A timestamp that represents when the Save was updated
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "updates"]
+pub struct NewUpdate {
+    pub save_id: i32,
+    pub files_changed: i32,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,213 @@
+//! Lossless conversion between paths and the text they are stored as in the database.
+//!
+//! Paths which are valid UTF-8 are stored as they are, so that existing databases and LIKE patterns keep working.
+//! Everything else the OS accepts (like the bytes of a Latin-1 file name on Linux, or an unpaired surrogate on
+//! Windows) is escaped behind U+FFFF, a noncharacter which is never found in real file names:
+//!
+//! * `U+FFFF` followed by two hex digits is a byte which isn't part of valid UTF-8 (Unix)
+//! * `U+FFFF u` followed by four hex digits is an unpaired UTF-16 surrogate (Windows)
+//! * `U+FFFF U+FFFF` is a `U+FFFF` which was part of the path
+//!
+//! # Examples
+//! ```
+//! use save_sync::paths;
+//! use std::path::Path;
+//!
+//! let path = Path::new("/home/user/.wine/drive_c/Saves/slot1.sav");
+//! let text = paths::encode(&path);
+//!
+//! assert_eq!(text, "/home/user/.wine/drive_c/Saves/slot1.sav");
+//! assert_eq!(paths::decode(&text), path);
+//! ```
+use std::path::{Path, PathBuf};
+
+const ESCAPE: char = '\u{FFFF}';
+
+/// Turns `path` into text which `decode` turns back into exactly the same path
+pub fn encode<P: AsRef<Path>>(path: &P) -> String {
+    imp::encode(path.as_ref())
+}
+
+/// The inverse of `encode`. Escapes which `encode` can't have produced are kept as they are
+pub fn decode(text: &str) -> PathBuf {
+    imp::decode(text)
+}
+
+fn push_escaped(text: &mut String, c: char) {
+    if c == ESCAPE {
+        text.push(ESCAPE);
+    }
+
+    text.push(c);
+}
+
+/// The value of `digits` hex digits at the start of `chars`, if that's what's there
+fn hex_digits(chars: &std::str::Chars, digits: usize) -> Option<u32> {
+    let hex: String = chars.clone().take(digits).collect();
+
+    match hex.len() == digits && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => u32::from_str_radix(&hex, 16).ok(),
+        false => None,
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{hex_digits, push_escaped, ESCAPE};
+    use std::ffi::OsString;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+
+    pub fn encode(path: &Path) -> String {
+        let bytes = path.as_os_str().as_bytes();
+        let mut text = String::with_capacity(bytes.len());
+
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                push_escaped(&mut text, c);
+            }
+
+            for byte in chunk.invalid() {
+                text.push(ESCAPE);
+                text.push_str(&format!("{:02x}", byte));
+            }
+        }
+
+        text
+    }
+
+    pub fn decode(text: &str) -> PathBuf {
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut chars = text.chars();
+        let mut buf = [0; 4];
+
+        while let Some(c) = chars.next() {
+            if c == ESCAPE {
+                if chars.clone().next() == Some(ESCAPE) {
+                    chars.next();
+                } else if let Some(byte) = hex_digits(&chars, 2) {
+                    bytes.push(byte as u8);
+                    chars.nth(1);
+                    continue;
+                }
+            }
+
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+
+        PathBuf::from(OsString::from_vec(bytes))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::{hex_digits, push_escaped, ESCAPE};
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+
+    pub fn encode(path: &Path) -> String {
+        let mut text = String::new();
+
+        for c in std::char::decode_utf16(path.as_os_str().encode_wide()) {
+            match c {
+                Ok(c) => push_escaped(&mut text, c),
+                Err(err) => {
+                    text.push(ESCAPE);
+                    text.push_str(&format!("u{:04x}", err.unpaired_surrogate()));
+                }
+            }
+        }
+
+        text
+    }
+
+    pub fn decode(text: &str) -> PathBuf {
+        let mut wide = Vec::with_capacity(text.len());
+        let mut chars = text.chars();
+        let mut buf = [0; 2];
+
+        while let Some(c) = chars.next() {
+            if c == ESCAPE {
+                let mut after_u = chars.clone();
+
+                if chars.clone().next() == Some(ESCAPE) {
+                    chars.next();
+                } else if after_u.next() == Some('u') {
+                    if let Some(unit) = hex_digits(&after_u, 4) {
+                        wide.push(unit as u16);
+                        chars.nth(4);
+                        continue;
+                    }
+                }
+            }
+
+            wide.extend_from_slice(c.encode_utf16(&mut buf));
+        }
+
+        PathBuf::from(OsString::from_wide(&wide))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_paths_are_unchanged() {
+        let path = Path::new("/home/user/Saves/スロット 1/00.sav");
+
+        assert_eq!(encode(&path), "/home/user/Saves/スロット 1/00.sav");
+        assert_eq!(decode(&encode(&path)), path);
+    }
+
+    #[test]
+    fn escape_character_round_trips() {
+        let path = PathBuf::from(format!("/saves/{}ff/{}", ESCAPE, ESCAPE));
+        let text = encode(&path);
+
+        assert_eq!(text, format!("/saves/{0}{0}ff/{0}{0}", ESCAPE));
+        assert_eq!(decode(&text), path);
+    }
+
+    #[test]
+    fn unknown_escapes_are_kept() {
+        let text = format!("/saves/{}zz", ESCAPE);
+
+        assert_eq!(decode(&text), PathBuf::from(&text));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_round_trips() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // "Sauvegardé" in Latin-1, as written by games running under Wine
+        let path = Path::new(OsStr::from_bytes(b"/saves/Sauvegard\xe9/\xff\xfe.sav"));
+        let text = encode(&path);
+
+        assert_eq!(
+            text,
+            format!("/saves/Sauvegard{0}e9/{0}ff{0}fe.sav", ESCAPE)
+        );
+        assert_eq!(decode(&text), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unpaired_surrogates_round_trip() {
+        use std::ffi::OsString;
+        use std::os::windows::ffi::OsStringExt;
+
+        let wide: Vec<u16> = "C:\\Saves\\"
+            .encode_utf16()
+            .chain(vec![0xD800, 0x61])
+            .collect();
+        let path = PathBuf::from(OsString::from_wide(&wide));
+        let text = encode(&path);
+
+        assert_eq!(text, format!("C:\\Saves\\{}ud800a", ESCAPE));
+        assert_eq!(decode(&text), path);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ProcessError {
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error("Process detection is not supported on this Operating System")]
+    Unsupported,
+}
+
+/// Represents a running process which matched one of the names we were looking for
+///
+/// # Properties
+/// * `pid` - The ID of the process
+/// * `name` - The executable name which matched
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Linux truncates `/proc/<pid>/comm` to 15 bytes
+const COMM_LEN: usize = 15;
+
+/// Finds every running process whose executable name matches one of `names`.
+///
+/// # Examples
+/// ```
+/// use save_sync::process;
+///
+/// let running = process::find_running(&["definitely-not-running.exe"]);
+/// # #[cfg(target_os = "linux")]
+/// assert!(running.unwrap().is_empty());
+/// ```
+#[cfg(target_os = "linux")]
+pub fn find_running<S: AsRef<str>>(names: &[S]) -> Result<Vec<Process>, ProcessError> {
+    find_running_in(&Path::new("/proc"), names)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn find_running<S: AsRef<str>>(_names: &[S]) -> Result<Vec<Process>, ProcessError> {
+    Err(ProcessError::Unsupported)
+}
+
+/// Does the same as `find_running`, but reads processes from a procfs mounted at `proc_root`
+pub fn find_running_in<P: AsRef<Path>, S: AsRef<str>>(
+    proc_root: &P,
+    names: &[S],
+) -> Result<Vec<Process>, ProcessError> {
+    let mut result = vec![];
+
+    if names.is_empty() {
+        return Ok(result);
+    }
+
+    for entry in fs::read_dir(proc_root)? {
+        let entry = entry?;
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue, // Not a process directory (e.g. /proc/sys)
+        };
+
+        // Processes may exit while we're looking at them, so read errors are expected here
+        let candidates = process_names(&entry.path());
+
+        for name in names {
+            let name = name.as_ref();
+
+            if candidates.iter().any(|candidate| is_match(name, candidate)) {
+                result.push(Process {
+                    pid,
+                    name: name.to_string(),
+                });
+                break;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Collects every name a process could be known by (comm, argv[0] and the target of exe)
+fn process_names(dir: &Path) -> Vec<String> {
+    let mut names = vec![];
+
+    if let Ok(comm) = fs::read_to_string(dir.join("comm")) {
+        names.push(comm.trim_end_matches('\n').to_string());
+    }
+
+    if let Ok(cmdline) = fs::read(dir.join("cmdline")) {
+        if let Some(argv0) = cmdline.split(|b| *b == 0).next() {
+            names.push(base_name(&String::from_utf8_lossy(argv0)));
+        }
+    }
+
+    if let Ok(exe) = fs::read_link(dir.join("exe")) {
+        names.push(base_name(&exe.to_string_lossy()));
+    }
+
+    names
+}
+
+/// Strips both UNIX and Windows style directories, since games under Wine report paths like `C:\Game\game.exe`
+fn base_name(path: &str) -> String {
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_string()
+}
+
+fn is_match(name: &str, candidate: &str) -> bool {
+    if candidate.is_empty() {
+        return false;
+    }
+
+    // Windows executables are case insensitive
+    let is_exe = name.to_ascii_lowercase().ends_with(".exe");
+    let eq = |a: &[u8], b: &[u8]| {
+        if is_exe {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+
+    let (name, candidate) = (name.as_bytes(), candidate.as_bytes());
+
+    if eq(name, candidate) {
+        return true;
+    }
+
+    // comm is truncated, so a truncated match is the best we can do
+    candidate.len() == COMM_LEN && name.len() > COMM_LEN && eq(&name[..COMM_LEN], candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn create_fake_process(root: &Path, pid: u32, comm: &str, cmdline: &[u8]) {
+        let dir: PathBuf = [root, &PathBuf::from(pid.to_string())].iter().collect();
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(dir.join("cmdline"), cmdline).unwrap();
+    }
+
+    #[test]
+    fn find_running_by_comm() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        create_fake_process(tmp_dir, 42, "celeste", b"./celeste\0--fullscreen\0");
+        create_fake_process(tmp_dir, 43, "bash", b"/bin/bash\0");
+        fs::create_dir(tmp_dir.join("sys")).unwrap();
+
+        let actual = find_running_in(&tmp_dir, &["celeste"]).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(
+            actual,
+            vec![Process {
+                pid: 42,
+                name: "celeste".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn find_running_wine_executable() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let cmdline = b"C:\\Program Files\\Game\\DarkSoulsIII.exe\0";
+        create_fake_process(tmp_dir, 1337, "DarkSoulsIII.ex", cmdline);
+
+        let actual = find_running_in(&tmp_dir, &["darksoulsiii.exe"]).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].pid, 1337);
+    }
+
+    #[test]
+    fn find_running_truncated_comm() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        create_fake_process(tmp_dir, 7, "a_very_long_gam", b"");
+
+        let actual = find_running_in(&tmp_dir, &["a_very_long_game_name"]).unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(actual.len(), 1);
+    }
+
+    #[test]
+    fn find_running_none() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        create_fake_process(tmp_dir, 43, "bash", b"/bin/bash\0");
+
+        let actual = find_running_in(&tmp_dir, &["celeste"]).unwrap();
+
+        test_dir.close().unwrap();
+        assert!(actual.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The kinds of work which report their progress
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Task {
+    Scanning,
+    Hashing,
+    Copying,
+    Compressing,
+    Decompressing,
+}
+
+/// Observes long running operations like backing up or compressing a save.
+///
+/// Every method does nothing by default, so implementors only have to care about what they want to display.
+/// Work may be split across threads, which is why implementors have to be `Sync`.
+///
+/// # Examples
+/// ```
+/// use save_sync::progress::{Progress, Task};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// #[derive(Default)]
+/// struct Counter(AtomicU64);
+///
+/// impl Progress for Counter {
+///     fn advance(&self, task: Task, bytes: u64) {
+///         if task == Task::Hashing {
+///             self.0.fetch_add(bytes, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// let counter = Counter::default();
+/// counter.advance(Task::Hashing, 1024);
+/// assert_eq!(counter.0.load(Ordering::Relaxed), 1024);
+/// ```
+pub trait Progress: Sync {
+    /// `task` has started. `total` is the amount of bytes it will go through, if that is known up front
+    fn start(&self, _task: Task, _total: Option<u64>) {}
+
+    /// `task` is now working on `path`. While scanning, this is called once for every file that was found
+    fn path(&self, _task: Task, _path: &Path) {}
+
+    /// `task` went through another `bytes` bytes
+    fn advance(&self, _task: Task, _bytes: u64) {}
+
+    /// `task` has finished
+    fn finish(&self, _task: Task) {}
+}
+
+/// Ignores all progress
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// Reports every byte which is read through it to a `Progress`
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    task: Task,
+    progress: &'a dyn Progress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, task: Task, progress: &'a dyn Progress) -> ProgressReader<'a, R> {
+        ProgressReader {
+            inner,
+            task,
+            progress,
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.advance(self.task, n as u64);
+
+        Ok(n)
+    }
+}
+
+/// Reports every byte which is written through it to a `Progress`
+pub struct ProgressWriter<'a, W> {
+    inner: W,
+    task: Task,
+    progress: &'a dyn Progress,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    pub fn new(inner: W, task: Task, progress: &'a dyn Progress) -> ProgressWriter<'a, W> {
+        ProgressWriter {
+            inner,
+            task,
+            progress,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.progress.advance(self.task, n as u64);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(Task, u64)>>);
+
+    impl Progress for Recorder {
+        fn advance(&self, task: Task, bytes: u64) {
+            self.0.lock().unwrap().push((task, bytes));
+        }
+    }
+
+    #[test]
+    fn reader_and_writer_report_bytes() {
+        let recorder = Recorder::default();
+        let data = vec![7u8; 100];
+
+        let mut reader = ProgressReader::new(&data[..], Task::Hashing, &recorder);
+        let mut writer = ProgressWriter::new(vec![], Task::Copying, &recorder);
+        io::copy(&mut reader, &mut writer).unwrap();
+
+        let copied = writer.into_inner();
+        let events = recorder.0.into_inner().unwrap();
+
+        let sum = |task| -> u64 {
+            events
+                .iter()
+                .filter(|(t, _)| *t == task)
+                .map(|(_, n)| n)
+                .sum()
+        };
+
+        assert_eq!(copied, data);
+        assert_eq!(sum(Task::Hashing), 100);
+        assert_eq!(sum(Task::Copying), 100);
+    }
+}
@@ -0,0 +1,523 @@
+//! Runs save-sync on a schedule, either through a systemd user timer or through the user's crontab.
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The name of both the service and the timer unit, without their extension
+pub const UNIT_NAME: &str = "save-sync-update";
+
+/// Ends the crontab line save-sync installed, so that it can be found again
+const CRON_MARKER: &str = "# save-sync schedule";
+
+/// How long to wait after the user logs in before the first scheduled run
+const STARTUP_DELAY: &str = "5min";
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+    #[error("\"{0}\" is not a valid interval. Use e.g. 30m, 2h, 1d, hourly, daily or weekly")]
+    InvalidInterval(String),
+    #[error("cron is unable to run something every {0}. Use an interval which evenly divides an hour or a day")]
+    UnsupportedByCron(Interval),
+    #[error("Unable to determine the systemd user unit directory")]
+    UnknownUnitDir,
+    #[error("{0} failed: {1}")]
+    CommandFailed(String, String),
+    #[error("{0} is not installed")]
+    MissingProgram(String),
+}
+
+/// How often a scheduled backup runs, in whole minutes
+///
+/// # Examples
+/// ```
+/// use save_sync::schedule::Interval;
+///
+/// let interval: Interval = "2h".parse().unwrap();
+/// assert_eq!(interval.minutes(), 120);
+/// assert_eq!(interval, "120m".parse().unwrap());
+/// assert_eq!("daily".parse::<Interval>().unwrap().minutes(), 24 * 60);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Interval {
+    minutes: u64,
+}
+
+impl Interval {
+    const HOUR: u64 = 60;
+    const DAY: u64 = 24 * Self::HOUR;
+    const WEEK: u64 = 7 * Self::DAY;
+
+    pub fn minutes(&self) -> u64 {
+        self.minutes
+    }
+
+    /// The schedule of a crontab line which runs every interval
+    fn cron_expression(&self) -> Result<String, ScheduleError> {
+        let minutes = self.minutes;
+
+        let expression = match minutes {
+            1 => "* * * * *".to_string(),
+            m if m < Self::HOUR && Self::HOUR % m == 0 => format!("*/{} * * * *", m),
+            Self::HOUR => "0 * * * *".to_string(),
+            m if m < Self::DAY && m % Self::HOUR == 0 && Self::DAY % m == 0 => {
+                format!("0 */{} * * *", m / Self::HOUR)
+            }
+            Self::DAY => "0 0 * * *".to_string(),
+            Self::WEEK => "0 0 * * 0".to_string(),
+            _ => return Err(ScheduleError::UnsupportedByCron(*self)),
+        };
+
+        Ok(expression)
+    }
+}
+
+impl FromStr for Interval {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ScheduleError::InvalidInterval(s.to_string());
+        let text = s.trim().to_lowercase();
+
+        let minutes = match text.as_str() {
+            "hourly" => Self::HOUR,
+            "daily" => Self::DAY,
+            "weekly" => Self::WEEK,
+            _ => {
+                let split = text.len() - text.trim_start_matches(char::is_numeric).len();
+                let (count, unit) = text.split_at(split);
+                let count: u64 = count.parse().map_err(|_| invalid())?;
+
+                let unit = match unit.trim() {
+                    "m" | "min" | "minute" | "minutes" => 1,
+                    "h" | "hour" | "hours" => Self::HOUR,
+                    "d" | "day" | "days" => Self::DAY,
+                    "w" | "week" | "weeks" => Self::WEEK,
+                    _ => return Err(invalid()),
+                };
+
+                count.checked_mul(unit).ok_or_else(invalid)?
+            }
+        };
+
+        if minutes == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Interval { minutes })
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.minutes {
+            m if m % Self::WEEK == 0 => write!(f, "{}w", m / Self::WEEK),
+            m if m % Self::DAY == 0 => write!(f, "{}d", m / Self::DAY),
+            m if m % Self::HOUR == 0 => write!(f, "{}h", m / Self::HOUR),
+            m => write!(f, "{}m", m),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scheduler {
+    Systemd,
+    Cron,
+}
+
+/// A command which is run every `interval`
+///
+/// # Properties
+/// * `command` - The program followed by its arguments
+/// * `interval` - How often the command runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    pub command: Vec<String>,
+    pub interval: Interval,
+}
+
+/// A schedule which save-sync has installed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Installed {
+    /// `active` is whether systemd reports the timer as running, `next` when it will fire next if it knows
+    Systemd {
+        timer: PathBuf,
+        active: bool,
+        next: Option<String>,
+    },
+    /// The line in the user's crontab
+    Cron(String),
+}
+
+impl Schedule {
+    /// A oneshot service which runs the command once
+    pub fn service_unit(&self) -> String {
+        let command: Vec<String> = self.command.iter().map(|arg| systemd_quote(arg)).collect();
+
+        format!(
+            "[Unit]\n\
+             Description=Back up saved games with save-sync\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart={}\n",
+            command.join(" ")
+        )
+    }
+
+    /// A timer which starts the service every interval, beginning shortly after the user logs in
+    pub fn timer_unit(&self) -> String {
+        format!(
+            "[Unit]\n\
+             Description=Back up saved games with save-sync every {}\n\
+             \n\
+             [Timer]\n\
+             OnStartupSec={}\n\
+             OnUnitActiveSec={}min\n\
+             \n\
+             [Install]\n\
+             WantedBy=timers.target\n",
+            self.interval, STARTUP_DELAY, self.interval.minutes
+        )
+    }
+
+    pub fn crontab_line(&self) -> Result<String, ScheduleError> {
+        let command: Vec<String> = self.command.iter().map(|arg| shell_quote(arg)).collect();
+
+        Ok(format!(
+            "{} {} {}",
+            self.interval.cron_expression()?,
+            command.join(" "),
+            CRON_MARKER
+        ))
+    }
+
+    /// Writes the service and timer units to `dir`, replacing the ones which were there before
+    pub fn write_units<P: AsRef<Path>>(&self, dir: &P) -> io::Result<(PathBuf, PathBuf)> {
+        let (service, timer) = unit_paths(dir);
+
+        fs::create_dir_all(dir)?;
+        fs::write(&service, self.service_unit())?;
+        fs::write(&timer, self.timer_unit())?;
+
+        Ok((service, timer))
+    }
+
+    /// Installs the schedule and starts it right away. Any schedule which was installed before is removed,
+    /// even if it used the other scheduler
+    pub fn install(&self, scheduler: Scheduler) -> Result<(), ScheduleError> {
+        remove()?;
+
+        match scheduler {
+            Scheduler::Systemd => {
+                self.write_units(&unit_dir()?)?;
+                systemctl(&["daemon-reload"])?;
+                systemctl(&["enable", "--now", &format!("{}.timer", UNIT_NAME)])?;
+            }
+            Scheduler::Cron => {
+                let crontab = read_crontab()?;
+                write_crontab(&with_line(&crontab, Some(&self.crontab_line()?)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists every schedule save-sync has installed
+pub fn installed() -> Result<Vec<Installed>, ScheduleError> {
+    let mut result = vec![];
+    let (_, timer) = unit_paths(&unit_dir()?);
+
+    if timer.is_file() {
+        let name = format!("{}.timer", UNIT_NAME);
+        let active = systemctl(&["is-active", &name]).is_ok();
+        let next = systemctl(&[
+            "show",
+            "--property=NextElapseUSecRealtime",
+            "--value",
+            &name,
+        ])
+        .ok()
+        .map(|next| next.trim().to_string())
+        .filter(|next| !next.is_empty());
+
+        result.push(Installed::Systemd {
+            timer,
+            active,
+            next,
+        });
+    }
+
+    let crontab = read_crontab()?;
+
+    for line in crontab.lines().filter(|line| line.ends_with(CRON_MARKER)) {
+        result.push(Installed::Cron(line.to_string()));
+    }
+
+    Ok(result)
+}
+
+/// Stops and removes every schedule save-sync has installed, returning the kinds which were removed
+pub fn remove() -> Result<Vec<Scheduler>, ScheduleError> {
+    let mut removed = vec![];
+    let dir = unit_dir()?;
+    let (_, timer) = unit_paths(&dir);
+
+    if timer.is_file() {
+        // The timer may have never been enabled, or systemd may not be running at all. Either way the units
+        // have to go, so that they aren't started later on
+        let _ = systemctl(&["disable", "--now", &format!("{}.timer", UNIT_NAME)]);
+        remove_units(&dir)?;
+        let _ = systemctl(&["daemon-reload"]);
+        removed.push(Scheduler::Systemd);
+    }
+
+    let crontab = read_crontab()?;
+    let without = with_line(&crontab, None);
+
+    if without != crontab {
+        write_crontab(&without)?;
+        removed.push(Scheduler::Cron);
+    }
+
+    Ok(removed)
+}
+
+/// Deletes the service and timer units from `dir`. Returns whether there was anything to delete
+pub fn remove_units<P: AsRef<Path>>(dir: &P) -> io::Result<bool> {
+    let mut found = false;
+    let (service, timer) = unit_paths(dir);
+
+    for path in [service, timer].iter().filter(|path| path.exists()) {
+        fs::remove_file(path)?;
+        found = true;
+    }
+
+    Ok(found)
+}
+
+/// Where systemd looks for the units of the current user
+pub fn unit_dir() -> Result<PathBuf, ScheduleError> {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.config_dir().join("systemd").join("user"))
+        .ok_or(ScheduleError::UnknownUnitDir)
+}
+
+fn unit_paths<P: AsRef<Path>>(dir: &P) -> (PathBuf, PathBuf) {
+    let dir = dir.as_ref();
+
+    (
+        dir.join(format!("{}.service", UNIT_NAME)),
+        dir.join(format!("{}.timer", UNIT_NAME)),
+    )
+}
+
+/// `crontab` with save-sync's line replaced by `line`, or removed if `line` is `None`
+fn with_line(crontab: &str, line: Option<&str>) -> String {
+    let mut lines: Vec<&str> = crontab
+        .lines()
+        .filter(|existing| !existing.ends_with(CRON_MARKER))
+        .collect();
+
+    lines.extend(line);
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        lines.join("\n") + "\n"
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<String, ScheduleError> {
+    let mut full = vec!["--user"];
+    full.extend_from_slice(args);
+
+    run("systemctl", &full, None)
+}
+
+/// Users without a crontab, or without cron at all, have an empty one
+fn read_crontab() -> Result<String, ScheduleError> {
+    match run("crontab", &["-l"], None) {
+        Ok(crontab) => Ok(crontab),
+        Err(ScheduleError::CommandFailed(_, message)) if message.contains("no crontab") => {
+            Ok(String::new())
+        }
+        Err(ScheduleError::MissingProgram(_)) => Ok(String::new()),
+        Err(err) => Err(err),
+    }
+}
+
+fn write_crontab(crontab: &str) -> Result<(), ScheduleError> {
+    run("crontab", &["-"], Some(crontab))?;
+    Ok(())
+}
+
+/// Runs `program`, feeding it `input`, and returns what it printed
+fn run(program: &str, args: &[&str], input: Option<&str>) -> Result<String, ScheduleError> {
+    let description = format!("{} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => ScheduleError::MissingProgram(program.to_string()),
+            _ => ScheduleError::CommandFailed(description.clone(), err.to_string()),
+        })?;
+
+    if let Some(input) = input {
+        // Taking stdin closes it once it is written, otherwise the program would wait for more
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(input.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(ScheduleError::CommandFailed(description, stderr));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quotes `arg` for the command line of a systemd unit. `%` and `$` are expanded by systemd even inside quotes
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    let plain = |c: char| !c.is_whitespace() && !"\"'\\;".contains(c);
+
+    if !escaped.is_empty() && escaped.chars().all(plain) {
+        return escaped;
+    }
+
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes `arg` for the shell cron runs commands with. cron turns unescaped `%` into newlines
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/-_.,:=+@".contains(c);
+
+    let quoted = if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    };
+
+    quoted.replace('%', "\\%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn schedule(interval: &str) -> Schedule {
+        Schedule {
+            command: vec![
+                "/usr/bin/save-sync".to_string(),
+                "update".to_string(),
+                "-f".to_string(),
+                "Hollow Knight".to_string(),
+            ],
+            interval: interval.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_intervals() {
+        let minutes = |s: &str| s.parse::<Interval>().map(|interval| interval.minutes());
+
+        assert_eq!(minutes("30m").unwrap(), 30);
+        assert_eq!(minutes("2 hours").unwrap(), 120);
+        assert_eq!(minutes("1d").unwrap(), 1440);
+        assert_eq!(minutes("Weekly").unwrap(), 10080);
+        assert!(minutes("0m").is_err());
+        assert!(minutes("h").is_err());
+        assert!(minutes("3 fortnights").is_err());
+
+        assert_eq!("90m".parse::<Interval>().unwrap().to_string(), "90m");
+        assert_eq!("48h".parse::<Interval>().unwrap().to_string(), "2d");
+    }
+
+    #[test]
+    fn cron_expressions() {
+        let expression = |s: &str| s.parse::<Interval>().unwrap().cron_expression();
+
+        assert_eq!(expression("15m").unwrap(), "*/15 * * * *");
+        assert_eq!(expression("hourly").unwrap(), "0 * * * *");
+        assert_eq!(expression("6h").unwrap(), "0 */6 * * *");
+        assert_eq!(expression("1d").unwrap(), "0 0 * * *");
+        assert_eq!(expression("1w").unwrap(), "0 0 * * 0");
+        assert!(matches!(
+            expression("90m"),
+            Err(ScheduleError::UnsupportedByCron(_))
+        ));
+        assert!(expression("5h").is_err());
+    }
+
+    #[test]
+    fn units_run_the_command() {
+        let schedule = schedule("6h");
+
+        assert!(schedule
+            .service_unit()
+            .contains("ExecStart=/usr/bin/save-sync update -f \"Hollow Knight\"\n"));
+        assert!(schedule.timer_unit().contains("OnUnitActiveSec=360min\n"));
+        assert!(schedule.timer_unit().contains("every 6h\n"));
+    }
+
+    #[test]
+    fn quote_arguments() {
+        assert_eq!(systemd_quote("50% done"), "\"50%% done\"");
+        assert_eq!(systemd_quote("$HOME"), "$$HOME");
+        assert_eq!(systemd_quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(shell_quote("Baldur's Gate"), "'Baldur'\\''s Gate'");
+        assert_eq!(shell_quote("100%"), "'100\\%'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn crontab_line_is_replaced() {
+        let line = schedule("1d").crontab_line().unwrap();
+        assert_eq!(
+            line,
+            "0 0 * * * /usr/bin/save-sync update -f 'Hollow Knight' # save-sync schedule"
+        );
+
+        let crontab = "MAILTO=me\n0 3 * * * backup.sh\n";
+        let installed = with_line(crontab, Some(&line));
+        let replaced = with_line(&installed, Some("* * * * * true # save-sync schedule"));
+
+        assert_eq!(installed, format!("{}{}\n", crontab, line));
+        assert_eq!(replaced.lines().count(), 3);
+        assert!(replaced.ends_with("true # save-sync schedule\n"));
+        assert_eq!(with_line(&replaced, None), crontab);
+        assert_eq!(with_line(&line, None), "");
+    }
+
+    #[test]
+    fn write_and_remove_units() {
+        let test_dir = TempDir::new().unwrap();
+        let dir = test_dir.path().join("systemd").join("user");
+
+        let (service, timer) = schedule("1h").write_units(&dir).unwrap();
+        let written = fs::read_to_string(&timer).unwrap();
+        let removed = remove_units(&dir).unwrap();
+        let removed_again = remove_units(&dir).unwrap();
+        let service_exists = service.exists();
+
+        test_dir.close().unwrap();
+
+        assert!(written.contains("OnUnitActiveSec=60min"));
+        assert!(removed);
+        assert!(!removed_again);
+        assert!(!service_exists);
+    }
+}
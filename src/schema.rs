@@ -1,3 +1,25 @@
+table! {
+    chunks (id) {
+        id -> Integer,
+        file_id -> Integer,
+        position -> Integer,
+        chunk_hash -> Binary,
+        chunk_size -> BigInt,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+    }
+}
+
+table! {
+    executables (id) {
+        id -> Integer,
+        name -> Text,
+        save_id -> Integer,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+    }
+}
+
 table! {
     files (id) {
         id -> Integer,
@@ -6,6 +28,21 @@ table! {
         save_id -> Integer,
         created_at -> Timestamp,
         modified_at -> Timestamp,
+        file_type -> Integer,
+        link_target -> Nullable<Text>,
+        permissions -> Nullable<Integer>,
+        file_mtime -> Nullable<Timestamp>,
+        file_size -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    notes (id) {
+        id -> Integer,
+        body -> Text,
+        save_id -> Integer,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
     }
 }
 
@@ -22,6 +59,33 @@ table! {
     }
 }
 
+table! {
+    settings (name) {
+        name -> Text,
+        value -> Text,
+        modified_at -> Timestamp,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+        save_id -> Integer,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+    }
+}
+
+table! {
+    updates (id) {
+        id -> Integer,
+        save_id -> Integer,
+        files_changed -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     users (id) {
         id -> Integer,
@@ -31,11 +95,22 @@ table! {
     }
 }
 
+joinable!(chunks -> files (file_id));
+joinable!(executables -> saves (save_id));
 joinable!(files -> saves (save_id));
+joinable!(notes -> saves (save_id));
 joinable!(saves -> users (user_id));
+joinable!(tags -> saves (save_id));
+joinable!(updates -> saves (save_id));
 
 allow_tables_to_appear_in_same_query!(
+    chunks,
+    executables,
     files,
+    notes,
     saves,
+    settings,
+    tags,
+    updates,
     users,
 );
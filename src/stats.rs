@@ -0,0 +1,323 @@
+use crate::archive::query::{ChunkQuery, FileQuery, SaveQuery};
+use crate::context::SaveSync;
+use crate::models::{FileType, Save, User};
+use crate::paths;
+use crate::storage::{self, StorageError};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StatsError {
+    #[error(transparent)]
+    StorageError(#[from] StorageError),
+}
+
+/// How large a Save and its backup are, and how often it has been updated
+///
+/// # Properties
+/// * `save` - The Save these statistics are about
+/// * `files` - The number of regular files in the Save
+/// * `live_size` - The size of every file in the Save as of the last backup, in bytes
+/// * `backup_size` - The number of bytes the backup takes up in storage. Chunks which are shared between files
+///   of the Save are only counted once
+/// * `updates` - How many times the Save has been updated since it was added
+/// * `last_backup` - When the Save was last backed up
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveStats {
+    pub save: Save,
+    pub files: u64,
+    pub live_size: u64,
+    pub backup_size: u64,
+    pub updates: u64,
+    pub last_backup: NaiveDateTime,
+}
+
+impl SaveStats {
+    /// Reads the statistics of `save` from the database and the storage backend its backup is in
+    pub fn collect(ctx: &SaveSync, save: Save) -> Result<SaveStats, StatsError> {
+        let db = ctx.db();
+        let files = db
+            .get_files(FileQuery::new().with_save_id(save.id))
+            .unwrap_or_default();
+        let updates = db.get_updates(save.id).unwrap_or_default();
+
+        let backend = ctx.storage_for(&save.uuid, &save.friendly_name)?;
+        let mut backup_size = 0;
+        let mut chunk_sizes = HashMap::new();
+        let mut count = 0;
+        let mut live_size = 0;
+
+        for file in files.iter().filter(|file| file.kind() == FileType::File) {
+            count += 1;
+            live_size += file.file_size.unwrap_or(0) as u64;
+
+            for chunk in db
+                .get_chunks(ChunkQuery::new().with_file_id(file.id))
+                .unwrap_or_default()
+            {
+                chunk_sizes.insert(chunk.chunk_hash, chunk.chunk_size as u64);
+            }
+        }
+
+        // Files which weren't split into chunks are stored as they are below the backup path
+        for key in backend.list(&Self::backup_prefix(&save)?)? {
+            backup_size += backend.size(&key)?;
+        }
+
+        backup_size += chunk_sizes.values().sum::<u64>();
+
+        // Only updates count as backups; refreshing the cached metadata of a file doesn't
+        let last_backup = updates
+            .iter()
+            .map(|update| update.created_at)
+            .max()
+            .unwrap_or(save.created_at);
+
+        Ok(SaveStats {
+            files: count,
+            live_size,
+            backup_size,
+            updates: updates.len() as u64,
+            last_backup,
+            save,
+        })
+    }
+
+    /// How many times smaller the backup is than the files it was made from. `None` if the backup is empty
+    pub fn compression_ratio(&self) -> Option<f64> {
+        ratio(self.live_size, self.backup_size)
+    }
+
+    /// The keys of every file in the backup of `save` start with this
+    fn backup_prefix(save: &Save) -> Result<String, StatsError> {
+        let save_path = paths::decode(&save.save_path);
+        let backup_path = paths::decode(&save.backup_path);
+        let key = storage::backup_key(&save_path, &backup_path, &save_path)?;
+
+        Ok(format!("{}/", key))
+    }
+}
+
+/// The statistics of every Save which belongs to a User
+///
+/// # Properties
+/// * `user` - The User these statistics are about
+/// * `saves` - The statistics of every Save of the User
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserStats {
+    pub user: User,
+    pub saves: Vec<SaveStats>,
+}
+
+impl UserStats {
+    pub fn collect(ctx: &SaveSync, user: User) -> Result<UserStats, StatsError> {
+        let saves = ctx
+            .db()
+            .get_saves(SaveQuery::new().with_user_id(user.id))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|save| SaveStats::collect(ctx, save))
+            .collect::<Result<_, _>>()?;
+
+        Ok(UserStats { user, saves })
+    }
+
+    pub fn files(&self) -> u64 {
+        self.saves.iter().map(|stats| stats.files).sum()
+    }
+
+    pub fn live_size(&self) -> u64 {
+        self.saves.iter().map(|stats| stats.live_size).sum()
+    }
+
+    /// Chunks which are shared between saves are counted once for every save
+    pub fn backup_size(&self) -> u64 {
+        self.saves.iter().map(|stats| stats.backup_size).sum()
+    }
+
+    pub fn updates(&self) -> u64 {
+        self.saves.iter().map(|stats| stats.updates).sum()
+    }
+
+    /// When any Save of the User was last backed up. `None` if the User doesn't have any saves
+    pub fn last_backup(&self) -> Option<NaiveDateTime> {
+        self.saves.iter().map(|stats| stats.last_backup).max()
+    }
+
+    pub fn compression_ratio(&self) -> Option<f64> {
+        ratio(self.live_size(), self.backup_size())
+    }
+}
+
+/// Collects the statistics of every User in the database, along with all of their saves
+pub fn collect(ctx: &SaveSync) -> Result<Vec<UserStats>, StatsError> {
+    ctx.db()
+        .get_all_users()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|user| UserStats::collect(ctx, user))
+        .collect()
+}
+
+fn ratio(live_size: u64, backup_size: u64) -> Option<f64> {
+    if backup_size == 0 {
+        None
+    } else {
+        Some(live_size as f64 / backup_size as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::{NewChunk, NewSave, NewUpdate, NewUser};
+    use crate::test_utils::create_file;
+    use chrono::{Duration, Utc};
+    use tempfile::TempDir;
+
+    #[test]
+    fn collect_save_and_user_stats() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let data_dir = tmp_dir.join("data");
+
+        let ctx = SaveSync::new(Config {
+            db_location: tmp_dir.join("saves.db"),
+            data_location: data_dir.clone(),
+            ..Config::default()
+        })
+        .unwrap();
+        let db = ctx.db();
+        let time = Utc::now().naive_utc() - Duration::days(1);
+        let backup_path = paths::encode(&data_dir.join("uuid").join("game"));
+
+        db.create_user(NewUser {
+            username: "User1",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "game",
+            save_path: "/saves/game",
+            backup_path: &backup_path,
+            uuid: "uuid",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        // A small file which is stored as it is, and a large one which is made up of one chunk twice
+        create_file(&ctx, "/saves/game/slot1.sav", 10, time);
+        create_file(&ctx, "/saves/game/big.sav", 2048, time);
+
+        let backend = ctx.storage_for("uuid", "game").unwrap();
+        backend.put("uuid/game/slot1.sav", &[1; 10]).unwrap();
+        backend.put("uuid/other/slot1.sav", &[1; 10]).unwrap();
+
+        for position in 0..2 {
+            db.create_chunks(&[NewChunk {
+                file_id: 2,
+                position,
+                chunk_hash: &[7; 8],
+                chunk_size: 1024,
+                created_at: time,
+                modified_at: time,
+            }]);
+        }
+
+        let updated_at = time + Duration::hours(1);
+
+        for _ in 0..3 {
+            db.create_update(NewUpdate {
+                save_id: 1,
+                files_changed: 1,
+                created_at: updated_at,
+            });
+        }
+
+        let users = collect(&ctx).unwrap();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user.username, "User1");
+
+        let stats = &users[0].saves[0];
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.live_size, 2058);
+        assert_eq!(stats.backup_size, 1034);
+        assert_eq!(stats.updates, 3);
+        assert_eq!(stats.last_backup, updated_at);
+        assert_eq!(stats.compression_ratio(), Some(2058.0 / 1034.0));
+
+        assert_eq!(users[0].files(), 2);
+        assert_eq!(users[0].updates(), 3);
+        assert_eq!(users[0].last_backup(), Some(updated_at));
+    }
+
+    #[test]
+    fn last_backup_ignores_files() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let data_dir = tmp_dir.join("data");
+
+        let ctx = SaveSync::new(Config {
+            db_location: tmp_dir.join("saves.db"),
+            data_location: data_dir.clone(),
+            ..Config::default()
+        })
+        .unwrap();
+        let db = ctx.db();
+        let time = Utc::now().naive_utc() - Duration::days(1);
+        let backup_path = paths::encode(&data_dir.join("uuid").join("game"));
+
+        db.create_user(NewUser {
+            username: "User1",
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+        db.create_save(NewSave {
+            friendly_name: "game",
+            save_path: "/saves/game",
+            backup_path: &backup_path,
+            uuid: "uuid",
+            user_id: 1,
+            created_at: time,
+            modified_at: time,
+        })
+        .unwrap();
+
+        // A file row which was touched after the save was added, without an update being made
+        create_file(&ctx, "/saves/game/slot1.sav", 10, time + Duration::hours(2));
+
+        let save = db.get_save(SaveQuery::new().with_uuid("uuid")).unwrap();
+        let without_updates = SaveStats::collect(&ctx, save.clone()).unwrap();
+
+        let updated_at = time + Duration::hours(1);
+        db.create_update(NewUpdate {
+            save_id: 1,
+            files_changed: 1,
+            created_at: updated_at,
+        });
+        let with_update = SaveStats::collect(&ctx, save).unwrap();
+
+        drop(ctx);
+        test_dir.close().unwrap();
+
+        assert_eq!(without_updates.last_backup, time);
+        assert_eq!(with_update.last_backup, updated_at);
+    }
+
+    #[test]
+    fn empty_backups_have_no_ratio() {
+        assert_eq!(ratio(0, 0), None);
+        assert_eq!(ratio(10, 0), None);
+        assert_eq!(ratio(10, 5), Some(2.0));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::atomic;
+use crate::paths;
+use crate::progress::{Progress, Task};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+pub mod local;
+pub mod s3;
+pub mod sftp;
+pub mod webdav;
+
+pub use local::LocalStorage;
+pub use s3::{S3Config, S3Storage};
+pub use sftp::{SftpConfig, SftpStorage};
+pub use webdav::{WebDavConfig, WebDavStorage};
+
+/// Everything but the unreserved characters of RFC 3986 is encoded
+pub(crate) const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Same as `URI_ENCODE`, except that `/` is left alone so that keys can be used as the path of a URL
+pub(crate) const PATH_ENCODE: &AsciiSet = &URI_ENCODE.remove(b'/');
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    SshError(#[from] ssh2::Error),
+    #[error("{0} does not exist in storage")]
+    NotFound(String),
+    #[error("{0} is not a valid storage key")]
+    InvalidKey(String),
+    #[error("Request to the storage backend failed: {0}")]
+    RequestError(String),
+    #[error("The storage backend responded with {0}: {1}")]
+    UnexpectedResponse(u16, String),
+}
+
+/// Somewhere to put backups, which stores blobs of bytes under `/` separated keys like `{uuid}/game/00.sav`
+///
+/// Only `put`, `get`, `list` and `delete` have to be implemented. Everything else can be overridden
+/// by backends which are able to do better than the default.
+pub trait StorageBackend: Send + Sync {
+    /// Stores `data` under `key`, replacing whatever was there before
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError>;
+
+    /// Returns `StorageError::NotFound` if nothing is stored under `key`
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Every key which starts with `prefix`, in order
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError>;
+
+    /// Deleting a key which doesn't exist is not an error
+    fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        match self.get(key) {
+            Ok(_) => Ok(true),
+            Err(StorageError::NotFound(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The number of bytes stored under `key`. Returns `StorageError::NotFound` if nothing is stored under `key`
+    fn size(&self, key: &str) -> Result<u64, StorageError> {
+        Ok(self.get(key)?.len() as u64)
+    }
+
+    /// Stores the file at `path` under `key`. Returns the number of bytes stored
+    fn put_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let data = fs::read(path)?;
+        progress.path(Task::Copying, path);
+
+        self.put(key, &data)?;
+        progress.advance(Task::Copying, data.len() as u64);
+
+        Ok(data.len() as u64)
+    }
+
+    /// Atomically writes whatever is stored under `key` to `path`. Returns the number of bytes written
+    fn get_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let data = self.get(key)?;
+        progress.path(Task::Copying, path);
+
+        atomic::write(&path, &data)?;
+        progress.advance(Task::Copying, data.len() as u64);
+
+        Ok(data.len() as u64)
+    }
+
+    /// Deletes every key which starts with `prefix`
+    fn delete_prefix(&self, prefix: &str) -> Result<(), StorageError> {
+        for key in self.list(prefix)? {
+            self.delete(&key)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes a storage backend in `Config`
+///
+/// # Examples
+/// ```
+/// use save_sync::storage::StorageConfig;
+///
+/// let config: StorageConfig = toml::from_str(r#"
+///     type = "s3"
+///     endpoint = "http://127.0.0.1:9000"
+///     region = "us-east-1"
+///     bucket = "saves"
+///     access_key = "minioadmin"
+///     secret_key = "minioadmin"
+/// "#).unwrap();
+///
+/// assert!(matches!(config, StorageConfig::S3(_)));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// A directory on this machine. Defaults to the data location of the config
+    Local {
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
+    /// A bucket of an S3 compatible service like AWS S3 or MinIO
+    S3(S3Config),
+    /// A directory on a host which can be reached over SSH
+    Sftp(SftpConfig),
+    /// A collection on a WebDAV server like Nextcloud
+    WebDav(WebDavConfig),
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::Local { path: None }
+    }
+}
+
+/// Creates the backend which is described by `config`. `data_location` is where local backups go by default
+pub fn open<P: AsRef<Path>>(
+    config: &StorageConfig,
+    data_location: &P,
+) -> Result<Arc<dyn StorageBackend>, StorageError> {
+    let backend: Arc<dyn StorageBackend> = match config {
+        StorageConfig::Local { path } => {
+            let root = path.as_deref().unwrap_or_else(|| data_location.as_ref());
+            Arc::new(LocalStorage::new(&root))
+        }
+        StorageConfig::S3(config) => Arc::new(S3Storage::new(config.clone())),
+        StorageConfig::Sftp(config) => Arc::new(SftpStorage::new(config.clone())),
+        StorageConfig::WebDav(config) => Arc::new(WebDavStorage::new(config.clone())),
+    };
+
+    Ok(backend)
+}
+
+/// Turns a relative path like `{uuid}/game/00.sav` into a key.
+/// Names which aren't UTF-8 are encoded with `paths::encode`
+pub fn path_to_key<P: AsRef<Path>>(path: &P) -> Result<String, StorageError> {
+    let path = path.as_ref();
+    let invalid = || StorageError::InvalidKey(path.to_string_lossy().to_string());
+    let mut parts = vec![];
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(paths::encode(&part)),
+            Component::CurDir => {}
+            _ => return Err(invalid()),
+        }
+    }
+
+    if parts.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// The key the backup of `file_path` is stored under, which is its backup path relative to the data location
+/// `backup_path` is in. The backup keeps its name when the save is moved, so `file_path` is relative to `save_path`
+///
+/// # Examples
+/// ```
+/// use save_sync::storage;
+/// use std::path::Path;
+///
+/// let save_path = Path::new("/home/user/Games/Celeste");
+/// let backup_path = Path::new("/home/user/.local/share/save-sync/some-uuid/Saves");
+/// let file_path = save_path.join("0.celeste");
+///
+/// let key = storage::backup_key(save_path, backup_path, &file_path).unwrap();
+///
+/// assert_eq!(key, "some-uuid/Saves/0.celeste");
+/// ```
+pub fn backup_key(
+    save_path: &Path,
+    backup_path: &Path,
+    file_path: &Path,
+) -> Result<String, StorageError> {
+    let invalid = |path: &Path| StorageError::InvalidKey(path.to_string_lossy().to_string());
+    let root = backup_path
+        .parent()
+        .and_then(Path::parent)
+        .ok_or_else(|| invalid(backup_path))?;
+    let relative = file_path
+        .strip_prefix(save_path)
+        .map_err(|_| invalid(file_path))?;
+
+    let destination = backup_path.join(relative);
+
+    path_to_key(
+        &destination
+            .strip_prefix(root)
+            .map_err(|_| invalid(backup_path))?,
+    )
+}
+
+/// The inverse of `path_to_key`. Keys which would escape the root of a backend are rejected.
+/// A `\` is a separator on Windows, but on Unix it's just another character a file name may contain
+pub fn key_to_path(key: &str) -> Result<PathBuf, StorageError> {
+    let invalid = || StorageError::InvalidKey(key.to_string());
+
+    if key.is_empty() || key.starts_with('/') {
+        return Err(invalid());
+    }
+
+    let mut path = PathBuf::new();
+
+    for part in key.split('/') {
+        let is_separated = cfg!(windows) && part.contains('\\');
+
+        if part.is_empty() || part == "." || part == ".." || is_separated {
+            return Err(invalid());
+        }
+
+        path.push(paths::decode(part));
+    }
+
+    Ok(path)
+}
+
+/// Undoes the escaping of the five predefined XML entities
+pub(crate) fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_paths() {
+        let path: PathBuf = ["uuid", "game", "00.sav"].iter().collect();
+
+        assert_eq!(path_to_key(&path).unwrap(), "uuid/game/00.sav");
+        assert_eq!(key_to_path("uuid/game/00.sav").unwrap(), path);
+
+        assert!(path_to_key(&PathBuf::from("/etc/passwd")).is_err());
+        assert!(key_to_path("../outside").is_err());
+        assert!(key_to_path("/absolute").is_err());
+        assert!(key_to_path("double//slash").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn backslashes_are_part_of_names_on_unix() {
+        let save_path = Path::new("/home/user/Games/Celeste");
+        let backup_path = Path::new("/data/uuid/Celeste");
+        let key = backup_key(save_path, backup_path, &save_path.join("a\\b")).unwrap();
+
+        assert_eq!(key, "uuid/Celeste/a\\b");
+        assert_eq!(
+            key_to_path(&key).unwrap(),
+            ["uuid", "Celeste", "a\\b"].iter().collect::<PathBuf>()
+        );
+        assert!(key_to_path("uuid/..\\..").is_ok());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn backslashes_are_separators_on_windows() {
+        assert!(key_to_path("uuid/..\\outside").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use super::{key_to_path, StorageBackend, StorageError};
+use crate::archive::HASH_BUFFER_SIZE;
+use crate::atomic;
+use crate::progress::Progress;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Stores blobs as files in a directory on this machine
+///
+/// # Examples
+/// ```
+/// use save_sync::storage::{LocalStorage, StorageBackend};
+/// # let dir = tempfile::TempDir::new().unwrap();
+///
+/// let storage = LocalStorage::new(&dir.path());
+/// storage.put("uuid/game/00.sav", b"save data").unwrap();
+///
+/// assert_eq!(storage.get("uuid/game/00.sav").unwrap(), b"save data");
+/// assert_eq!(storage.list("uuid/").unwrap(), vec!["uuid/game/00.sav"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new<P: AsRef<Path>>(root: &P) -> LocalStorage {
+        LocalStorage {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, StorageError> {
+        Ok(self.root.join(key_to_path(key)?))
+    }
+
+    fn create_parent(path: &Path) -> Result<(), StorageError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(())
+    }
+
+    fn collect_keys(&self, dir: &Path, keys: &mut Vec<String>) -> Result<(), StorageError> {
+        let list = match fs::read_dir(dir) {
+            Ok(list) => list,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for entry in list {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                self.collect_keys(&path, keys)?;
+            } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                // Files whose names aren't UTF-8 can't have been stored through a key
+                if let Ok(key) = super::path_to_key(&relative) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the directories above `path` which have become empty, up to the root
+    fn prune(&self, path: &Path) {
+        let mut dir = path.parent();
+
+        while let Some(parent) = dir {
+            if parent == self.root || fs::remove_dir(parent).is_err() {
+                break;
+            }
+
+            dir = parent.parent();
+        }
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        Self::create_parent(&path)?;
+
+        Ok(atomic::write(&path, data)?)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        match fs::read(self.path_for(key)?) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.into()))
+            }
+            result => Ok(result?),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        // Only the directory the prefix points into has to be searched
+        let dir = match prefix.rfind('/') {
+            Some(i) => self.path_for(&prefix[..i])?,
+            None => self.root.clone(),
+        };
+
+        let mut keys = vec![];
+        self.collect_keys(&dir, &mut keys)?;
+
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => {
+                self.prune(&path);
+                Ok(())
+            }
+        }
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        Ok(self.path_for(key)?.is_file())
+    }
+
+    fn size(&self, key: &str) -> Result<u64, StorageError> {
+        match fs::metadata(self.path_for(key)?) {
+            Ok(meta) if meta.is_file() => Ok(meta.len()),
+            Ok(_) => Err(StorageError::NotFound(key.into())),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.into()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let destination = self.path_for(key)?;
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+        Self::create_parent(&destination)?;
+
+        Ok(atomic::copy_with(
+            &path,
+            &destination,
+            &mut buffer,
+            progress,
+        )?)
+    }
+
+    fn get_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let source = self.path_for(key)?;
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+
+        match atomic::copy_with(&source, &path, &mut buffer, progress) {
+            Err(err) if err.kind() == ErrorKind::NotFound && !source.exists() => {
+                Err(StorageError::NotFound(key.into()))
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use tempfile::TempDir;
+
+    #[test]
+    fn put_get_list_delete() {
+        let test_dir = TempDir::new().unwrap();
+        let storage = LocalStorage::new(&test_dir.path().join("backups"));
+
+        storage.put("uuid/game/00.sav", b"first").unwrap();
+        storage.put("uuid/game/sub/01.sav", b"second").unwrap();
+        storage.put("other/02.sav", b"third").unwrap();
+        storage.put("uuid/game/00.sav", b"replaced").unwrap();
+
+        let replaced = storage.get("uuid/game/00.sav").unwrap();
+        let size = storage.size("uuid/game/00.sav").unwrap();
+        let dir_size = storage.size("uuid/game");
+        let listed = storage.list("uuid/").unwrap();
+        let everything = storage.list("").unwrap();
+
+        storage.delete("uuid/game/sub/01.sav").unwrap();
+        storage.delete("uuid/game/missing.sav").unwrap();
+        let pruned = !test_dir.path().join("backups/uuid/game/sub").exists();
+        let missing = storage.get("uuid/game/sub/01.sav");
+        let escape = storage.put("../escape", b"nope");
+
+        test_dir.close().unwrap();
+        assert_eq!(replaced, b"replaced");
+        assert_eq!(size, 8);
+        assert!(matches!(dir_size, Err(StorageError::NotFound(_))));
+        assert_eq!(listed, vec!["uuid/game/00.sav", "uuid/game/sub/01.sav"]);
+        assert_eq!(everything.len(), 3);
+        assert!(pruned);
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+        assert!(matches!(escape, Err(StorageError::InvalidKey(_))));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn names_with_backslashes() {
+        let test_dir = TempDir::new().unwrap();
+        let root = test_dir.path().join("backups");
+        let storage = LocalStorage::new(&root);
+
+        storage.put("uuid/a\\b", b"save data").unwrap();
+
+        let stored = storage.get("uuid/a\\b").unwrap();
+        let is_file = root.join("uuid").join("a\\b").is_file();
+
+        test_dir.close().unwrap();
+        assert_eq!(stored, b"save data");
+        assert!(is_file);
+    }
+
+    #[test]
+    fn put_and_get_file() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let storage = LocalStorage::new(&tmp_dir.join("backups"));
+
+        let source = tmp_dir.join("00.sav");
+        let target = tmp_dir.join("restored.sav");
+        fs::write(&source, "save data").unwrap();
+
+        let stored = storage
+            .put_file("uuid/00.sav", &source, &NoProgress)
+            .unwrap();
+        let restored = storage
+            .get_file("uuid/00.sav", &target, &NoProgress)
+            .unwrap();
+        let actual = fs::read_to_string(&target).unwrap();
+        let missing = storage.get_file("uuid/missing.sav", &target, &NoProgress);
+
+        test_dir.close().unwrap();
+        assert_eq!(stored, 9);
+        assert_eq!(restored, 9);
+        assert_eq!(actual, "save data");
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+    }
+}
@@ -0,0 +1,567 @@
+use super::{key_to_path, StorageBackend, StorageError};
+use crate::progress::{Progress, ProgressReader, ProgressWriter, Task};
+use serde::{Deserialize, Serialize};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, RenameFlags, Session, Sftp};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Returned by the SFTP server when a path doesn't exist
+const NO_SUCH_FILE: ErrorCode = ErrorCode::SFTP(2);
+
+/// Describes a directory on a host which can be reached over SSH
+///
+/// # Properties
+/// * `host` - The name or address of the host
+/// * `port` - Defaults to 22
+/// * `user` - The user to log in as
+/// * `key_path` - The private key to authenticate with. Agents and passwords aren't supported
+/// * `passphrase` - The passphrase of the private key, if it has one
+/// * `root` - The directory on the host which mirrors the data location, e.g. `/srv/save-sync`
+/// * `known_hosts` - The known_hosts file the key of the host has to be in. Defaults to `~/.ssh/known_hosts`
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "SftpConfig::default_port")]
+    pub port: u16,
+    pub user: String,
+    pub key_path: PathBuf,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    pub root: String,
+    #[serde(default)]
+    pub known_hosts: Option<PathBuf>,
+}
+
+impl SftpConfig {
+    fn default_port() -> u16 {
+        22
+    }
+}
+
+/// Stores blobs as files below a directory on a remote host, laid out the same way `LocalStorage` would
+///
+/// The connection is only established once it's needed, and is shared by every thread.
+pub struct SftpStorage {
+    config: SftpConfig,
+    connection: Mutex<Option<Connection>>,
+}
+
+struct Connection {
+    // The SFTP channel can't outlive the session it was opened on
+    sftp: Sftp,
+    _session: Session,
+}
+
+impl std::fmt::Debug for SftpStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SftpStorage")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl SftpStorage {
+    pub fn new(config: SftpConfig) -> SftpStorage {
+        SftpStorage {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Where `key` is stored on the host. Remote paths always use `/`, whatever this machine uses
+    fn remote_path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        // Rejects keys which would escape the root
+        key_to_path(key)?;
+
+        Ok(PathBuf::from(format!(
+            "{}/{}",
+            self.config.root.trim_end_matches('/'),
+            key
+        )))
+    }
+
+    fn connect(&self) -> Result<Connection, StorageError> {
+        let config = &self.config;
+        let stream = TcpStream::connect((config.host.as_str(), config.port))?;
+
+        let mut session = Session::new()?;
+        session.set_tcp_stream(stream);
+        session.handshake()?;
+
+        self.verify_host(&session)?;
+
+        session.userauth_pubkey_file(
+            &config.user,
+            None,
+            &config.key_path,
+            config.passphrase.as_deref(),
+        )?;
+
+        Ok(Connection {
+            sftp: session.sftp()?,
+            _session: session,
+        })
+    }
+
+    /// Makes sure that we are talking to the host we think we are talking to
+    fn verify_host(&self, session: &Session) -> Result<(), StorageError> {
+        let known_hosts_path = match &self.config.known_hosts {
+            Some(path) => path.clone(),
+            None => directories::BaseDirs::new()
+                .map(|dirs| dirs.home_dir().join(".ssh").join("known_hosts"))
+                .ok_or_else(|| {
+                    StorageError::RequestError("Unable to find ~/.ssh/known_hosts".to_string())
+                })?,
+        };
+
+        let mut known_hosts = session.known_hosts()?;
+        known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)?;
+
+        let (key, _) = session.host_key().ok_or_else(|| {
+            StorageError::RequestError(format!("{} didn't present a host key", self.config.host))
+        })?;
+
+        match known_hosts.check_port(&self.config.host, self.config.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::NotFound => Err(StorageError::RequestError(format!(
+                "{} is not in {}",
+                self.config.host,
+                known_hosts_path.to_string_lossy()
+            ))),
+            _ => Err(StorageError::RequestError(format!(
+                "The host key of {} does not match the one in {}",
+                self.config.host,
+                known_hosts_path.to_string_lossy()
+            ))),
+        }
+    }
+
+    /// The SFTP channel, which is opened on first use
+    fn sftp(&self) -> Result<SftpGuard<'_>, StorageError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        if connection.is_none() {
+            *connection = Some(self.connect()?);
+        }
+
+        Ok(SftpGuard(connection))
+    }
+
+    /// Creates every directory between the root and `path`
+    fn create_parents(sftp: &Sftp, root: &str, path: &Path) -> Result<(), StorageError> {
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+
+        if sftp.stat(parent).map(|stat| stat.is_dir()).unwrap_or(false) {
+            return Ok(());
+        }
+
+        let mut current = PathBuf::from(root.trim_end_matches('/'));
+        let relative = parent
+            .strip_prefix(&current)
+            .unwrap_or(parent)
+            .to_path_buf();
+
+        for component in relative.iter() {
+            current.push(component);
+
+            if let Err(err) = sftp.mkdir(&current, 0o755) {
+                // Another thread may have beaten us to it
+                if !sftp
+                    .stat(&current)
+                    .map(|stat| stat.is_dir())
+                    .unwrap_or(false)
+                {
+                    return Err(err.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes whatever `write` writes to a temporary file next to `path`, and then moves it into place,
+    /// so that an interrupted upload never leaves a truncated blob behind
+    fn upload<F>(&self, key: &str, write: F) -> Result<(), StorageError>
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()>,
+    {
+        let path = self.remote_path(key)?;
+        let tmp = PathBuf::from(format!("{}.save-sync-tmp", path.to_string_lossy()));
+        let guard = self.sftp()?;
+        let sftp = guard.sftp();
+
+        Self::create_parents(sftp, &self.config.root, &path)?;
+
+        let mut file = sftp.create(&tmp)?;
+        write(&mut file)?;
+        drop(file);
+
+        let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+        let renamed = sftp.rename(&tmp, &path, Some(flags)).or_else(|_| {
+            // Servers which speak version 3 of the protocol, like OpenSSH, won't rename onto an existing file
+            sftp.unlink(&path)?;
+            sftp.rename(&tmp, &path, None)
+        });
+
+        if let Err(err) = renamed {
+            let _ = sftp.unlink(&tmp);
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    /// Hands whatever is stored under `key` to `read`
+    fn download<F, T>(&self, key: &str, read: F) -> Result<T, StorageError>
+    where
+        F: FnOnce(&mut dyn Read) -> io::Result<T>,
+    {
+        let path = self.remote_path(key)?;
+        let guard = self.sftp()?;
+
+        let mut file = match guard.sftp().open(&path) {
+            Ok(file) => file,
+            Err(err) if err.code() == NO_SUCH_FILE => {
+                return Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(read(&mut file)?)
+    }
+
+    fn collect_keys(
+        sftp: &Sftp,
+        dir: &Path,
+        key_prefix: &str,
+        prefix: &str,
+        keys: &mut Vec<String>,
+    ) -> Result<(), StorageError> {
+        let entries = match sftp.readdir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.code() == NO_SUCH_FILE => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for (path, stat) in entries {
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let key = format!("{}{}", key_prefix, name);
+
+            if stat.is_dir() {
+                // Only descend into directories which can contain keys with prefix
+                if key.starts_with(prefix) || prefix.starts_with(&format!("{}/", key)) {
+                    Self::collect_keys(sftp, &path, &format!("{}/", key), prefix, keys)?;
+                }
+            } else if stat.is_file() && key.starts_with(prefix) && !key.ends_with(".save-sync-tmp")
+            {
+                keys.push(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes `dir` along with everything inside of it
+    fn remove_dir_all(sftp: &Sftp, dir: &Path) -> Result<(), StorageError> {
+        let entries = match sftp.readdir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.code() == NO_SUCH_FILE => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for (path, stat) in entries {
+            match stat.is_dir() {
+                true => Self::remove_dir_all(sftp, &path)?,
+                false => sftp.unlink(&path)?,
+            }
+        }
+
+        Ok(sftp.rmdir(dir)?)
+    }
+
+    /// Removes the directories between `path` and the root which are empty now
+    fn prune(&self, sftp: &Sftp, path: &Path) {
+        let root = Path::new(self.config.root.trim_end_matches('/'));
+        let mut current = path.parent();
+
+        while let Some(dir) = current {
+            // rmdir refuses to remove directories which aren't empty
+            if dir == root || !dir.starts_with(root) || sftp.rmdir(dir).is_err() {
+                break;
+            }
+
+            current = dir.parent();
+        }
+    }
+}
+
+/// Keeps the connection locked for as long as its SFTP channel is in use
+struct SftpGuard<'a>(MutexGuard<'a, Option<Connection>>);
+
+impl SftpGuard<'_> {
+    fn sftp(&self) -> &Sftp {
+        // sftp() never hands out a guard without a connection
+        &self.0.as_ref().expect("connected").sftp
+    }
+}
+
+impl StorageBackend for SftpStorage {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        self.upload(key, |file| file.write_all(data))
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.download(key, |file| {
+            let mut data = vec![];
+            file.read_to_end(&mut data)?;
+            Ok(data)
+        })
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        // Start at the deepest directory which contains every key with prefix
+        let (dir, key_prefix) = match prefix.rfind('/') {
+            Some(i) => (self.remote_path(&prefix[..i])?, &prefix[..=i]),
+            None => (PathBuf::from(&self.config.root), ""),
+        };
+
+        let guard = self.sftp()?;
+        let mut keys = vec![];
+        Self::collect_keys(guard.sftp(), &dir, key_prefix, prefix, &mut keys)?;
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.remote_path(key)?;
+        let guard = self.sftp()?;
+
+        match guard.sftp().unlink(&path) {
+            Err(err) if err.code() != NO_SUCH_FILE => Err(err.into()),
+            _ => {
+                self.prune(guard.sftp(), &path);
+                Ok(())
+            }
+        }
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        let path = self.remote_path(key)?;
+        let guard = self.sftp()?;
+
+        match guard.sftp().stat(&path) {
+            Ok(stat) => Ok(stat.is_file()),
+            Err(err) if err.code() == NO_SUCH_FILE => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn size(&self, key: &str) -> Result<u64, StorageError> {
+        let path = self.remote_path(key)?;
+        let stat = self.sftp()?.sftp().stat(&path);
+
+        match stat {
+            Ok(stat) if stat.is_file() => match stat.size {
+                Some(size) => Ok(size),
+                None => Ok(self.get(key)?.len() as u64),
+            },
+            Ok(_) => Err(StorageError::NotFound(key.to_string())),
+            Err(err) if err.code() == NO_SUCH_FILE => Err(StorageError::NotFound(key.to_string())),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let file = std::fs::File::open(path)?;
+        let mut reader = ProgressReader::new(file, Task::Copying, progress);
+        let mut total = 0;
+        progress.path(Task::Copying, path);
+
+        self.upload(key, |remote| {
+            total = io::copy(&mut reader, remote)?;
+            Ok(())
+        })?;
+
+        Ok(total)
+    }
+
+    fn get_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        progress.path(Task::Copying, path);
+
+        self.download(key, |remote| {
+            let mut total = 0;
+
+            crate::atomic::write_with(&path, |file| {
+                let mut writer = ProgressWriter::new(file, Task::Copying, progress);
+                total = io::copy(remote, &mut writer)?;
+                Ok(())
+            })?;
+
+            Ok(total)
+        })
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<(), StorageError> {
+        // A whole directory can be removed at once
+        if let Some(dir) = prefix.strip_suffix('/') {
+            let path = self.remote_path(dir)?;
+            let guard = self.sftp()?;
+
+            Self::remove_dir_all(guard.sftp(), &path)?;
+            self.prune(guard.sftp(), &path);
+            return Ok(());
+        }
+
+        for key in self.list(prefix)? {
+            self.delete(&key)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use std::env;
+    use tempfile::TempDir;
+
+    /// The remote tests need an sshd to talk to, e.g.
+    ///
+    /// ```sh
+    /// ssh-keygen -t ed25519 -N "" -f /tmp/save-sync-key
+    /// docker run -d -p 2222:22 -v /tmp/save-sync-key.pub:/home/saves/.ssh/keys/id.pub:ro \
+    ///     atmoz/sftp saves::1001::upload
+    /// ssh-keyscan -p 2222 127.0.0.1 > /tmp/save-sync-known-hosts
+    ///
+    /// SAVE_SYNC_SFTP_HOST=127.0.0.1 SAVE_SYNC_SFTP_PORT=2222 SAVE_SYNC_SFTP_USER=saves \
+    /// SAVE_SYNC_SFTP_KEY=/tmp/save-sync-key SAVE_SYNC_SFTP_ROOT=/upload \
+    /// SAVE_SYNC_SFTP_KNOWN_HOSTS=/tmp/save-sync-known-hosts cargo test sftp -- --ignored
+    /// ```
+    fn remote_config() -> SftpConfig {
+        let var = |name: &str| env::var(name).unwrap_or_else(|_| panic!("{} is not set", name));
+
+        SftpConfig {
+            host: var("SAVE_SYNC_SFTP_HOST"),
+            port: var("SAVE_SYNC_SFTP_PORT").parse().unwrap(),
+            user: var("SAVE_SYNC_SFTP_USER"),
+            key_path: PathBuf::from(var("SAVE_SYNC_SFTP_KEY")),
+            passphrase: None,
+            root: format!("{}/{}", var("SAVE_SYNC_SFTP_ROOT"), uuid::Uuid::new_v4()),
+            known_hosts: Some(PathBuf::from(var("SAVE_SYNC_SFTP_KNOWN_HOSTS"))),
+        }
+    }
+
+    #[test]
+    fn config_has_defaults() {
+        let toml_str = r#"
+            host = "nas.local"
+            user = "saves"
+            key_path = "/home/saves/.ssh/id_ed25519"
+            root = "/srv/save-sync"
+        "#;
+
+        let config: SftpConfig = toml::from_str(toml_str).unwrap();
+
+        assert_eq!(config.port, 22);
+        assert_eq!(config.passphrase, None);
+        assert_eq!(config.known_hosts, None);
+    }
+
+    #[test]
+    fn remote_paths_mirror_the_data_location() {
+        let storage = SftpStorage::new(SftpConfig {
+            host: "nas.local".to_string(),
+            port: 22,
+            user: "saves".to_string(),
+            key_path: PathBuf::from("id_ed25519"),
+            passphrase: None,
+            root: "/srv/save-sync/".to_string(),
+            known_hosts: None,
+        });
+
+        let path = storage.remote_path("uuid/game/00.sav").unwrap();
+        let escaping = storage.remote_path("uuid/../../etc/passwd");
+
+        assert_eq!(path, PathBuf::from("/srv/save-sync/uuid/game/00.sav"));
+        assert!(matches!(escaping, Err(StorageError::InvalidKey(_))));
+    }
+
+    #[test]
+    #[ignore]
+    fn put_get_list_delete() {
+        let storage = SftpStorage::new(remote_config());
+
+        storage.put("uuid/game/00.sav", b"first").unwrap();
+        storage.put("uuid/game/sub dir/01.sav", b"second").unwrap();
+        storage.put("chunks/ab/ab00", b"chunk").unwrap();
+
+        let actual = storage.get("uuid/game/sub dir/01.sav").unwrap();
+        let listed = storage.list("uuid/").unwrap();
+        let exists = storage.exists("uuid/game/00.sav").unwrap();
+
+        storage.delete("uuid/game/00.sav").unwrap();
+        storage.delete("uuid/missing.sav").unwrap();
+        let missing = storage.get("uuid/game/00.sav");
+
+        storage.delete_prefix("uuid/").unwrap();
+        let left = storage.list("").unwrap();
+        storage.delete_prefix("chunks/").unwrap();
+
+        assert_eq!(actual, b"second");
+        assert_eq!(listed, vec!["uuid/game/00.sav", "uuid/game/sub dir/01.sav"]);
+        assert!(exists);
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+        assert_eq!(left, vec!["chunks/ab/ab00"]);
+    }
+
+    #[test]
+    #[ignore]
+    fn put_and_get_file() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let storage = SftpStorage::new(remote_config());
+
+        let source = tmp_dir.join("00.sav");
+        let target = tmp_dir.join("restored.sav");
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&source, &data).unwrap();
+
+        let put = storage
+            .put_file("uuid/00.sav", &source, &NoProgress)
+            .unwrap();
+        let got = storage
+            .get_file("uuid/00.sav", &target, &NoProgress)
+            .unwrap();
+        let restored = std::fs::read(&target).unwrap();
+        storage.delete_prefix("uuid/").unwrap();
+
+        test_dir.close().unwrap();
+        assert_eq!(put, data.len() as u64);
+        assert_eq!(got, data.len() as u64);
+        assert_eq!(restored, data);
+    }
+}
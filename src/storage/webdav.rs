@@ -0,0 +1,846 @@
+use super::{xml_unescape, StorageBackend, StorageError, PATH_ENCODE};
+use crate::atomic;
+use crate::progress::{NoProgress, Progress, ProgressReader, Task};
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use twox_hash::XxHash64;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/></d:prop></d:propfind>"#;
+
+/// Describes a collection on a WebDAV server like Nextcloud
+///
+/// # Properties
+/// * `url` - The collection backups are stored in, e.g. `https://cloud.example.com/remote.php/dav/files/alice/save-sync`
+/// * `username` - Sent along with `password` using HTTP Basic authentication
+/// * `password` - An app password is preferable to the password of the account
+/// * `uploads_url` - Where uploads in parts can be staged, e.g. `https://cloud.example.com/remote.php/dav/uploads/alice`.
+///   Servers without Nextcloud's chunked uploads should leave this unset
+/// * `part_size` - Files which are larger than this are uploaded in parts of this size, when `uploads_url` is set
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub uploads_url: Option<String>,
+    #[serde(default = "WebDavConfig::default_part_size")]
+    pub part_size: u64,
+}
+
+impl WebDavConfig {
+    fn default_part_size() -> u64 {
+        10 * 1024 * 1024
+    }
+}
+
+/// Stores blobs as files on a WebDAV server
+///
+/// Collections are created as they are needed. Large files are uploaded in parts if the server supports
+/// Nextcloud's chunked uploads, so that an interrupted upload continues where it left off.
+#[derive(Debug)]
+pub struct WebDavStorage {
+    config: WebDavConfig,
+    agent: ureq::Agent,
+}
+
+/// A single `<response>` of a PROPFIND
+#[derive(Debug, PartialEq, Eq)]
+struct DavEntry {
+    /// Percent-decoded path of the resource, without the trailing `/` of collections
+    path: String,
+    is_collection: bool,
+    size: Option<u64>,
+}
+
+impl WebDavStorage {
+    pub fn new(config: WebDavConfig) -> WebDavStorage {
+        WebDavStorage {
+            config,
+            agent: ureq::agent(),
+        }
+    }
+
+    fn url_for(&self, key: &str) -> String {
+        let base = self.config.url.trim_end_matches('/');
+
+        match key.is_empty() {
+            true => base.to_string(),
+            false => format!("{}/{}", base, utf8_percent_encode(key, PATH_ENCODE)),
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let mut request = self.agent.request(method, url);
+        request.auth(&self.config.username, &self.config.password);
+
+        request
+    }
+
+    /// Turns responses which never made it to the server into errors
+    fn sent(response: ureq::Response) -> Result<ureq::Response, StorageError> {
+        match response.synthetic_error() {
+            Some(err) => Err(StorageError::RequestError(err.to_string())),
+            None => Ok(response),
+        }
+    }
+
+    /// Turns any unsuccessful response into an error
+    fn check(response: ureq::Response, key: &str) -> Result<ureq::Response, StorageError> {
+        let response = Self::sent(response)?;
+
+        match response.status() {
+            200..=299 => Ok(response),
+            404 => Err(StorageError::NotFound(key.to_string())),
+            status => {
+                let body = response.into_string().unwrap_or_default();
+                Err(StorageError::UnexpectedResponse(status, body))
+            }
+        }
+    }
+
+    /// Creates `url` as a collection. Collections which already exist are fine
+    fn mkcol(&self, url: &str) -> Result<(), StorageError> {
+        let response = Self::sent(self.request("MKCOL", url).call())?;
+
+        match response.status() {
+            // 405 Method Not Allowed means that there already is something at url
+            200..=299 | 405 => Ok(()),
+            status => {
+                let body = response.into_string().unwrap_or_default();
+                Err(StorageError::UnexpectedResponse(status, body))
+            }
+        }
+    }
+
+    /// Creates the base collection and every collection between it and `key`
+    fn create_parents(&self, key: &str) -> Result<(), StorageError> {
+        self.mkcol(&self.url_for(""))?;
+
+        let parts: Vec<&str> = key.split('/').collect();
+        for i in 1..parts.len() {
+            self.mkcol(&self.url_for(&parts[..i].join("/")))?;
+        }
+
+        Ok(())
+    }
+
+    /// PUTs whatever `body` returns to `url`. The parents of `key` are only created once the server has
+    /// complained about them missing, which saves a round trip per parent for every other upload.
+    fn put_with<'r, F>(&self, key: &str, url: &str, len: u64, body: F) -> Result<(), StorageError>
+    where
+        F: Fn(bool) -> io::Result<Box<dyn Read + 'r>>,
+    {
+        let send = |retry: bool| -> Result<ureq::Response, StorageError> {
+            let response = self
+                .request("PUT", url)
+                .set("Content-Length", &len.to_string())
+                .send(body(retry)?);
+
+            Self::sent(response)
+        };
+
+        let mut response = send(false)?;
+
+        // 409 Conflict is what servers answer with when the parent collection is missing
+        if response.status() == 409 {
+            self.create_parents(key)?;
+            response = send(true)?;
+        }
+
+        Self::check(response, key).map(|_| ())
+    }
+
+    /// Every resource directly inside of the collection at `url`, or `None` if there is no such collection
+    fn propfind(&self, url: &str) -> Result<Option<Vec<DavEntry>>, StorageError> {
+        let response = self
+            .request("PROPFIND", &format!("{}/", url.trim_end_matches('/')))
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND_BODY);
+
+        let xml = match Self::check(response, url) {
+            Ok(response) => response.into_string()?,
+            Err(StorageError::NotFound(_)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let own_path = url_path(url);
+        let entries = multistatus(&xml)
+            .into_iter()
+            .filter(|entry| entry.path != own_path)
+            .collect();
+
+        Ok(Some(entries))
+    }
+
+    fn collect_keys(
+        &self,
+        dir: &str,
+        prefix: &str,
+        keys: &mut Vec<String>,
+    ) -> Result<(), StorageError> {
+        let base = url_path(&self.config.url);
+        let entries = match self.propfind(&self.url_for(dir))? {
+            Some(entries) => entries,
+            None => return Ok(()),
+        };
+
+        for entry in entries {
+            let key = match entry.path.strip_prefix(&base) {
+                Some(key) => key.trim_start_matches('/').to_string(),
+                None => continue,
+            };
+
+            if entry.is_collection {
+                // Only descend into collections which can contain keys with prefix
+                if key.starts_with(prefix) || prefix.starts_with(&format!("{}/", key)) {
+                    self.collect_keys(&key, prefix, keys)?;
+                }
+            } else if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Uploads `path` in parts to a collection in `uploads_url`, and has the server put them together at `key`.
+    ///
+    /// The name of the collection only depends on `key` and the size and modification time of the file,
+    /// so parts which were uploaded by an earlier attempt are skipped.
+    fn put_file_in_parts(
+        &self,
+        uploads_url: &str,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let metadata = path.metadata()?;
+        let len = metadata.len();
+        let part_size = self.config.part_size.max(1);
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(key.as_bytes());
+        hasher.write(&len.to_le_bytes());
+        hasher.write(&mtime.to_le_bytes());
+
+        let upload_url = format!(
+            "{}/save-sync-{:016x}",
+            uploads_url.trim_end_matches('/'),
+            hasher.finish()
+        );
+        self.mkcol(&upload_url)?;
+
+        let uploaded = self.propfind(&upload_url)?.unwrap_or_default();
+        let mut offset = 0;
+
+        while offset < len {
+            let part_len = part_size.min(len - offset);
+            // Parts are put together in the order of their names
+            let name = format!("{:016}", offset);
+            let part_path = format!("{}/{}", url_path(&upload_url), name);
+
+            let is_uploaded = uploaded
+                .iter()
+                .any(|entry| entry.path == part_path && entry.size == Some(part_len));
+
+            if is_uploaded {
+                progress.advance(Task::Copying, part_len);
+            } else {
+                let part_key = format!("{}/{}", key, name);
+                let part_url = format!("{}/{}", upload_url, name);
+
+                self.put_with(&part_key, &part_url, part_len, |retry| {
+                    let mut file = File::open(path)?;
+                    file.seek(SeekFrom::Start(offset))?;
+                    let part = file.take(part_len);
+
+                    Ok(match retry {
+                        true => Box::new(ProgressReader::new(part, Task::Copying, &NoProgress)),
+                        false => Box::new(ProgressReader::new(part, Task::Copying, progress)),
+                    })
+                })?;
+            }
+
+            offset += part_len;
+        }
+
+        let assemble = |retry: bool| -> Result<ureq::Response, StorageError> {
+            if retry {
+                self.create_parents(key)?;
+            }
+
+            let response = self
+                .request("MOVE", &format!("{}/.file", upload_url))
+                .set("Destination", &self.url_for(key))
+                .set("Overwrite", "T")
+                .call();
+
+            Self::sent(response)
+        };
+
+        let mut response = assemble(false)?;
+
+        if response.status() == 409 {
+            response = assemble(true)?;
+        }
+
+        Self::check(response, key)?;
+        Ok(len)
+    }
+}
+
+impl StorageBackend for WebDavStorage {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        self.put_with(key, &self.url_for(key), data.len() as u64, |_| {
+            Ok(Box::new(data))
+        })
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let response = self.request("GET", &self.url_for(key)).call();
+        let mut data = vec![];
+        Self::check(response, key)?
+            .into_reader()
+            .read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        // Start at the deepest collection which contains every key with prefix
+        let dir = match prefix.rfind('/') {
+            Some(i) => &prefix[..i],
+            None => "",
+        };
+
+        let mut keys = vec![];
+        self.collect_keys(dir, prefix, &mut keys)?;
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let response = self.request("DELETE", &self.url_for(key)).call();
+
+        match Self::check(response, key) {
+            Err(StorageError::NotFound(_)) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        let response = self.request("HEAD", &self.url_for(key)).call();
+
+        match Self::check(response, key) {
+            Ok(_) => Ok(true),
+            Err(StorageError::NotFound(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn size(&self, key: &str) -> Result<u64, StorageError> {
+        let response = Self::check(self.request("HEAD", &self.url_for(key)).call(), key)?;
+
+        // Servers which don't say how large a file is have to be asked for the whole file instead
+        match response.header("Content-Length").map(str::parse) {
+            Some(Ok(len)) => Ok(len),
+            _ => Ok(self.get(key)?.len() as u64),
+        }
+    }
+
+    fn put_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let len = path.metadata()?.len();
+        progress.path(Task::Copying, path);
+
+        if let Some(uploads_url) = &self.config.uploads_url {
+            if len > self.config.part_size {
+                return self.put_file_in_parts(uploads_url, key, path, progress);
+            }
+        }
+
+        self.put_with(key, &self.url_for(key), len, |retry| {
+            let file = File::open(path)?;
+
+            // Whatever was sent before the server turned us down has already been reported
+            Ok(match retry {
+                true => Box::new(ProgressReader::new(file, Task::Copying, &NoProgress)),
+                false => Box::new(ProgressReader::new(file, Task::Copying, progress)),
+            })
+        })?;
+
+        Ok(len)
+    }
+
+    fn get_file(
+        &self,
+        key: &str,
+        path: &Path,
+        progress: &dyn Progress,
+    ) -> Result<u64, StorageError> {
+        let response = self.request("GET", &self.url_for(key)).call();
+        let reader = Self::check(response, key)?.into_reader();
+        let mut reader = ProgressReader::new(reader, Task::Copying, progress);
+        let mut total = 0;
+        progress.path(Task::Copying, path);
+
+        atomic::write_with(&path, |file| {
+            total = io::copy(&mut reader, file)?;
+            Ok(())
+        })?;
+
+        Ok(total)
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<(), StorageError> {
+        // A whole collection can be deleted at once
+        if let Some(dir) = prefix.strip_suffix('/') {
+            return self.delete(dir);
+        }
+
+        for key in self.list(prefix)? {
+            self.delete(&key)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The percent-decoded path of `url`, without a trailing `/`
+fn url_path(url: &str) -> String {
+    let without_scheme = match url.find("://") {
+        Some(i) => {
+            let rest = &url[i + 3..];
+            rest.find('/').map(|j| &rest[j..]).unwrap_or("")
+        }
+        None => url,
+    };
+
+    percent_decode_str(without_scheme)
+        .decode_utf8_lossy()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// The `<response>`s of a `<multistatus>`
+fn multistatus(xml: &str) -> Vec<DavEntry> {
+    elements(xml, "response")
+        .into_iter()
+        .filter_map(|response| {
+            let href = elements(response, "href").into_iter().next()?;
+            let resource_type = elements(response, "resourcetype");
+            let size = elements(response, "getcontentlength").into_iter().next();
+
+            Some(DavEntry {
+                path: url_path(&xml_unescape(href.trim())),
+                is_collection: resource_type
+                    .iter()
+                    .any(|inner| !elements(inner, "collection").is_empty()),
+                size: size.and_then(|size| size.trim().parse().ok()),
+            })
+        })
+        .collect()
+}
+
+/// The contents of every element called `name` in `xml`, whatever namespace prefix it has.
+/// WebDAV responses are simple enough not to need a real XML parser
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = vec![];
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let tag = &rest[..end];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next();
+        let local_name = tag_name.map(|tag| tag.rsplit(':').next().unwrap_or(tag));
+
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('/') || local_name != Some(name) {
+            continue;
+        }
+
+        if tag.ends_with('/') {
+            found.push("");
+            continue;
+        }
+
+        // Elements we look for are never nested inside of themselves
+        let mut search = rest;
+        let mut offset = 0;
+
+        while let Some(close) = search.find("</") {
+            let after = &search[close + 2..];
+            let close_end = after.find('>').unwrap_or(after.len());
+            let close_name = &after[..close_end];
+
+            if close_name.rsplit(':').next() == Some(name) {
+                found.push(&rest[..offset + close]);
+                rest = &rest[offset + close..];
+                break;
+            }
+
+            offset += close + 2;
+            search = after;
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use tempfile::TempDir;
+    use tiny_http::{Header, Response, Server};
+
+    /// `alice:secret`, encoded for HTTP Basic authentication
+    const AUTHORIZATION: &str = "Basic YWxpY2U6c2VjcmV0";
+
+    #[derive(Debug, Default)]
+    struct Dav {
+        files: BTreeMap<String, Vec<u8>>,
+        collections: BTreeSet<String>,
+        /// Part uploads which have been received, in order
+        parts: Vec<String>,
+        /// The next upload of a part with this name fails
+        fail_part: Option<String>,
+    }
+
+    impl Dav {
+        fn parent(path: &str) -> &str {
+            path.rsplit_once('/')
+                .map(|(parent, _)| parent)
+                .unwrap_or("")
+        }
+
+        fn has_parent(&self, path: &str) -> bool {
+            let parent = Self::parent(path);
+            parent.is_empty() || self.collections.contains(parent)
+        }
+
+        fn remove(&mut self, path: &str) -> bool {
+            let inside = format!("{}/", path);
+            let before = self.files.len() + self.collections.len();
+
+            self.files
+                .retain(|p, _| p != path && !p.starts_with(&inside));
+            self.collections
+                .retain(|p| p != path && !p.starts_with(&inside));
+
+            before != self.files.len() + self.collections.len()
+        }
+
+        fn propfind(&self, path: &str) -> Option<String> {
+            if !self.collections.contains(path) {
+                return None;
+            }
+
+            let inside = format!("{}/", path);
+            let mut xml = String::from(r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:">"#);
+            let response = |href: &str, props: &str| {
+                let href = utf8_percent_encode(href, PATH_ENCODE).to_string();
+                format!(
+                    "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+                     <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                    href.replace('&', "&amp;"),
+                    props
+                )
+            };
+
+            xml.push_str(&response(
+                &inside,
+                "<d:resourcetype><d:collection/></d:resourcetype>",
+            ));
+
+            for collection in &self.collections {
+                if Self::parent(collection) == path {
+                    let props = "<d:resourcetype>\n<d:collection />\n</d:resourcetype>";
+                    xml.push_str(&response(&format!("{}/", collection), props));
+                }
+            }
+
+            for (file, data) in &self.files {
+                if Self::parent(file) == path {
+                    let props = format!(
+                        "<d:resourcetype/><d:getcontentlength>{}</d:getcontentlength>",
+                        data.len()
+                    );
+                    xml.push_str(&response(file, &props));
+                }
+            }
+
+            xml.push_str("</d:multistatus>");
+            Some(xml)
+        }
+    }
+
+    /// A tiny WebDAV server which keeps everything in memory, and supports Nextcloud's chunked uploads
+    fn fake_dav() -> (WebDavConfig, Arc<Mutex<Dav>>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let origin = format!("http://{}", server.server_addr());
+        let dav = Arc::new(Mutex::new(Dav::default()));
+        let state = dav.clone();
+
+        {
+            let mut dav = dav.lock().unwrap();
+            dav.collections.insert("/dav".to_string());
+            dav.collections.insert("/dav/files".to_string());
+            dav.collections.insert("/dav/uploads".to_string());
+        }
+
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = vec![];
+                request.as_reader().read_to_end(&mut body).unwrap();
+
+                let header = |name: &str| {
+                    request
+                        .headers()
+                        .iter()
+                        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+                        .map(|h| h.value.as_str().to_string())
+                };
+
+                if header("Authorization").as_deref() != Some(AUTHORIZATION) {
+                    request.respond(Response::empty(401)).unwrap();
+                    continue;
+                }
+
+                let decode = |s: &str| percent_decode_str(s).decode_utf8().unwrap().to_string();
+                let path = decode(request.url()).trim_end_matches('/').to_string();
+                let mut dav = state.lock().unwrap();
+
+                let status = |code: u16| Response::from_data(vec![]).with_status_code(code);
+                let response = match request.method().as_str() {
+                    "MKCOL" if dav.collections.contains(&path) => status(405),
+                    "MKCOL" if !dav.has_parent(&path) => status(409),
+                    "MKCOL" => {
+                        dav.collections.insert(path);
+                        status(201)
+                    }
+                    "PUT" if !dav.has_parent(&path) => status(409),
+                    "PUT"
+                        if dav
+                            .fail_part
+                            .as_ref()
+                            .is_some_and(|part| path.ends_with(part)) =>
+                    {
+                        dav.fail_part = None;
+                        status(500)
+                    }
+                    "PUT" => {
+                        if path.starts_with("/dav/uploads/") {
+                            dav.parts.push(path.clone());
+                        }
+                        dav.files.insert(path, body);
+                        status(201)
+                    }
+                    "GET" | "HEAD" => match dav.files.get(&path) {
+                        Some(data) => Response::from_data(data.clone()),
+                        None => status(404),
+                    },
+                    "DELETE" => match dav.remove(&path) {
+                        true => status(204),
+                        false => status(404),
+                    },
+                    "PROPFIND" => match dav.propfind(&path) {
+                        Some(xml) => Response::from_data(xml.into_bytes()).with_status_code(207),
+                        None => status(404),
+                    },
+                    "MOVE" if path.ends_with("/.file") => {
+                        let upload = Dav::parent(&path).to_string();
+                        let destination = url_path(&header("Destination").unwrap());
+
+                        if !dav.has_parent(&destination) {
+                            status(409)
+                        } else {
+                            let inside = format!("{}/", upload);
+                            let data: Vec<u8> = dav
+                                .files
+                                .iter()
+                                .filter(|(p, _)| p.starts_with(&inside))
+                                .flat_map(|(_, data)| data.clone())
+                                .collect();
+
+                            dav.remove(&upload);
+                            dav.files.insert(destination, data);
+                            status(201)
+                        }
+                    }
+                    _ => status(405),
+                };
+
+                let response = response
+                    .with_header("Content-Type: application/xml".parse::<Header>().unwrap());
+                request.respond(response).unwrap();
+            }
+        });
+
+        let config = WebDavConfig {
+            url: format!("{}/dav/files/save-sync", origin),
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            uploads_url: Some(format!("{}/dav/uploads", origin)),
+            part_size: 1024,
+        };
+
+        (config, dav)
+    }
+
+    #[test]
+    fn put_get_list_delete() {
+        let (config, dav) = fake_dav();
+        let storage = WebDavStorage::new(config);
+
+        storage.put("uuid/game/00.sav", b"first").unwrap();
+        storage
+            .put("uuid/game/sub dir/01 & 02.sav", b"second")
+            .unwrap();
+        storage.put("chunks/ab/ab00", b"chunk").unwrap();
+
+        let stored: Vec<String> = dav.lock().unwrap().files.keys().cloned().collect();
+        let actual = storage.get("uuid/game/sub dir/01 & 02.sav").unwrap();
+        let listed = storage.list("uuid/").unwrap();
+        let partial = storage.list("chunks/a").unwrap();
+        let exists = storage.exists("uuid/game/00.sav").unwrap();
+
+        storage.delete("uuid/game/00.sav").unwrap();
+        storage.delete("uuid/missing.sav").unwrap();
+        let missing = storage.get("uuid/game/00.sav");
+
+        storage.delete_prefix("uuid/").unwrap();
+        let left = storage.list("").unwrap();
+
+        assert_eq!(
+            stored,
+            vec![
+                "/dav/files/save-sync/chunks/ab/ab00",
+                "/dav/files/save-sync/uuid/game/00.sav",
+                "/dav/files/save-sync/uuid/game/sub dir/01 & 02.sav",
+            ]
+        );
+        assert_eq!(actual, b"second");
+        assert_eq!(
+            listed,
+            vec!["uuid/game/00.sav", "uuid/game/sub dir/01 & 02.sav"]
+        );
+        assert_eq!(partial, vec!["chunks/ab/ab00"]);
+        assert!(exists);
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+        assert_eq!(left, vec!["chunks/ab/ab00"]);
+        assert!(storage.list("nothing/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn interrupted_uploads_resume() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+        let (config, dav) = fake_dav();
+        let storage = WebDavStorage::new(config);
+
+        let source = tmp_dir.join("big.sav");
+        let target = tmp_dir.join("restored.sav");
+        let data: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&source, &data).unwrap();
+
+        // The second of three parts fails the first time around
+        dav.lock().unwrap().fail_part = Some(format!("{:016}", 1024));
+        let first = storage.put_file("uuid/big.sav", &source, &NoProgress);
+        let second = storage
+            .put_file("uuid/big.sav", &source, &NoProgress)
+            .unwrap();
+
+        let n = storage
+            .get_file("uuid/big.sav", &target, &NoProgress)
+            .unwrap();
+        let restored = std::fs::read(&target).unwrap();
+        let dav = dav.lock().unwrap();
+        let parts: Vec<&str> = dav
+            .parts
+            .iter()
+            .map(|part| &part[part.len() - 16..])
+            .collect();
+
+        test_dir.close().unwrap();
+        assert!(matches!(
+            first,
+            Err(StorageError::UnexpectedResponse(500, _))
+        ));
+        assert_eq!(second, 3000);
+        assert_eq!(n, 3000);
+        assert_eq!(restored, data);
+        // The first part is only uploaded once
+        assert_eq!(
+            parts,
+            vec!["0000000000000000", "0000000000001024", "0000000000002048"]
+        );
+        assert!(!dav.collections.iter().any(|c| c.contains("save-sync-")));
+    }
+
+    #[test]
+    fn wrong_credentials_are_rejected() {
+        let (mut config, _) = fake_dav();
+        config.password = "wrong".to_string();
+        let storage = WebDavStorage::new(config);
+
+        let result = storage.put("uuid/00.sav", b"data");
+
+        assert!(matches!(
+            result,
+            Err(StorageError::UnexpectedResponse(401, _))
+        ));
+    }
+
+    #[test]
+    fn multistatus_with_any_prefix() {
+        let xml = r#"<D:multistatus xmlns:D="DAV:">
+            <D:response><D:href>/dav/a%20b/</D:href>
+                <D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop></D:propstat>
+            </D:response>
+            <response xmlns="DAV:"><href>http://host/dav/a%20b/c&amp;d.sav</href>
+                <propstat><prop><resourcetype/><getcontentlength>12</getcontentlength></prop></propstat>
+            </response>
+        </D:multistatus>"#;
+
+        let expected = vec![
+            DavEntry {
+                path: "/dav/a b".to_string(),
+                is_collection: true,
+                size: None,
+            },
+            DavEntry {
+                path: "/dav/a b/c&d.sav".to_string(),
+                is_collection: false,
+                size: Some(12),
+            },
+        ];
+
+        assert_eq!(multistatus(xml), expected);
+    }
+}
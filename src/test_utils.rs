@@ -0,0 +1,23 @@
+//! Helpers which are shared by the tests of several modules
+
+use crate::context::SaveSync;
+use crate::models::{FileType, NewFile};
+use chrono::NaiveDateTime;
+
+/// Records a regular file of `size` bytes at `path` in the first Save
+pub fn create_file(ctx: &SaveSync, path: &str, size: i64, time: NaiveDateTime) {
+    ctx.db()
+        .create_file(NewFile {
+            file_path: path,
+            file_hash: &[0; 8],
+            save_id: 1,
+            created_at: time,
+            modified_at: time,
+            file_type: FileType::File.into(),
+            link_target: None,
+            permissions: None,
+            file_mtime: None,
+            file_size: Some(size),
+        })
+        .unwrap();
+}
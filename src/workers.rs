@@ -0,0 +1,178 @@
+use crate::archive::{Archive, ArchiveError, HASH_BUFFER_SIZE};
+use crate::atomic;
+use crate::config::Config;
+use crate::progress::Progress;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WorkerError {
+    #[error(transparent)]
+    ThreadPoolError(#[from] ThreadPoolBuildError),
+    #[error("Unable to obtain reference to the global static config")]
+    InaccessibleConfig,
+}
+
+/// A bounded pool of threads which hashes and copies files in parallel.
+///
+/// Every thread owns a buffer which is reused for every file that thread works on.
+///
+/// # Examples
+/// ```
+/// use save_sync::workers::Workers;
+///
+/// let workers = Workers::new(2).unwrap();
+/// let lengths = workers.map(&["a", "bb", "ccc"], |_buffer, s| s.len());
+///
+/// assert_eq!(workers.threads(), 2);
+/// assert_eq!(lengths, vec![1, 2, 3]);
+/// ```
+#[derive(Debug)]
+pub struct Workers {
+    pool: ThreadPool,
+}
+
+impl Workers {
+    /// Creates a pool with `threads` threads. 0 means one thread per CPU
+    pub fn new(threads: usize) -> Result<Workers, WorkerError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("save-sync-worker-{}", i))
+            .build()?;
+
+        Ok(Workers { pool })
+    }
+
+    /// Creates a pool with as many threads as the global config asks for
+    pub fn from_config() -> Result<Workers, WorkerError> {
+        let threads = Config::static_config()
+            .map_err(|_| WorkerError::InaccessibleConfig)?
+            .threads;
+
+        Self::new(threads)
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Calls `f` on every item in parallel. The results are in the same order as `items`.
+    ///
+    /// `f` is handed a buffer of `HASH_BUFFER_SIZE` bytes which belongs to the thread it runs on.
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&mut [u8], &T) -> R + Sync + Send,
+    {
+        self.pool.install(|| {
+            items
+                .par_iter()
+                .map_init(|| vec![0; HASH_BUFFER_SIZE], |buffer, item| f(buffer, item))
+                .collect()
+        })
+    }
+
+    /// Hashes every file in `paths` like `Archive::calc_hash_with` does
+    ///
+    /// The seed is passed in (see `Archive::hash_seed`) so that the threads don't fight over the config lock.
+    pub fn hash_files<P: AsRef<Path> + Sync>(
+        &self,
+        seed: u64,
+        paths: &[P],
+        progress: &dyn Progress,
+    ) -> Vec<Result<u64, ArchiveError>> {
+        self.map(paths, |buffer, path| {
+            Archive::calc_hash_with(path, seed, buffer, progress)
+        })
+    }
+
+    /// Atomically copies every `(from, to)` pair like `atomic::copy_with` does
+    pub fn copy_files<P, Q>(
+        &self,
+        pairs: &[(P, Q)],
+        progress: &dyn Progress,
+    ) -> Vec<io::Result<u64>>
+    where
+        P: AsRef<Path> + Sync,
+        Q: AsRef<Path> + Sync,
+    {
+        self.map(pairs, |buffer, (from, to)| {
+            atomic::copy_with(from, to, buffer, progress)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn hash_files_matches_calc_hash() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let mut paths = vec![];
+
+        for i in 0..8 {
+            let path: PathBuf = [tmp_dir, &PathBuf::from(format!("{}.sav", i))]
+                .iter()
+                .collect();
+            // Larger than a single buffer, so that the buffer is reused within a file too
+            let bytes: Vec<u8> = (0..HASH_BUFFER_SIZE * 2 + i)
+                .map(|_| rand::random())
+                .collect();
+            fs::write(&path, bytes).unwrap();
+            paths.push(path);
+        }
+
+        let seed = 1_912_251_925_143;
+        let workers = Workers::new(4).unwrap();
+        let actual = workers.hash_files(seed, &paths, &NoProgress);
+
+        let mut buffer = vec![0; 16];
+        let expected: Vec<u64> = paths
+            .iter()
+            .map(|path| Archive::calc_hash_with(path, seed, &mut buffer, &NoProgress).unwrap())
+            .collect();
+
+        test_dir.close().unwrap();
+        assert_eq!(actual.len(), expected.len());
+
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            assert_eq!(actual.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn copy_files_reports_each_failure() {
+        let test_dir = TempDir::new().unwrap();
+        let tmp_dir = test_dir.path();
+
+        let source: PathBuf = [tmp_dir, &PathBuf::from("00.sav")].iter().collect();
+        let missing: PathBuf = [tmp_dir, &PathBuf::from("missing.sav")].iter().collect();
+        let target1: PathBuf = [tmp_dir, &PathBuf::from("backup0.sav")].iter().collect();
+        let target2: PathBuf = [tmp_dir, &PathBuf::from("backup1.sav")].iter().collect();
+        fs::write(&source, "save data").unwrap();
+
+        let workers = Workers::new(2).unwrap();
+        let pairs = vec![(source, target1.clone()), (missing, target2.clone())];
+        let results = workers.copy_files(&pairs, &NoProgress);
+
+        let copied = fs::read_to_string(&target1).unwrap();
+        let exists = target2.exists();
+
+        test_dir.close().unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &9);
+        assert!(results[1].is_err());
+        assert_eq!(copied, "save data");
+        assert!(!exists);
+    }
+}